@@ -0,0 +1,272 @@
+use miniz_oxide::deflate::{compress_to_vec, compress_to_vec_zlib};
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    http::{ContentType, Header},
+    Request, Response,
+};
+use std::{env, io::Cursor};
+
+const COMPRESSION_LEVEL: u8 = 6;
+
+#[derive(Debug, PartialEq)]
+enum Encoding {
+    Gzip,
+    Deflate,
+}
+
+impl Encoding {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+        }
+    }
+
+    fn encode(&self, body: &[u8]) -> Vec<u8> {
+        match self {
+            Encoding::Gzip => gzip(body),
+            // HTTP's "deflate" is the zlib format, not a raw deflate stream
+            Encoding::Deflate => compress_to_vec_zlib(body, COMPRESSION_LEVEL),
+        }
+    }
+}
+
+/// Compresses response bodies with gzip or deflate, based on the client's `Accept-Encoding`
+pub struct ResponseCompression {
+    min_size: usize,
+}
+
+impl ResponseCompression {
+    pub fn new(min_size: usize) -> Self {
+        ResponseCompression { min_size }
+    }
+
+    /// Returns the fairing if `COMPRESSION_ENABLED` is set to true
+    pub fn from_env() -> Option<Self> {
+        let enabled = env::var("COMPRESSION_ENABLED")
+            .ok()
+            .and_then(|val| val.parse().ok())
+            .unwrap_or(false);
+
+        if !enabled {
+            return None;
+        }
+
+        // Small bodies are not worth the CPU time
+        let min_size = env::var("COMPRESSION_MIN_SIZE")
+            .ok()
+            .and_then(|val| val.parse().ok())
+            .unwrap_or(1024);
+
+        Some(ResponseCompression::new(min_size))
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for ResponseCompression {
+    fn info(&self) -> Info {
+        Info {
+            name: "Response compression",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        if response.headers().contains("Content-Encoding") {
+            return;
+        }
+
+        // Streamed bodies, like the live total event stream, never end, so they can't be buffered
+        let is_event_stream = response.content_type() == Some(ContentType::EventStream);
+        if is_event_stream || response.body().preset_size().is_none() {
+            return;
+        }
+
+        let encoding = match preferred_encoding(request.headers().get("Accept-Encoding")) {
+            Some(encoding) => encoding,
+            None => return,
+        };
+
+        let body = match response.body_mut().to_bytes().await {
+            Ok(body) => body,
+            Err(err) => {
+                eprintln!("Failed to read response body for compression: {:#?}", err);
+                return;
+            }
+        };
+
+        // Vary on the header, so caches do not serve compressed bodies to other clients
+        response.adjoin_header(Header::new("Vary", "Accept-Encoding"));
+
+        if body.len() < self.min_size {
+            response.set_sized_body(body.len(), Cursor::new(body));
+            return;
+        }
+
+        let compressed = encoding.encode(&body);
+        response.set_header(Header::new("Content-Encoding", encoding.as_str()));
+        response.set_sized_body(compressed.len(), Cursor::new(compressed));
+    }
+}
+
+/// Picks gzip over deflate, skipping any coding the client disabled with `q=0`.
+/// `*` accepts the codings that are not named on their own.
+fn preferred_encoding<'a>(accept_encoding: impl Iterator<Item = &'a str>) -> Option<Encoding> {
+    let mut accepted = Vec::new();
+    let mut disabled_codings = Vec::new();
+
+    for value in accept_encoding.flat_map(|header| header.split(',')) {
+        let mut parts = value.split(';').map(str::trim);
+        let coding = parts.next().unwrap_or_default().to_lowercase();
+        let disabled = parts.any(|param| {
+            param
+                .strip_prefix("q=")
+                .and_then(|q| q.parse::<f32>().ok())
+                .is_some_and(|q| q == 0.0)
+        });
+
+        if disabled {
+            disabled_codings.push(coding);
+        } else {
+            accepted.push(coding);
+        }
+    }
+
+    let is_accepted = |coding: &str| {
+        accepted.iter().any(|c| c == coding)
+            || (accepted.iter().any(|c| c == "*") && !disabled_codings.iter().any(|c| c == coding))
+    };
+
+    if is_accepted("gzip") {
+        Some(Encoding::Gzip)
+    } else if is_accepted("deflate") {
+        Some(Encoding::Deflate)
+    } else {
+        None
+    }
+}
+
+/// Wraps a raw deflate stream in a minimal gzip header and trailer (RFC 1952)
+fn gzip(body: &[u8]) -> Vec<u8> {
+    let deflated = compress_to_vec(body, COMPRESSION_LEVEL);
+
+    let mut gzipped = Vec::with_capacity(deflated.len() + 18);
+    // Magic number, deflate method, no flags, no mtime, no extra flags, unknown OS
+    gzipped.extend_from_slice(&[0x1f, 0x8b, 0x08, 0x00, 0, 0, 0, 0, 0x00, 0xff]);
+    gzipped.extend_from_slice(&deflated);
+    gzipped.extend_from_slice(&crc32(body).to_le_bytes());
+    gzipped.extend_from_slice(&(body.len() as u32).to_le_bytes());
+
+    gzipped
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffff_u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use miniz_oxide::inflate::{decompress_to_vec, decompress_to_vec_zlib};
+    use rocket::{get, http::Header, local::asynchronous::Client, routes};
+
+    #[get("/large")]
+    fn large() -> String {
+        "time track ".repeat(1000)
+    }
+
+    async fn client() -> Client {
+        let rocket = rocket::build()
+            .mount("/", routes![large])
+            .attach(ResponseCompression::new(1024));
+        Client::tracked(rocket).await.unwrap()
+    }
+
+    #[test]
+    fn crc32_matches_known_value() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn preferred_encoding_respects_quality() {
+        assert_eq!(
+            preferred_encoding(["deflate, gzip;q=0.5"].into_iter()),
+            Some(Encoding::Gzip)
+        );
+        assert_eq!(
+            preferred_encoding(["gzip;q=0, deflate"].into_iter()),
+            Some(Encoding::Deflate)
+        );
+        assert_eq!(
+            preferred_encoding(["gzip;q=0, *"].into_iter()),
+            Some(Encoding::Deflate)
+        );
+        assert_eq!(
+            preferred_encoding(["gzip;q=0, deflate;q=0, *"].into_iter()),
+            None
+        );
+        assert_eq!(preferred_encoding(["*"].into_iter()), Some(Encoding::Gzip));
+        assert_eq!(preferred_encoding(["br"].into_iter()), None);
+    }
+
+    #[rocket::async_test]
+    async fn large_response_is_gzipped_when_requested() {
+        let client = client().await;
+
+        let response = client
+            .get("/large")
+            .header(Header::new("Accept-Encoding", "gzip"))
+            .dispatch()
+            .await;
+
+        assert_eq!(response.headers().get_one("Content-Encoding"), Some("gzip"));
+        let body = response.into_bytes().await.unwrap();
+        assert!(
+            body.len() < 11_000,
+            "Expected a compressed body, got {} bytes",
+            body.len()
+        );
+
+        // Strip the 10 byte header and 8 byte trailer to inflate the deflate stream
+        let inflated = decompress_to_vec(&body[10..body.len() - 8]).unwrap();
+        assert_eq!(inflated, large().into_bytes());
+        let crc = u32::from_le_bytes(body[body.len() - 8..body.len() - 4].try_into().unwrap());
+        assert_eq!(crc, crc32(&inflated));
+    }
+
+    #[rocket::async_test]
+    async fn large_response_is_deflated_when_requested() {
+        let client = client().await;
+
+        let response = client
+            .get("/large")
+            .header(Header::new("Accept-Encoding", "deflate"))
+            .dispatch()
+            .await;
+
+        assert_eq!(
+            response.headers().get_one("Content-Encoding"),
+            Some("deflate")
+        );
+        let body = response.into_bytes().await.unwrap();
+        assert_eq!(decompress_to_vec_zlib(&body).unwrap(), large().into_bytes());
+    }
+
+    #[rocket::async_test]
+    async fn response_is_untouched_without_accept_encoding() {
+        let client = client().await;
+
+        let response = client.get("/large").dispatch().await;
+
+        assert_eq!(response.headers().get_one("Content-Encoding"), None);
+        assert_eq!(response.into_string().await.unwrap(), large());
+    }
+}
@@ -0,0 +1,133 @@
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    http::{Header, Method, Status},
+    Request, Response,
+};
+use std::{env, io::Cursor};
+
+const DEFAULT_EXPOSE_HEADERS: &str = "X-Total-Count, x-request-id, x-sitt-version-warning";
+const ALLOW_METHODS: &str = "GET, POST, PUT, DELETE, OPTIONS";
+const ALLOW_HEADERS: &str = "x-api-key, x-sitt-client-version, Content-Type";
+
+/// Adds CORS headers, so browser clients can call the API and read its custom response headers
+pub struct Cors {
+    allowed_origin: String,
+    expose_headers: String,
+}
+
+impl Cors {
+    pub fn new(allowed_origin: &str, expose_headers: &str) -> Self {
+        Cors {
+            allowed_origin: allowed_origin.to_string(),
+            expose_headers: expose_headers.to_string(),
+        }
+    }
+
+    /// Returns the fairing if `CORS_ENABLED` is set to true
+    pub fn from_env() -> Option<Self> {
+        let enabled = env::var("CORS_ENABLED")
+            .ok()
+            .and_then(|val| val.parse().ok())
+            .unwrap_or(false);
+
+        if !enabled {
+            return None;
+        }
+
+        let allowed_origin = env::var("CORS_ALLOWED_ORIGIN").unwrap_or_else(|_| String::from("*"));
+
+        // Headers a browser may read from responses, besides the CORS-safelisted ones
+        let expose_headers = env::var("CORS_EXPOSE_HEADERS")
+            .unwrap_or_else(|_| String::from(DEFAULT_EXPOSE_HEADERS));
+
+        Some(Cors::new(&allowed_origin, &expose_headers))
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for Cors {
+    fn info(&self) -> Info {
+        Info {
+            name: "CORS",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        response.set_header(Header::new(
+            "Access-Control-Allow-Origin",
+            self.allowed_origin.clone(),
+        ));
+        response.set_header(Header::new("Access-Control-Allow-Methods", ALLOW_METHODS));
+        response.set_header(Header::new("Access-Control-Allow-Headers", ALLOW_HEADERS));
+        if !self.expose_headers.is_empty() {
+            response.set_header(Header::new(
+                "Access-Control-Expose-Headers",
+                self.expose_headers.clone(),
+            ));
+        }
+
+        // There are no OPTIONS routes, so answer preflight requests here
+        if request.method() == Method::Options && response.status() == Status::NotFound {
+            response.set_status(Status::NoContent);
+            response.set_sized_body(0, Cursor::new(""));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::{get, local::asynchronous::Client, routes};
+
+    #[get("/projects")]
+    fn projects() -> &'static str {
+        "[]"
+    }
+
+    async fn client(cors: Cors) -> Client {
+        let rocket = rocket::build().mount("/", routes![projects]).attach(cors);
+        Client::tracked(rocket).await.unwrap()
+    }
+
+    #[rocket::async_test]
+    async fn response_exposes_the_custom_headers() {
+        let client = client(Cors::new("*", DEFAULT_EXPOSE_HEADERS)).await;
+
+        let response = client.get("/projects").dispatch().await;
+
+        let expose_headers = response
+            .headers()
+            .get_one("Access-Control-Expose-Headers")
+            .unwrap_or_default();
+        for header in ["X-Total-Count", "x-request-id", "x-sitt-version-warning"] {
+            assert!(
+                expose_headers.contains(header),
+                "Expected {} to be exposed, got: {}",
+                header,
+                expose_headers
+            );
+        }
+        assert_eq!(
+            response.headers().get_one("Access-Control-Allow-Origin"),
+            Some("*")
+        );
+    }
+
+    #[rocket::async_test]
+    async fn preflight_request_is_answered() {
+        let client = client(Cors::new("https://sitt.example.com", "X-Total-Count")).await;
+
+        let response = client.options("/projects").dispatch().await;
+
+        assert_eq!(response.status(), Status::NoContent);
+        assert_eq!(
+            response.headers().get_one("Access-Control-Allow-Origin"),
+            Some("https://sitt.example.com")
+        );
+        assert_eq!(
+            response.headers().get_one("Access-Control-Expose-Headers"),
+            Some("X-Total-Count")
+        );
+    }
+}
@@ -0,0 +1,4 @@
+pub mod compression;
+pub mod cors;
+pub mod shutdown;
+pub mod version_warning;
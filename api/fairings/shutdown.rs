@@ -0,0 +1,91 @@
+use rocket::{
+    config::{Shutdown, Sig},
+    fairing::{Fairing, Info, Kind},
+    Orbit, Rocket,
+};
+use std::env;
+
+const DEFAULT_GRACE: u32 = 10;
+const DEFAULT_MERCY: u32 = 5;
+
+/// Stops accepting connections on SIGTERM or Ctrl-C, and gives in-flight requests `grace` seconds
+/// to finish before their I/O is cancelled, and `mercy` more seconds before connections are closed.
+pub fn shutdown_config(grace: u32, mercy: u32) -> Shutdown {
+    let mut shutdown = Shutdown {
+        ctrlc: true,
+        grace,
+        mercy,
+        ..Default::default()
+    };
+    #[cfg(unix)]
+    shutdown.signals.insert(Sig::Term);
+
+    shutdown
+}
+
+/// Reads `SHUTDOWN_GRACE` and `SHUTDOWN_MERCY` in seconds. A rolling deploy must wait longer than
+/// their sum before killing the process, or writes like stopping a time track may be cut off.
+pub fn shutdown_config_from_env() -> Shutdown {
+    let grace = env::var("SHUTDOWN_GRACE")
+        .ok()
+        .and_then(|val| val.parse().ok())
+        .unwrap_or(DEFAULT_GRACE);
+    let mercy = env::var("SHUTDOWN_MERCY")
+        .ok()
+        .and_then(|val| val.parse().ok())
+        .unwrap_or(DEFAULT_MERCY);
+
+    shutdown_config(grace, mercy)
+}
+
+/// Logs the shutdown sequence, so a deploy's logs show whether in-flight requests were given time to finish
+pub struct ShutdownLogger;
+
+#[rocket::async_trait]
+impl Fairing for ShutdownLogger {
+    fn info(&self) -> Info {
+        Info {
+            name: "Shutdown logger",
+            kind: Kind::Shutdown,
+        }
+    }
+
+    async fn on_shutdown(&self, rocket: &Rocket<Orbit>) {
+        let shutdown = &rocket.config().shutdown;
+        println!(
+            "Shutdown requested: no longer accepting connections, waiting up to {}s for in-flight requests",
+            shutdown.grace + shutdown.mercy
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::figment::Figment;
+
+    #[rocket::async_test]
+    async fn shutdown_config_is_applied_to_rocket() {
+        let figment = Figment::from(rocket::Config::debug_default())
+            .merge(("shutdown", shutdown_config(30, 7)));
+
+        let rocket = rocket::custom(figment)
+            .attach(ShutdownLogger)
+            .ignite()
+            .await
+            .unwrap();
+
+        let shutdown = &rocket.config().shutdown;
+        assert_eq!(shutdown.grace, 30);
+        assert_eq!(shutdown.mercy, 7);
+        assert!(
+            shutdown.ctrlc,
+            "Expected Ctrl-C to trigger a graceful shutdown"
+        );
+        #[cfg(unix)]
+        assert!(
+            shutdown.signals.contains(&Sig::Term),
+            "Expected SIGTERM to trigger a graceful shutdown"
+        );
+    }
+}
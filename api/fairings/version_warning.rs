@@ -0,0 +1,138 @@
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    http::Header,
+    Request, Response,
+};
+
+/// The version of the client, sent by the CLI with every request
+pub const CLIENT_VERSION_HEADER: &str = "x-sitt-client-version";
+/// Set when the client is older than the API, so the client can tell its user to update
+pub const VERSION_WARNING_HEADER: &str = "x-sitt-version-warning";
+
+/// Warns clients that are older than the API. Clients that don't send their version are not warned.
+pub struct VersionWarning {
+    api_version: String,
+}
+
+impl VersionWarning {
+    pub fn new(api_version: &str) -> Self {
+        VersionWarning {
+            api_version: api_version.to_string(),
+        }
+    }
+}
+
+impl Default for VersionWarning {
+    fn default() -> Self {
+        Self::new(env!("CARGO_PKG_VERSION"))
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for VersionWarning {
+    fn info(&self) -> Info {
+        Info {
+            name: "Client version warning",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let Some(client_version) = request.headers().get_one(CLIENT_VERSION_HEADER) else {
+            return;
+        };
+
+        if is_older(client_version, &self.api_version) {
+            response.set_header(Header::new(
+                VERSION_WARNING_HEADER,
+                format!(
+                    "sitt {} is older than the API ({}), update sitt to use its latest features",
+                    client_version, self.api_version
+                ),
+            ));
+        }
+    }
+}
+
+// Compares `major.minor.patch`, ignoring pre-release and build suffixes. Unparseable versions are
+// never older, so a malformed header doesn't warn on every request.
+fn is_older(client_version: &str, api_version: &str) -> bool {
+    match (parse_version(client_version), parse_version(api_version)) {
+        (Some(client_version), Some(api_version)) => client_version < api_version,
+        _ => false,
+    }
+}
+
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let version = version.trim().trim_start_matches('v');
+    let version = version.split(['-', '+']).next()?;
+    let mut parts = version.split('.').map(|part| part.parse::<u64>().ok());
+
+    let major = parts.next()??;
+    let minor = parts.next().unwrap_or(Some(0))?;
+    let patch = parts.next().unwrap_or(Some(0))?;
+    if parts.next().is_some() {
+        return None;
+    }
+
+    Some((major, minor, patch))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::{get, local::asynchronous::Client, routes};
+
+    #[get("/projects")]
+    fn projects() -> &'static str {
+        "[]"
+    }
+
+    async fn client() -> Client {
+        let rocket = rocket::build()
+            .mount("/", routes![projects])
+            .attach(VersionWarning::new("0.2.0"));
+        Client::tracked(rocket).await.unwrap()
+    }
+
+    #[test]
+    fn is_older_compares_versions() {
+        assert!(is_older("0.1.0", "0.2.0"));
+        assert!(is_older("0.2.0-beta.1", "0.2.1"));
+        assert!(is_older("v1", "1.0.1"));
+        assert!(!is_older("0.2.0", "0.2.0"));
+        assert!(!is_older("0.10.0", "0.2.0"));
+        assert!(!is_older("not a version", "0.2.0"));
+    }
+
+    #[rocket::async_test]
+    async fn outdated_clients_are_warned() {
+        let client = client().await;
+
+        let response = client
+            .get("/projects")
+            .header(Header::new(CLIENT_VERSION_HEADER, "0.1.0"))
+            .dispatch()
+            .await;
+        let warning = response.headers().get_one(VERSION_WARNING_HEADER);
+        assert!(
+            warning.is_some_and(|warning| warning.contains("0.1.0") && warning.contains("0.2.0")),
+            "Expected a warning naming both versions, got: {:?}",
+            warning
+        );
+
+        for client_version in [Some("0.2.0"), None] {
+            let mut request = client.get("/projects");
+            if let Some(client_version) = client_version {
+                request = request.header(Header::new(CLIENT_VERSION_HEADER, client_version));
+            }
+            let response = request.dispatch().await;
+            assert_eq!(
+                response.headers().get_one(VERSION_WARNING_HEADER),
+                None,
+                "Expected no warning for client version {:?}",
+                client_version
+            );
+        }
+    }
+}
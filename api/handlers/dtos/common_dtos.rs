@@ -1,6 +1,86 @@
+use rocket::{data::ByteUnit, request::Request};
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ErrorResponse {
     pub error_message: String,
 }
+
+/// Whether the request announces a body larger than the limit, so it can be rejected before reading it.
+/// The body is still read with the limit, as the header may be missing or wrong.
+pub fn content_length_exceeds(req: &Request<'_>, limit: ByteUnit) -> bool {
+    req.headers()
+        .get_one("Content-Length")
+        .and_then(|length| length.trim().parse::<u64>().ok())
+        .is_some_and(|length| length > limit.as_u64())
+}
+
+/// Formats the duration rounded to whole seconds. Durations are kept to the millisecond,
+/// but showing the fractions of a second is just noise.
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs() + u64::from(duration.subsec_millis() >= 500);
+    humantime::format_duration(Duration::from_secs(seconds)).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::dtos::project_dtos::CreateProjectDto;
+    use rocket::{
+        http::{ContentType, Header, Status},
+        local::asynchronous::Client,
+        post, routes,
+    };
+
+    #[post("/projects", data = "<create_project_dto>")]
+    fn create(create_project_dto: CreateProjectDto) -> String {
+        create_project_dto.name
+    }
+
+    async fn client() -> Client {
+        Client::tracked(rocket::build().mount("/", routes![create]))
+            .await
+            .unwrap()
+    }
+
+    #[rocket::async_test]
+    async fn oversized_content_length_is_rejected_early() {
+        let client = client().await;
+
+        // The body is small, so only the header can cause the rejection
+        let response = client
+            .post("/projects")
+            .header(ContentType::JSON)
+            .header(Header::new("Content-Length", "1048576"))
+            .body(r#"{"name": "sitt"}"#)
+            .dispatch()
+            .await;
+
+        assert_eq!(response.status(), Status::PayloadTooLarge);
+    }
+
+    #[rocket::async_test]
+    async fn content_length_within_limit_is_accepted() {
+        let client = client().await;
+        let body = r#"{"name": "sitt"}"#;
+
+        let response = client
+            .post("/projects")
+            .header(ContentType::JSON)
+            .header(Header::new("Content-Length", body.len().to_string()))
+            .body(body)
+            .dispatch()
+            .await;
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.into_string().await.unwrap(), "sitt");
+    }
+
+    #[test]
+    fn format_duration_rounds_to_whole_seconds() {
+        assert_eq!(format_duration(Duration::from_millis(90_499)), "1m 30s");
+        assert_eq!(format_duration(Duration::from_millis(90_500)), "1m 31s");
+        assert_eq!(format_duration(Duration::ZERO), "0s");
+    }
+}
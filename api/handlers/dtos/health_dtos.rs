@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+
+use crate::services::health_service::HealthDetail;
+
+pub const HEALTHY: &str = "OK";
+pub const DEGRADED: &str = "DEGRADED";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HealthDto {
+    pub status: String,
+    // Only in the verbose health check
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tables: Option<Vec<TableStatusDto>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_admin_present: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TableStatusDto {
+    pub name: String,
+    pub status: String,
+}
+
+impl HealthDto {
+    pub fn healthy() -> Self {
+        HealthDto {
+            status: HEALTHY.to_string(),
+            tables: None,
+            default_admin_present: None,
+        }
+    }
+}
+
+impl From<HealthDetail> for HealthDto {
+    fn from(detail: HealthDetail) -> Self {
+        let status = if detail.is_healthy() {
+            HEALTHY
+        } else {
+            DEGRADED
+        };
+
+        HealthDto {
+            status: status.to_string(),
+            tables: Some(
+                detail
+                    .tables
+                    .into_iter()
+                    .map(|(name, status)| TableStatusDto { name, status })
+                    .collect(),
+            ),
+            default_admin_present: Some(detail.default_admin_present),
+        }
+    }
+}
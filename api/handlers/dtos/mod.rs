@@ -1,4 +1,5 @@
 pub mod common_dtos;
+pub mod health_dtos;
 pub mod project_dtos;
 pub mod time_track_dtos;
 pub mod user_dtos;
@@ -1,12 +1,16 @@
+use super::common_dtos::{content_length_exceeds, format_duration};
 use crate::models::project_model::{Project, ProjectStatus};
 use chrono::{DateTime, Utc};
-use humantime::format_duration;
 use rocket::data::{self, Data, FromData, ToByteUnit};
 use rocket::http::Status;
 use rocket::outcome::Outcome;
 use rocket::request::Request;
 use serde::{Deserialize, Serialize};
-use validator::{Validate, ValidationErrors};
+use std::time::Duration;
+use validator::{Validate, ValidationError, ValidationErrors};
+
+pub const MAX_TAGS: usize = 10;
+pub const MAX_TAG_LENGTH: usize = 20;
 
 #[derive(Debug)]
 pub enum Error {
@@ -24,14 +28,39 @@ pub struct CreateProjectDto {
         message = "must be between 1 and 25 characters long"
     ))]
     pub name: String,
+    // Replaces the tags of the project when given, leaving them untouched on edits otherwise
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(custom(function = "validate_tags"))]
+    pub tags: Option<Vec<String>>,
+}
+
+pub fn validate_tags(tags: &[String]) -> Result<(), ValidationError> {
+    if tags.len() > MAX_TAGS {
+        return Err(ValidationError::new("too_many_tags"));
+    }
+
+    let invalid_length = |tag: &String| {
+        let length = tag.trim().chars().count();
+        length == 0 || length > MAX_TAG_LENGTH
+    };
+    if tags.iter().any(invalid_length) {
+        return Err(ValidationError::new("invalid_tag_length"));
+    }
+
+    Ok(())
 }
 
 #[rocket::async_trait]
 impl<'r> FromData<'r> for CreateProjectDto {
     type Error = ();
 
-    async fn from_data(_req: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
-        let limit = 256.bytes();
+    async fn from_data(req: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        // Room for the name and the maximum number of tags
+        let limit = 1.kibibytes();
+        if content_length_exceeds(req, limit) {
+            return Outcome::Error((Status::PayloadTooLarge, ()));
+        }
+
         let string = match data.open(limit).into_string().await {
             Ok(string) if string.is_complete() => string.into_inner(),
             Ok(_) => return Outcome::Error((Status::PayloadTooLarge, ())),
@@ -56,9 +85,14 @@ pub struct ProjectDto {
     pub name: String,
     pub status: ProjectStatus,
     pub total_duration: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
     pub created_at: DateTime<Utc>,
+    pub created_by: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub modified_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modified_by: Option<String>,
 }
 
 impl From<Project> for ProjectDto {
@@ -67,9 +101,29 @@ impl From<Project> for ProjectDto {
             project_id: p.id,
             name: p.name,
             status: p.status,
-            total_duration: format_duration(p.total_duration).to_string(),
+            total_duration: format_duration(p.total_duration),
+            tags: p.tags,
             created_at: p.created_at,
+            created_by: p.created_by,
             modified_at: p.modified_at,
+            modified_by: p.modified_by,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TagSummaryDto {
+    pub tag: String,
+    pub total_duration: String,
+    pub project_count: usize,
+}
+
+impl TagSummaryDto {
+    pub fn new(tag: String, total_duration: Duration, project_count: usize) -> Self {
+        TagSummaryDto {
+            tag,
+            total_duration: format_duration(total_duration),
+            project_count,
         }
     }
 }
@@ -1,6 +1,9 @@
-use crate::models::time_track_model::{TimeTrack, TimeTrackStatus};
+use super::common_dtos::{content_length_exceeds, format_duration};
+use crate::models::{
+    project_model::ProjectStatus,
+    time_track_model::{TimeTrack, TimeTrackNote, TimeTrackStatus},
+};
 use chrono::{DateTime, Utc};
-use humantime::format_duration;
 use rocket::{
     data::{self, FromData, ToByteUnit},
     http::Status,
@@ -8,22 +11,41 @@ use rocket::{
     Data, Request,
 };
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 use uuid::Uuid;
+use validator::{Validate, ValidationError};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, Validate)]
 #[serde(crate = "rocket::serde")]
+#[validate(schema(function = "validate_chronological"))]
 pub struct CreateTimeTrackDto {
     pub project_id: String,
     pub started_at: DateTime<Utc>,
     pub stopped_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate(length(max = 250, message = "must be at most 250 characters long"))]
+    pub comment: Option<String>,
+}
+
+// Equal timestamps are allowed, they log an instantaneous event with zero duration
+fn validate_chronological(dto: &CreateTimeTrackDto) -> Result<(), ValidationError> {
+    if dto.started_at > dto.stopped_at {
+        return Err(ValidationError::new("stopped_before_started"));
+    }
+
+    Ok(())
 }
 
 #[rocket::async_trait]
 impl<'r> FromData<'r> for CreateTimeTrackDto {
     type Error = ();
 
-    async fn from_data(_req: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
-        let limit = 256.bytes();
+    async fn from_data(req: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        let limit = 1.kibibytes();
+        if content_length_exceeds(req, limit) {
+            return Outcome::Error((Status::PayloadTooLarge, ()));
+        }
+
         let string = match data.open(limit).into_string().await {
             Ok(string) if string.is_complete() => string.into_inner(),
             Ok(_) => return Outcome::Error((Status::PayloadTooLarge, ())),
@@ -39,10 +61,56 @@ impl<'r> FromData<'r> for CreateTimeTrackDto {
             return Outcome::Error((Status::UnprocessableEntity, ()));
         }
 
+        if update_time_track_dto.validate().is_err() {
+            return Outcome::Error((Status::UnprocessableEntity, ()));
+        }
+
         Outcome::Success(update_time_track_dto)
     }
 }
 
+/// A note to append to a time track
+#[derive(Debug, Deserialize, Serialize, Validate)]
+#[serde(crate = "rocket::serde")]
+pub struct AppendNoteDto {
+    #[validate(length(
+        min = 1,
+        max = 250,
+        message = "must be between 1 and 250 characters long"
+    ))]
+    pub note: String,
+}
+
+#[rocket::async_trait]
+impl<'r> FromData<'r> for AppendNoteDto {
+    type Error = ();
+
+    async fn from_data(req: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        let limit = 1.kibibytes();
+        if content_length_exceeds(req, limit) {
+            return Outcome::Error((Status::PayloadTooLarge, ()));
+        }
+
+        let string = match data.open(limit).into_string().await {
+            Ok(string) if string.is_complete() => string.into_inner(),
+            Ok(_) => return Outcome::Error((Status::PayloadTooLarge, ())),
+            Err(_) => return Outcome::Error((Status::InternalServerError, ())),
+        };
+
+        let append_note_dto: AppendNoteDto = match serde_json::from_str(&string) {
+            Ok(value) => value,
+            Err(_) => return Outcome::Error((Status::UnprocessableEntity, ())),
+        };
+
+        // A note of only whitespace would be stored empty, as the note is trimmed
+        if append_note_dto.validate().is_err() || append_note_dto.note.trim().is_empty() {
+            return Outcome::Error((Status::UnprocessableEntity, ()));
+        }
+
+        Outcome::Success(append_note_dto)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TimeTrackDto {
     pub time_track_id: String,
@@ -53,6 +121,14 @@ pub struct TimeTrackDto {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stopped_at: Option<DateTime<Utc>>,
     pub total_duration: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub notes: Vec<TimeTrackNote>,
+    pub created_at: DateTime<Utc>,
+    // The id of the user who logged the time
+    #[serde(default)]
+    pub created_by: String,
 }
 
 impl TimeTrackDto {
@@ -64,7 +140,82 @@ impl TimeTrackDto {
             status: t.status,
             started_at: t.started_at,
             stopped_at: t.stopped_at,
-            total_duration: format_duration(t.total_duration).to_string(),
+            total_duration: format_duration(t.total_duration),
+            comment: t.comment,
+            notes: t.notes,
+            created_at: t.created_at,
+            created_by: t.created_by,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecentTimeTracksDto {
+    pub time_tracks: Vec<TimeTrackDto>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+/// An event of the live total stream of a project
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LiveTotalDto {
+    pub project_id: String,
+    pub status: ProjectStatus,
+    pub elapsed: String,
+    pub total_duration: String,
+}
+
+/// What deleting a time track took off its project's total
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeletedTimeTrackDto {
+    pub deleted_duration: String,
+    pub total_duration: String,
+}
+
+impl DeletedTimeTrackDto {
+    pub fn new(deleted_duration: Duration, total_duration: Duration) -> Self {
+        DeletedTimeTrackDto {
+            deleted_duration: format_duration(deleted_duration),
+            total_duration: format_duration(total_duration),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_time_track_dto(
+        started_at: DateTime<Utc>,
+        stopped_at: DateTime<Utc>,
+    ) -> CreateTimeTrackDto {
+        CreateTimeTrackDto {
+            project_id: Uuid::new_v4().to_string(),
+            started_at,
+            stopped_at,
+            comment: None,
+        }
+    }
+
+    #[test]
+    fn validate_accepts_equal_timestamps() {
+        let now = Utc::now();
+
+        assert!(
+            create_time_track_dto(now, now).validate().is_ok(),
+            "Expected equal timestamps to be a valid zero duration entry"
+        );
+    }
+
+    #[test]
+    fn validate_rejects_reversed_timestamps() {
+        let now = Utc::now();
+
+        assert!(
+            create_time_track_dto(now, now - chrono::Duration::seconds(1))
+                .validate()
+                .is_err(),
+            "Expected a stop before the start to be rejected"
+        );
+    }
+}
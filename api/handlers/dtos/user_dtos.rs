@@ -5,13 +5,17 @@ use rocket::{
     outcome::Outcome,
     Data, Request,
 };
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
 use validator::Validate;
 
-use crate::models::user_model::{User, UserRole};
+use super::common_dtos::content_length_exceeds;
+use crate::models::user_model::{ApiKeyScope, User, UserRole};
 
 #[derive(Debug, Serialize, Deserialize, Validate)]
 pub struct CreateUserDto {
+    // Trimmed when parsed, so a blank name fails the length check and lookups by name match
+    #[serde(deserialize_with = "trimmed")]
     #[validate(length(
         min = 1,
         max = 25,
@@ -19,14 +23,25 @@ pub struct CreateUserDto {
     ))]
     pub name: String,
     pub role: UserRole,
+    #[serde(default)]
+    pub scope: ApiKeyScope,
+}
+
+fn trimmed<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    Ok(name.trim().to_string())
 }
 
 #[rocket::async_trait]
 impl<'r> FromData<'r> for CreateUserDto {
     type Error = ();
 
-    async fn from_data(_req: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+    async fn from_data(req: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
         let limit = 256.bytes();
+        if content_length_exceeds(req, limit) {
+            return Outcome::Error((Status::PayloadTooLarge, ()));
+        }
+
         let string = match data.open(limit).into_string().await {
             Ok(string) if string.is_complete() => string.into_inner(),
             Ok(_) => return Outcome::Error((Status::PayloadTooLarge, ())),
@@ -46,11 +61,107 @@ impl<'r> FromData<'r> for CreateUserDto {
     }
 }
 
+/// The name of the personal admin that replaces the default admin
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct ClaimAdminDto {
+    #[serde(deserialize_with = "trimmed")]
+    #[validate(length(
+        min = 1,
+        max = 25,
+        message = "must be between 1 and 25 characters long"
+    ))]
+    pub name: String,
+}
+
+#[rocket::async_trait]
+impl<'r> FromData<'r> for ClaimAdminDto {
+    type Error = ();
+
+    async fn from_data(req: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        let limit = 256.bytes();
+        if content_length_exceeds(req, limit) {
+            return Outcome::Error((Status::PayloadTooLarge, ()));
+        }
+
+        let string = match data.open(limit).into_string().await {
+            Ok(string) if string.is_complete() => string.into_inner(),
+            Ok(_) => return Outcome::Error((Status::PayloadTooLarge, ())),
+            Err(_) => return Outcome::Error((Status::InternalServerError, ())),
+        };
+
+        let claim_admin_dto: ClaimAdminDto = match serde_json::from_str(&string) {
+            Ok(value) => value,
+            Err(_) => return Outcome::Error((Status::UnprocessableEntity, ())),
+        };
+
+        if claim_admin_dto.validate().is_err() {
+            return Outcome::Error((Status::UnprocessableEntity, ()));
+        }
+
+        Outcome::Success(claim_admin_dto)
+    }
+}
+
+/// The personal admin with its one-time API key. The default admin is kept if deleting it failed.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClaimedAdminDto {
+    pub admin: UserDto,
+    pub default_admin_deleted: bool,
+}
+
+pub const MAX_BULK_USERS: usize = 100;
+
+/// A JSON array of users to create. Each user is validated on its own when it is created,
+/// so one invalid user does not fail the others.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CreateUsersDto {
+    pub users: Vec<CreateUserDto>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromData<'r> for CreateUsersDto {
+    type Error = ();
+
+    async fn from_data(req: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        let limit = (MAX_BULK_USERS * 128).bytes();
+        if content_length_exceeds(req, limit) {
+            return Outcome::Error((Status::PayloadTooLarge, ()));
+        }
+
+        let string = match data.open(limit).into_string().await {
+            Ok(string) if string.is_complete() => string.into_inner(),
+            Ok(_) => return Outcome::Error((Status::PayloadTooLarge, ())),
+            Err(_) => return Outcome::Error((Status::InternalServerError, ())),
+        };
+
+        let create_users_dto: CreateUsersDto = match serde_json::from_str(&string) {
+            Ok(value) => value,
+            Err(_) => return Outcome::Error((Status::UnprocessableEntity, ())),
+        };
+
+        if create_users_dto.users.is_empty() || create_users_dto.users.len() > MAX_BULK_USERS {
+            return Outcome::Error((Status::UnprocessableEntity, ()));
+        }
+
+        Outcome::Success(create_users_dto)
+    }
+}
+
+/// The one-time API keys of the created users and the errors of the failed ones, by name
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CreatedUsersDto {
+    pub api_keys: BTreeMap<String, String>,
+    pub failed: BTreeMap<String, String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UserDto {
     pub id: String,
     pub name: String,
     pub role: UserRole,
+    #[serde(default)]
+    pub scope: ApiKeyScope,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub api_key: Option<String>,
     pub created_at: DateTime<Utc>,
@@ -63,9 +174,62 @@ impl From<User> for UserDto {
             id: u.id,
             name: u.name,
             role: u.role,
+            scope: u.scope,
             api_key: u.api_key,
             created_at: u.created_at,
             created_by: u.created_by,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> CreateUserDto {
+        serde_json::from_str(json).expect("Expected the user to parse")
+    }
+
+    #[test]
+    fn parse_trims_surrounding_whitespace() {
+        let create_user_dto = parse(r#"{"name": "  Alice  ", "role": "USER"}"#);
+
+        assert_eq!(create_user_dto.name, "Alice");
+        assert!(create_user_dto.validate().is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_blank_name() {
+        let create_user_dto = parse(r#"{"name": " \t ", "role": "USER"}"#);
+
+        assert!(
+            create_user_dto.validate().is_err(),
+            "Expected a name of only whitespace to be rejected"
+        );
+    }
+
+    #[test]
+    fn bulk_parse_trims_each_name() {
+        let create_users_dto: CreateUsersDto = serde_json::from_str(
+            r#"[{"name": " Alice", "role": "USER"}, {"name": "Bob ", "role": "ADMIN"}]"#,
+        )
+        .unwrap();
+
+        let names: Vec<&str> = create_users_dto
+            .users
+            .iter()
+            .map(|user| user.name.as_str())
+            .collect();
+        assert_eq!(names, ["Alice", "Bob"]);
+    }
+
+    #[test]
+    fn user_without_scope_parses_as_read_write() {
+        let user: UserDto = serde_json::from_str(
+            r#"{"id": "1", "name": "Alice", "role": "USER", "created_at": "2024-01-01T00:00:00Z", "created_by": "SYSTEM"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(user.scope, ApiKeyScope::ReadWrite);
+    }
+}
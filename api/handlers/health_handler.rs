@@ -0,0 +1,118 @@
+use super::{
+    dtos::{common_dtos::ErrorResponse, health_dtos::HealthDto},
+    validation::user_validation::AdminValidation,
+};
+use crate::services::health_service::HealthService;
+use rocket::{get, http::Status, response::status, routes, serde::json::Json, Route, State};
+use std::sync::Arc;
+
+pub fn routes() -> Vec<Route> {
+    routes![health]
+}
+
+/// Answers without touching the database, unless `verbose` asks for the status of every table.
+/// The verbose check requires an ADMIN, as it reveals whether the default admin still exists.
+#[get("/health?<verbose>")]
+pub async fn health(
+    health_service: &State<Arc<HealthService>>,
+    admin_user: Option<AdminValidation>,
+    verbose: Option<bool>,
+) -> Result<Json<HealthDto>, status::Custom<Json<ErrorResponse>>> {
+    if !verbose.unwrap_or(false) {
+        return Ok(Json(HealthDto::healthy()));
+    }
+
+    if admin_user.is_none() {
+        return Err(status::Custom(
+            Status::Unauthorized,
+            Json(ErrorResponse {
+                error_message: String::from("The verbose health check requires an ADMIN API key"),
+            }),
+        ));
+    }
+
+    let detail = health_service.detail().await;
+    Ok(Json(HealthDto::from(detail)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        handlers::dtos::health_dtos::{DEGRADED, HEALTHY},
+        infrastructure::database::{DbError, TableStatusStore},
+        models::user_model::{User, UserRole},
+        services::test_utils::in_memory_services,
+    };
+    use async_trait::async_trait;
+    use rocket::{http::Header, local::asynchronous::Client};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Reports every table as CREATING and counts how often it was asked
+    #[derive(Debug, Default)]
+    struct CreatingTables(AtomicUsize);
+
+    #[async_trait]
+    impl TableStatusStore for CreatingTables {
+        async fn table_status(&self, _table_name: &str) -> Result<String, DbError> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(String::from("CREATING"))
+        }
+    }
+
+    #[rocket::async_test]
+    async fn health_is_shallow_unless_verbose() {
+        let services = in_memory_services().await;
+        let admin = services
+            .user_service
+            .create(
+                "admin",
+                &UserRole::Admin,
+                &User::new("SYSTEM", &UserRole::Admin, "SYSTEM"),
+            )
+            .await
+            .unwrap();
+        let tables = Arc::new(CreatingTables::default());
+        let health_service = Arc::new(HealthService::new(
+            tables.clone(),
+            services.user_service.repository.clone(),
+        ));
+
+        let rocket = rocket::build()
+            .manage(services.user_service)
+            .manage(health_service)
+            .mount("/", routes());
+        let client = Client::tracked(rocket).await.unwrap();
+
+        let response = client.get("/health").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let health: HealthDto = response.into_json().await.unwrap();
+        assert_eq!(health.status, HEALTHY);
+        assert!(health.tables.is_none());
+        assert_eq!(
+            tables.0.load(Ordering::SeqCst),
+            0,
+            "Expected the shallow check to not describe the tables"
+        );
+
+        let response = client.get("/health?verbose=true").dispatch().await;
+        assert_eq!(
+            response.status(),
+            Status::Unauthorized,
+            "Expected the verbose check to require an ADMIN"
+        );
+
+        let response = client
+            .get("/health?verbose=true")
+            .header(Header::new("x-api-key", admin.api_key.unwrap()))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let health: HealthDto = response.into_json().await.unwrap();
+        assert_eq!(health.status, DEGRADED);
+        let tables = health.tables.unwrap();
+        assert_eq!(tables.len(), 3);
+        assert!(tables.iter().all(|t| t.status == "CREATING"));
+        assert_eq!(health.default_admin_present, Some(false));
+    }
+}
@@ -1,4 +1,5 @@
 pub mod dtos;
+pub mod health_handler;
 pub mod project_handler;
 pub mod time_track_handler;
 pub mod user_handler;
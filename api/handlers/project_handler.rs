@@ -1,19 +1,30 @@
 use super::dtos::common_dtos::ErrorResponse;
 use super::validation::user_validation::UserValidation;
 use super::validation::uuid_validation::UuidValidation;
-use crate::handlers::dtos::project_dtos::{CreateProjectDto, ProjectDto};
-use crate::services::project_service::{ProjectError, ProjectService};
+use crate::handlers::dtos::project_dtos::{CreateProjectDto, ProjectDto, TagSummaryDto};
+use crate::models::user_model::UserRole;
+use crate::services::project_service::{ProjectError, ProjectService, TagTotal};
+use crate::services::user_service::{UserError, UserService};
 use rocket::serde::json::Json;
-use rocket::{delete, get, http::Status, post, response::status, routes, State};
+use rocket::{delete, get, http::Status, post, response::status, routes, Either, State};
 use rocket::{put, Route};
 use std::sync::Arc;
 
 pub fn routes() -> Vec<Route> {
-    routes![create, get, get_all, update, delete]
+    routes![
+        create,
+        get,
+        get_all,
+        get_summary_by_tag,
+        update,
+        update_by_name,
+        delete
+    ]
 }
 
+/// With `if_not_exists`, an existing project with the same name is returned with 200 instead of a 409
 #[post(
-    "/projects",
+    "/projects?<if_not_exists>",
     format = "application/json",
     data = "<create_project_dto>"
 )]
@@ -21,12 +32,31 @@ pub async fn create(
     project_service: &State<Arc<ProjectService>>,
     user: UserValidation,
     create_project_dto: CreateProjectDto,
-) -> Result<status::Created<Json<ProjectDto>>, status::Custom<Json<ErrorResponse>>> {
+    if_not_exists: Option<bool>,
+) -> Result<
+    Either<status::Created<Json<ProjectDto>>, Json<ProjectDto>>,
+    status::Custom<Json<ErrorResponse>>,
+> {
     let user = &user.0;
     let project_name = create_project_dto.name;
+    let tags = create_project_dto.tags.unwrap_or_default();
 
-    match project_service.create(user, project_name).await {
-        Ok(project) => Ok(status::Created::new("/projects").body(Json(ProjectDto::from(project)))),
+    let result = if if_not_exists.unwrap_or(false) {
+        project_service
+            .get_or_create(user, project_name, tags)
+            .await
+    } else {
+        project_service
+            .create_with_tags(user, project_name, tags)
+            .await
+            .map(|project| (project, true))
+    };
+
+    match result {
+        Ok((project, true)) => Ok(Either::Left(
+            status::Created::new("/projects").body(Json(ProjectDto::from(project))),
+        )),
+        Ok((project, false)) => Ok(Either::Right(Json(ProjectDto::from(project)))),
         Err(err) => match err {
             ProjectError::NotFound => Err(status::Custom(
                 Status::NotFound,
@@ -40,7 +70,7 @@ pub async fn create(
                     error_message: err.to_string(),
                 }),
             )),
-            ProjectError::TooManyProjects => Err(status::Custom(
+            ProjectError::TooManyProjects(_) => Err(status::Custom(
                 Status::BadRequest,
                 Json(ErrorResponse {
                     error_message: err.to_string(),
@@ -59,14 +89,57 @@ pub async fn create(
     }
 }
 
-#[get("/projects")]
+/// ADMINs can list the projects of another user with `created_by`, the id of that user
+#[get("/projects?<tag>&<search>&<created_by>")]
 pub async fn get_all(
     project_service: &State<Arc<ProjectService>>,
+    user_service: &State<Arc<UserService>>,
     user: UserValidation,
+    tag: Option<&str>,
+    search: Option<&str>,
+    created_by: Option<&str>,
 ) -> Result<Json<Vec<ProjectDto>>, status::Custom<Json<ErrorResponse>>> {
-    let user = &user.0;
+    let user = match created_by {
+        None => user.0,
+        Some(_) if user.0.role != UserRole::Admin => {
+            return Err(status::Custom(
+                Status::Forbidden,
+                Json(ErrorResponse {
+                    error_message: String::from(
+                        "Only ADMIN users can list the projects of other users",
+                    ),
+                }),
+            ))
+        }
+        Some(user_id) => match user_service.get_by_id(user_id, false).await {
+            Ok(owner) => owner,
+            Err(err @ UserError::NotFound) => {
+                return Err(status::Custom(
+                    Status::NotFound,
+                    Json(ErrorResponse {
+                        error_message: err.to_string(),
+                    }),
+                ))
+            }
+            Err(err) => {
+                eprintln!("{}", err);
+                return Err(status::Custom(
+                    Status::InternalServerError,
+                    Json(ErrorResponse {
+                        error_message: String::from("An internal error occurred"),
+                    }),
+                ));
+            }
+        },
+    };
+    let user = &user;
+
+    let result = match search {
+        Some(query) => project_service.search(user, tag, query).await,
+        None => project_service.get_all(user, tag).await,
+    };
 
-    match project_service.get_all(user).await {
+    match result {
         Ok(projects) => {
             let project_dtos: Vec<ProjectDto> =
                 projects.into_iter().map(ProjectDto::from).collect();
@@ -84,6 +157,44 @@ pub async fn get_all(
     }
 }
 
+#[get("/projects/summary/by-tag?<include_running>")]
+pub async fn get_summary_by_tag(
+    project_service: &State<Arc<ProjectService>>,
+    user: UserValidation,
+    include_running: Option<bool>,
+) -> Result<Json<Vec<TagSummaryDto>>, status::Custom<Json<ErrorResponse>>> {
+    let user = &user.0;
+    let include_running = include_running.unwrap_or(true);
+
+    match project_service
+        .get_total_by_tag(user, include_running)
+        .await
+    {
+        Ok(totals) => {
+            let mut summaries: Vec<(String, TagTotal)> = totals.into_iter().collect();
+            // Most tracked tags first, ties by name
+            summaries.sort_by(|(a_tag, (a_total, _)), (b_tag, (b_total, _))| {
+                b_total.cmp(a_total).then_with(|| a_tag.cmp(b_tag))
+            });
+
+            let tag_summary_dtos = summaries
+                .into_iter()
+                .map(|(tag, (total, project_count))| TagSummaryDto::new(tag, total, project_count))
+                .collect();
+            Ok(Json(tag_summary_dtos))
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            Err(status::Custom(
+                Status::InternalServerError,
+                Json(ErrorResponse {
+                    error_message: String::from("An internal error occurred"),
+                }),
+            ))
+        }
+    }
+}
+
 #[get("/projects/<project_id>")]
 pub async fn get(
     project_service: &State<Arc<ProjectService>>,
@@ -131,7 +242,7 @@ pub async fn update(
     let new_project_name = update_project.name;
 
     match project_service
-        .update_name(user, project_id, new_project_name)
+        .update_name_and_tags(user, project_id, new_project_name, update_project.tags)
         .await
     {
         Ok(project) => Ok(Json(ProjectDto::from(project))),
@@ -142,6 +253,57 @@ pub async fn update(
                     error_message: err.to_string(),
                 }),
             )),
+            ProjectError::ProjectExistsWithSameName(_) => Err(status::Custom(
+                Status::Conflict,
+                Json(ErrorResponse {
+                    error_message: err.to_string(),
+                }),
+            )),
+            _ => {
+                eprintln!("{}", err);
+                Err(status::Custom(
+                    Status::InternalServerError,
+                    Json(ErrorResponse {
+                        error_message: String::from("An internal error occurred"),
+                    }),
+                ))
+            }
+        },
+    }
+}
+
+#[put(
+    "/projects/by-name/<project_name>",
+    format = "application/json",
+    data = "<update_project>"
+)]
+pub async fn update_by_name(
+    project_service: &State<Arc<ProjectService>>,
+    user: UserValidation,
+    project_name: &str,
+    update_project: CreateProjectDto,
+) -> Result<Json<ProjectDto>, status::Custom<Json<ErrorResponse>>> {
+    let user = &user.0;
+    let new_project_name = update_project.name;
+
+    match project_service
+        .update_name_and_tags_by_name(user, project_name, new_project_name, update_project.tags)
+        .await
+    {
+        Ok(project) => Ok(Json(ProjectDto::from(project))),
+        Err(err) => match err {
+            ProjectError::NotFound => Err(status::Custom(
+                Status::NotFound,
+                Json(ErrorResponse {
+                    error_message: err.to_string(),
+                }),
+            )),
+            ProjectError::ProjectExistsWithSameName(_) => Err(status::Custom(
+                Status::Conflict,
+                Json(ErrorResponse {
+                    error_message: err.to_string(),
+                }),
+            )),
             _ => {
                 eprintln!("{}", err);
                 Err(status::Custom(
@@ -185,3 +347,249 @@ pub async fn delete(
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::user_model::{ApiKeyScope, User, UserRole},
+        services::test_utils::test_client,
+    };
+    use rocket::http::{ContentType, Header};
+
+    #[rocket::async_test]
+    async fn projects_are_created_with_tags_and_filtered_by_tag() {
+        let (client, user, _) = test_client(routes()).await;
+        let api_key = user.api_key.unwrap();
+
+        for body in [
+            r#"{"name": "client", "tags": ["billable", "client"]}"#,
+            r#"{"name": "internal", "tags": ["internal"]}"#,
+        ] {
+            let response = client
+                .post("/projects")
+                .header(ContentType::JSON)
+                .header(Header::new("x-api-key", api_key.clone()))
+                .body(body)
+                .dispatch()
+                .await;
+            assert_eq!(response.status(), Status::Created);
+        }
+
+        let response = client
+            .get("/projects?tag=billable")
+            .header(Header::new("x-api-key", api_key.clone()))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let projects: Vec<ProjectDto> = response.into_json().await.unwrap();
+        assert_eq!(projects.len(), 1);
+        assert_eq!(projects[0].name, "client");
+        assert_eq!(projects[0].tags, vec!["billable", "client"]);
+    }
+
+    #[rocket::async_test]
+    async fn invalid_tags_are_rejected() {
+        let (client, user, _) = test_client(routes()).await;
+        let api_key = user.api_key.unwrap();
+
+        let too_many_tags: Vec<String> = (0..11).map(|i| format!("tag{}", i)).collect();
+        for tags in [
+            too_many_tags,
+            vec![String::from("a_tag_that_is_far_too_long")],
+            vec![String::from("  ")],
+        ] {
+            let body = serde_json::json!({ "name": "sitt", "tags": tags }).to_string();
+            let response = client
+                .post("/projects")
+                .header(ContentType::JSON)
+                .header(Header::new("x-api-key", api_key.clone()))
+                .body(body)
+                .dispatch()
+                .await;
+            assert_eq!(
+                response.status(),
+                Status::UnprocessableEntity,
+                "Expected the tags {:?} to be rejected",
+                tags
+            );
+        }
+    }
+
+    #[rocket::async_test]
+    async fn summary_by_tag_sums_overlapping_tags() {
+        let (client, user, services) = test_client(routes()).await;
+        for (name, tags, hours) in [
+            ("client", vec!["billable", "client"], 2),
+            ("support", vec!["billable"], 1),
+            ("untagged", vec![], 4),
+        ] {
+            let mut project = services
+                .project_service
+                .create_with_tags(
+                    &user,
+                    name.to_string(),
+                    tags.into_iter().map(String::from).collect(),
+                )
+                .await
+                .unwrap();
+            project.total_duration = std::time::Duration::from_secs(hours * 3600);
+            services
+                .project_service
+                .update(&user, &mut project)
+                .await
+                .unwrap();
+        }
+
+        let response = client
+            .get("/projects/summary/by-tag")
+            .header(Header::new("x-api-key", user.api_key.unwrap()))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+
+        let summaries: Vec<TagSummaryDto> = response.into_json().await.unwrap();
+        let summaries: Vec<(&str, &str, usize)> = summaries
+            .iter()
+            .map(|s| (s.tag.as_str(), s.total_duration.as_str(), s.project_count))
+            .collect();
+        assert_eq!(
+            summaries,
+            vec![
+                ("(untagged)", "4h", 1),
+                ("billable", "3h", 2),
+                ("client", "2h", 1)
+            ]
+        );
+    }
+
+    #[rocket::async_test]
+    async fn create_if_not_exists_returns_the_existing_project() {
+        let (client, user, _) = test_client(routes()).await;
+        let api_key = user.api_key.unwrap();
+        let create = |uri: &'static str| {
+            client
+                .post(uri)
+                .header(ContentType::JSON)
+                .header(Header::new("x-api-key", api_key.clone()))
+                .body(r#"{"name": "sitt"}"#)
+                .dispatch()
+        };
+
+        let response = create("/projects?if_not_exists=true").await;
+        assert_eq!(response.status(), Status::Created);
+        let created: ProjectDto = response.into_json().await.unwrap();
+
+        let response = create("/projects?if_not_exists=true").await;
+        assert_eq!(response.status(), Status::Ok);
+        let existing: ProjectDto = response.into_json().await.unwrap();
+        assert_eq!(existing.project_id, created.project_id);
+
+        let response = create("/projects").await;
+        assert_eq!(
+            response.status(),
+            Status::Conflict,
+            "Expected a duplicate without the flag to still conflict"
+        );
+    }
+
+    #[rocket::async_test]
+    async fn projects_are_searched_by_name() {
+        let (client, user, services) = test_client(routes()).await;
+        let api_key = user.api_key.clone().unwrap();
+        for name in ["client work", "internal", "Client meetings"] {
+            services
+                .project_service
+                .create(&user, name.to_string())
+                .await
+                .unwrap();
+        }
+
+        let response = client
+            .get("/projects?search=client")
+            .header(Header::new("x-api-key", api_key))
+            .dispatch()
+            .await;
+
+        assert_eq!(response.status(), Status::Ok);
+        let projects: Vec<ProjectDto> = response.into_json().await.unwrap();
+        let mut names: Vec<String> = projects.into_iter().map(|p| p.name).collect();
+        names.sort();
+        assert_eq!(names, vec!["Client meetings", "client work"]);
+    }
+
+    #[rocket::async_test]
+    async fn admins_list_the_projects_of_another_user() {
+        let (client, user, services) = test_client(routes()).await;
+        let admin = services.create_user("admin", &UserRole::Admin).await;
+        let other_user = services.create_user("other", &UserRole::User).await;
+        services
+            .project_service
+            .create(&user, "sitt".to_string())
+            .await
+            .unwrap();
+
+        let response = client
+            .get(format!("/projects?created_by={}", user.id))
+            .header(Header::new("x-api-key", admin.api_key.unwrap()))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let projects: Vec<ProjectDto> = response.into_json().await.unwrap();
+        assert_eq!(projects.len(), 1);
+        assert_eq!(projects[0].name, "sitt");
+
+        let response = client
+            .get(format!("/projects?created_by={}", user.id))
+            .header(Header::new("x-api-key", other_user.api_key.unwrap()))
+            .dispatch()
+            .await;
+        assert_eq!(
+            response.status(),
+            Status::Forbidden,
+            "Expected USERs to not list the projects of others"
+        );
+    }
+
+    #[rocket::async_test]
+    async fn read_only_key_can_list_but_not_create_or_delete() {
+        let (client, _, services) = test_client(routes()).await;
+        let admin = User::new("admin", &UserRole::Admin, "SYSTEM");
+        let viewer = services
+            .user_service
+            .create_with_scope("viewer", &UserRole::User, &ApiKeyScope::ReadOnly, &admin)
+            .await
+            .unwrap();
+        let project = services
+            .project_service
+            .create(&viewer, String::from("dashboard"))
+            .await
+            .unwrap();
+        let api_key = viewer.api_key.unwrap();
+
+        let response = client
+            .get("/projects")
+            .header(Header::new("x-api-key", api_key.clone()))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let projects: Vec<ProjectDto> = response.into_json().await.unwrap();
+        assert_eq!(projects.len(), 1);
+
+        let response = client
+            .post("/projects")
+            .header(ContentType::JSON)
+            .header(Header::new("x-api-key", api_key.clone()))
+            .body(r#"{"name": "new"}"#)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Forbidden);
+
+        let response = client
+            .delete(format!("/projects/{}", project.id))
+            .header(Header::new("x-api-key", api_key))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Forbidden);
+    }
+}
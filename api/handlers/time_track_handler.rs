@@ -1,18 +1,47 @@
 use super::{
     dtos::{
-        common_dtos::ErrorResponse,
-        time_track_dtos::{CreateTimeTrackDto, TimeTrackDto},
+        common_dtos::{format_duration, ErrorResponse},
+        time_track_dtos::{
+            AppendNoteDto, CreateTimeTrackDto, DeletedTimeTrackDto, LiveTotalDto,
+            RecentTimeTracksDto, TimeTrackDto,
+        },
     },
     validation::{user_validation::UserValidation, uuid_validation::UuidValidation},
 };
-use crate::services::time_track_service::{TimeTrackError, TimeTrackService};
+use crate::{
+    models::project_model::ProjectStatus,
+    services::time_track_service::{LiveTotal, TimeTrackError, TimeTrackService},
+};
 use rocket::{
-    delete, get, http::Status, post, put, response::status, routes, serde::json::Json, Route, State,
+    delete, get,
+    http::Status,
+    post, put,
+    response::{
+        status,
+        stream::{Event, EventStream},
+    },
+    routes,
+    serde::json::Json,
+    tokio::{select, time},
+    Either, Route, Shutdown, State,
 };
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
+
+const STREAM_INTERVAL: Duration = Duration::from_secs(5);
 
 pub fn routes() -> Vec<Route> {
-    routes![start, stop, create, get, update, delete]
+    routes![
+        start,
+        stop,
+        heartbeat,
+        stream,
+        create,
+        get,
+        get_recent,
+        update,
+        append_note,
+        delete
+    ]
 }
 
 #[post("/timetrack/<project_id>/start")]
@@ -93,6 +122,118 @@ pub async fn stop(
     }
 }
 
+#[post("/timetrack/<project_id>/heartbeat")]
+pub async fn heartbeat(
+    time_track_service: &State<Arc<TimeTrackService>>,
+    user: UserValidation,
+    project_id: UuidValidation,
+) -> Result<status::NoContent, status::Custom<Json<ErrorResponse>>> {
+    let user = &user.0;
+    let project_id = project_id.0.to_string();
+
+    match time_track_service.heartbeat(user, &project_id).await {
+        Ok(_) => Ok(status::NoContent),
+        Err(err) => match err {
+            TimeTrackError::ProjectNotFound => Err(status::Custom(
+                Status::NotFound,
+                Json(ErrorResponse {
+                    error_message: err.to_string(),
+                }),
+            )),
+            TimeTrackError::NoInProgressTimeTracking(_) => Err(status::Custom(
+                Status::BadRequest,
+                Json(ErrorResponse {
+                    error_message: err.to_string(),
+                }),
+            )),
+            _ => {
+                eprintln!("{}", err);
+                Err(status::Custom(
+                    Status::InternalServerError,
+                    Json(ErrorResponse {
+                        error_message: String::from("An internal error occurred"),
+                    }),
+                ))
+            }
+        },
+    }
+}
+
+/// Streams the live total of the project as server-sent events, until the time tracking stops
+#[get("/timetrack/<project_id>/stream")]
+pub async fn stream(
+    time_track_service: &State<Arc<TimeTrackService>>,
+    user: UserValidation,
+    project_id: UuidValidation,
+    mut shutdown: Shutdown,
+) -> Result<EventStream![], status::Custom<Json<ErrorResponse>>> {
+    let user = user.0;
+    let project_id = project_id.0.to_string();
+
+    // Fail with a status code before the stream is opened
+    let first_live_total = match time_track_service.get_live_total(&user, &project_id).await {
+        Ok(live_total) => live_total,
+        Err(err) => {
+            return match err {
+                TimeTrackError::ProjectNotFound => Err(status::Custom(
+                    Status::NotFound,
+                    Json(ErrorResponse {
+                        error_message: err.to_string(),
+                    }),
+                )),
+                _ => {
+                    eprintln!("{}", err);
+                    Err(status::Custom(
+                        Status::InternalServerError,
+                        Json(ErrorResponse {
+                            error_message: String::from("An internal error occurred"),
+                        }),
+                    ))
+                }
+            }
+        }
+    };
+
+    let time_track_service = time_track_service.inner().clone();
+    let to_event = move |project_id: &str, (status, elapsed, total): LiveTotal| {
+        Event::json(&LiveTotalDto {
+            project_id: project_id.to_string(),
+            status,
+            elapsed: format_duration(elapsed),
+            total_duration: format_duration(total),
+        })
+    };
+
+    Ok(EventStream! {
+        let mut live_total = first_live_total;
+        let mut interval = time::interval(STREAM_INTERVAL);
+        // The first tick completes immediately
+        interval.tick().await;
+
+        loop {
+            let is_active = live_total.0 == ProjectStatus::Active;
+            yield to_event(&project_id, live_total);
+
+            if !is_active {
+                break;
+            }
+
+            select! {
+                _ = interval.tick() => {},
+                _ = &mut shutdown => break,
+            }
+
+            live_total = match time_track_service.get_live_total(&user, &project_id).await {
+                Ok(live_total) => live_total,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    break;
+                }
+            };
+        }
+    })
+}
+
 #[post(
     "/timetrack",
     format = "application/json",
@@ -107,9 +248,10 @@ pub async fn create(
     let project_id = create_time_track_dto.project_id;
     let started_at = create_time_track_dto.started_at;
     let stopped_at = create_time_track_dto.stopped_at;
+    let comment = create_time_track_dto.comment;
 
     match time_track_service
-        .create(user, project_id, started_at, stopped_at)
+        .create(user, project_id, started_at, stopped_at, comment)
         .await
     {
         Ok(res) => Ok(Json(TimeTrackDto::from_time_track_with_project_name(
@@ -122,6 +264,14 @@ pub async fn create(
                     error_message: err.to_string(),
                 }),
             )),
+            TimeTrackError::StoppedInFuture | TimeTrackError::StoppedBeforeStarted => {
+                Err(status::Custom(
+                    Status::BadRequest,
+                    Json(ErrorResponse {
+                        error_message: err.to_string(),
+                    }),
+                ))
+            }
             _ => {
                 eprintln!("{}", err);
                 Err(status::Custom(
@@ -135,16 +285,23 @@ pub async fn create(
     }
 }
 
-#[get("/timetrack/<project_id>")]
+/// Lists the time tracks of the project. `include_running=false` leaves out the elapsed time of
+/// the running time track.
+#[get("/timetrack/<project_id>?<include_running>")]
 pub async fn get(
     time_track_service: &State<Arc<TimeTrackService>>,
     user: UserValidation,
     project_id: UuidValidation,
+    include_running: Option<bool>,
 ) -> Result<Json<Vec<TimeTrackDto>>, status::Custom<Json<ErrorResponse>>> {
     let user = &user.0;
     let project_id = project_id.0.to_string();
+    let include_running = include_running.unwrap_or(true);
 
-    match time_track_service.get_all(user, &project_id).await {
+    match time_track_service
+        .get_all(user, &project_id, include_running)
+        .await
+    {
         Ok(result) => {
             let time_track_items_dto = result
                 .0
@@ -173,6 +330,55 @@ pub async fn get(
     }
 }
 
+#[get("/timetrack/recent?<limit>&<cursor>")]
+pub async fn get_recent(
+    time_track_service: &State<Arc<TimeTrackService>>,
+    user: UserValidation,
+    limit: Option<usize>,
+    cursor: Option<String>,
+) -> Result<Json<RecentTimeTracksDto>, status::Custom<Json<ErrorResponse>>> {
+    let user = &user.0;
+    let limit = limit.unwrap_or(10).clamp(1, 50);
+
+    match time_track_service
+        .get_recent(user, limit, cursor.as_deref())
+        .await
+    {
+        Ok((time_tracks, next_cursor)) => Ok(Json(RecentTimeTracksDto {
+            time_tracks: time_tracks
+                .into_iter()
+                .map(|(tt, project_name)| {
+                    TimeTrackDto::from_time_track_with_project_name(tt, project_name)
+                })
+                .collect(),
+            next_cursor,
+        })),
+        Err(err) => match err {
+            TimeTrackError::InvalidCursor(_) => Err(status::Custom(
+                Status::BadRequest,
+                Json(ErrorResponse {
+                    error_message: err.to_string(),
+                }),
+            )),
+            TimeTrackError::IndexNotReady => Err(status::Custom(
+                Status::ServiceUnavailable,
+                Json(ErrorResponse {
+                    error_message: err.to_string(),
+                }),
+            )),
+            _ => {
+                eprintln!("{}", err);
+                Err(status::Custom(
+                    Status::InternalServerError,
+                    Json(ErrorResponse {
+                        error_message: String::from("An internal error occurred"),
+                    }),
+                ))
+            }
+        },
+    }
+}
+
 #[put(
     "/timetrack/<time_track_id>",
     format = "application/json",
@@ -189,6 +395,7 @@ pub async fn update(
     let project_id = update_time_track_dto.project_id;
     let new_started_at = update_time_track_dto.started_at;
     let new_stopped_at = update_time_track_dto.stopped_at;
+    let new_comment = update_time_track_dto.comment;
 
     match time_track_service
         .update(
@@ -197,6 +404,7 @@ pub async fn update(
             time_track_id,
             new_started_at,
             new_stopped_at,
+            new_comment,
         )
         .await
     {
@@ -210,6 +418,14 @@ pub async fn update(
                     error_message: err.to_string(),
                 }),
             )),
+            TimeTrackError::StoppedInFuture | TimeTrackError::StoppedBeforeStarted => {
+                Err(status::Custom(
+                    Status::BadRequest,
+                    Json(ErrorResponse {
+                        error_message: err.to_string(),
+                    }),
+                ))
+            }
             _ => {
                 eprintln!("{}", err);
                 Err(status::Custom(
@@ -223,13 +439,66 @@ pub async fn update(
     }
 }
 
-#[delete("/timetrack/<project_id>/<time_track_id>")]
+#[post(
+    "/timetrack/<project_id>/<time_track_id>/notes",
+    format = "application/json",
+    data = "<append_note_dto>"
+)]
+pub async fn append_note(
+    time_track_service: &State<Arc<TimeTrackService>>,
+    user: UserValidation,
+    project_id: UuidValidation,
+    time_track_id: UuidValidation,
+    append_note_dto: AppendNoteDto,
+) -> Result<Json<TimeTrackDto>, status::Custom<Json<ErrorResponse>>> {
+    let user = &user.0;
+    let project_id = project_id.0.to_string();
+    let time_track_id = time_track_id.0.to_string();
+
+    match time_track_service
+        .append_note(user, &project_id, &time_track_id, &append_note_dto.note)
+        .await
+    {
+        Ok(res) => Ok(Json(TimeTrackDto::from_time_track_with_project_name(
+            res.0, res.1,
+        ))),
+        Err(err) => match err {
+            TimeTrackError::NotFound | TimeTrackError::ProjectNotFound => Err(status::Custom(
+                Status::NotFound,
+                Json(ErrorResponse {
+                    error_message: err.to_string(),
+                }),
+            )),
+            TimeTrackError::TooManyNotes => Err(status::Custom(
+                Status::UnprocessableEntity,
+                Json(ErrorResponse {
+                    error_message: err.to_string(),
+                }),
+            )),
+            _ => {
+                eprintln!("{}", err);
+                Err(status::Custom(
+                    Status::InternalServerError,
+                    Json(ErrorResponse {
+                        error_message: String::from("An internal error occurred"),
+                    }),
+                ))
+            }
+        },
+    }
+}
+
+/// Responds with 204 No Content. `include_totals=true` responds with 200 and the deleted duration
+/// and the stored project total instead.
+#[delete("/timetrack/<project_id>/<time_track_id>?<include_totals>")]
 pub async fn delete(
     time_track_service: &State<Arc<TimeTrackService>>,
     user: UserValidation,
     project_id: UuidValidation,
     time_track_id: UuidValidation,
-) -> Result<status::NoContent, status::Custom<Json<ErrorResponse>>> {
+    include_totals: Option<bool>,
+) -> Result<Either<status::NoContent, Json<DeletedTimeTrackDto>>, status::Custom<Json<ErrorResponse>>>
+{
     let user = &user.0;
     let project_id = project_id.0.to_string();
     let time_track_id = time_track_id.0.to_string();
@@ -238,7 +507,10 @@ pub async fn delete(
         .delete(user, project_id, time_track_id)
         .await
     {
-        Ok(_) => Ok(status::NoContent),
+        Ok(_) if !include_totals.unwrap_or(false) => Ok(Either::Left(status::NoContent)),
+        Ok((deleted_duration, total_duration)) => Ok(Either::Right(Json(
+            DeletedTimeTrackDto::new(deleted_duration, total_duration),
+        ))),
         Err(err) => match err {
             TimeTrackError::NotFound => Err(status::Custom(
                 Status::NotFound,
@@ -258,3 +530,294 @@ pub async fn delete(
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        fairings::compression::ResponseCompression,
+        models::{time_track_model::MAX_NOTES, user_model::UserRole},
+        services::test_utils::{in_memory_services, test_client},
+    };
+    use chrono::Utc;
+    use rocket::{http::Header, local::asynchronous::Client, tokio::io::AsyncReadExt};
+
+    #[rocket::async_test]
+    async fn stream_emits_the_live_total_of_an_active_project() {
+        let (client, user, services) = test_client(routes()).await;
+        let project = services
+            .project_service
+            .create(&user, "sitt".to_string())
+            .await
+            .unwrap();
+        services
+            .time_track_service
+            .start(&user, &project.id)
+            .await
+            .unwrap();
+
+        let mut response = client
+            .get(format!("/timetrack/{}/stream", project.id))
+            .header(Header::new("x-api-key", user.api_key.unwrap()))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+
+        // Read until the end of the first event, as the stream stays open while active
+        let mut body = Vec::new();
+        let mut buffer = [0; 256];
+        while !body.ends_with(b"\n\n") {
+            let read = response.read(&mut buffer).await.unwrap();
+            assert!(read > 0, "Expected the stream to stay open");
+            body.extend_from_slice(&buffer[..read]);
+        }
+
+        let event = String::from_utf8(body).unwrap();
+        assert!(
+            event.contains("data:") && event.contains(&project.id),
+            "Expected an event for the project, got: {}",
+            event
+        );
+        assert!(
+            event.contains("\"status\":\"ACTIVE\""),
+            "Expected an ACTIVE status, got: {}",
+            event
+        );
+    }
+
+    #[rocket::async_test]
+    async fn stream_emits_events_when_compression_is_accepted() {
+        let services = in_memory_services().await;
+        let user = services.create_user("test", &UserRole::User).await;
+        let project = services
+            .project_service
+            .create(&user, "sitt".to_string())
+            .await
+            .unwrap();
+        services
+            .time_track_service
+            .start(&user, &project.id)
+            .await
+            .unwrap();
+
+        // The compression fairing must not wait for the end of the never ending stream
+        let rocket = rocket::build()
+            .manage(services.user_service)
+            .manage(services.project_service)
+            .manage(services.time_track_service)
+            .mount("/", routes())
+            .attach(ResponseCompression::new(0));
+        let client = Client::tracked(rocket).await.unwrap();
+
+        let mut response = client
+            .get(format!("/timetrack/{}/stream", project.id))
+            .header(Header::new("x-api-key", user.api_key.unwrap()))
+            .header(Header::new("Accept-Encoding", "gzip"))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.headers().get_one("Content-Encoding"), None);
+
+        let mut body = Vec::new();
+        let mut buffer = [0; 256];
+        while !body.ends_with(b"\n\n") {
+            let read = response.read(&mut buffer).await.unwrap();
+            assert!(read > 0, "Expected the stream to stay open");
+            body.extend_from_slice(&buffer[..read]);
+        }
+        let event = String::from_utf8(body).unwrap();
+        assert!(
+            event.contains("data:") && event.contains(&project.id),
+            "Expected an event for the project, got: {}",
+            event
+        );
+    }
+
+    #[rocket::async_test]
+    async fn delete_responds_with_the_adjusted_project_total() {
+        let (client, user, services) = test_client(routes()).await;
+        let project = services
+            .project_service
+            .create(&user, "sitt".to_string())
+            .await
+            .unwrap();
+        let stopped_at = Utc::now() - chrono::Duration::hours(1);
+        let mut time_track_ids = Vec::new();
+        for hours in [2, 3] {
+            let (time_track, _) = services
+                .time_track_service
+                .create(
+                    &user,
+                    project.id.clone(),
+                    stopped_at - chrono::Duration::hours(hours),
+                    stopped_at,
+                    None,
+                )
+                .await
+                .unwrap();
+            time_track_ids.push(time_track.id);
+        }
+
+        let api_key = user.api_key.unwrap();
+
+        let response = client
+            .delete(format!(
+                "/timetrack/{}/{}?include_totals=true",
+                project.id, time_track_ids[0]
+            ))
+            .header(Header::new("x-api-key", api_key.clone()))
+            .dispatch()
+            .await;
+
+        assert_eq!(response.status(), Status::Ok);
+        let deleted: DeletedTimeTrackDto = response.into_json().await.unwrap();
+        assert_eq!(deleted.deleted_duration, "2h");
+        assert_eq!(deleted.total_duration, "3h");
+
+        // Existing clients still get No Content
+        let response = client
+            .delete(format!("/timetrack/{}/{}", project.id, time_track_ids[1]))
+            .header(Header::new("x-api-key", api_key))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::NoContent);
+    }
+
+    #[rocket::async_test]
+    async fn comment_is_read_back_after_create_and_update() {
+        let (client, user, services) = test_client(routes()).await;
+        let project = services
+            .project_service
+            .create(&user, "sitt".to_string())
+            .await
+            .unwrap();
+        let api_key = user.api_key.unwrap();
+        let stopped_at = Utc::now() - chrono::Duration::hours(1);
+        let started_at = stopped_at - chrono::Duration::hours(1);
+        let body = |comment: &str| {
+            format!(
+                r#"{{"project_id": "{}", "started_at": "{}", "stopped_at": "{}", "comment": "{}"}}"#,
+                project.id,
+                started_at.to_rfc3339(),
+                stopped_at.to_rfc3339(),
+                comment
+            )
+        };
+
+        let response = client
+            .post("/timetrack")
+            .header(Header::new("x-api-key", api_key.clone()))
+            .header(rocket::http::ContentType::JSON)
+            .body(body("Fixed the login bug"))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let created: TimeTrackDto = response.into_json().await.unwrap();
+
+        let response = client
+            .put(format!("/timetrack/{}", created.time_track_id))
+            .header(Header::new("x-api-key", api_key.clone()))
+            .header(rocket::http::ContentType::JSON)
+            .body(body("Fixed the login and logout bugs"))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+
+        let response = client
+            .get(format!("/timetrack/{}", project.id))
+            .header(Header::new("x-api-key", api_key))
+            .dispatch()
+            .await;
+        let time_tracks: Vec<TimeTrackDto> = response.into_json().await.unwrap();
+        assert_eq!(created.comment.as_deref(), Some("Fixed the login bug"));
+        assert_eq!(
+            time_tracks[0].comment.as_deref(),
+            Some("Fixed the login and logout bugs"),
+            "Expected the edited comment to be stored"
+        );
+    }
+
+    #[rocket::async_test]
+    async fn append_note_returns_the_notes_of_the_time_track() {
+        let (client, user, services) = test_client(routes()).await;
+        let project = services
+            .project_service
+            .create(&user, "sitt".to_string())
+            .await
+            .unwrap();
+        let (time_track, _) = services
+            .time_track_service
+            .start(&user, &project.id)
+            .await
+            .unwrap();
+        let api_key = user.api_key.unwrap();
+
+        for note in ["Started on the login bug", "Found the cause"] {
+            let response = client
+                .post(format!("/timetrack/{}/{}/notes", project.id, time_track.id))
+                .header(Header::new("x-api-key", api_key.clone()))
+                .header(rocket::http::ContentType::JSON)
+                .body(format!(r#"{{"note": "{}"}}"#, note))
+                .dispatch()
+                .await;
+            assert_eq!(response.status(), Status::Ok);
+        }
+
+        let response = client
+            .get(format!("/timetrack/{}", project.id))
+            .header(Header::new("x-api-key", api_key.clone()))
+            .dispatch()
+            .await;
+        let time_tracks: Vec<TimeTrackDto> = response.into_json().await.unwrap();
+        let notes: Vec<&str> = time_tracks[0]
+            .notes
+            .iter()
+            .map(|note| note.text.as_str())
+            .collect();
+        assert_eq!(notes, ["Started on the login bug", "Found the cause"]);
+
+        let response = client
+            .post(format!("/timetrack/{}/{}/notes", project.id, time_track.id))
+            .header(Header::new("x-api-key", api_key))
+            .header(rocket::http::ContentType::JSON)
+            .body(r#"{"note": "   "}"#)
+            .dispatch()
+            .await;
+        assert_eq!(
+            response.status(),
+            Status::UnprocessableEntity,
+            "Expected a blank note to be rejected"
+        );
+    }
+
+    #[rocket::async_test]
+    async fn append_note_is_rejected_past_the_maximum_of_notes() {
+        let (client, user, services) = test_client(routes()).await;
+        let project = services
+            .project_service
+            .create(&user, "sitt".to_string())
+            .await
+            .unwrap();
+        let (time_track, _) = services
+            .time_track_service
+            .start(&user, &project.id)
+            .await
+            .unwrap();
+        for i in 0..MAX_NOTES {
+            services
+                .time_track_service
+                .append_note(&user, &project.id, &time_track.id, &format!("Note {}", i))
+                .await
+                .unwrap();
+        }
+
+        let response = client
+            .post(format!("/timetrack/{}/{}/notes", project.id, time_track.id))
+            .header(Header::new("x-api-key", user.api_key.unwrap()))
+            .header(rocket::http::ContentType::JSON)
+            .body(r#"{"note": "One too many"}"#)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+    }
+}
@@ -2,19 +2,31 @@ use super::validation::uuid_validation::UuidValidation;
 use super::{
     dtos::{
         common_dtos::ErrorResponse,
-        user_dtos::{CreateUserDto, UserDto},
+        user_dtos::{
+            ClaimAdminDto, ClaimedAdminDto, CreateUserDto, CreateUsersDto, CreatedUsersDto, UserDto,
+        },
     },
-    validation::user_validation::AdminValidation,
+    validation::user_validation::{AdminValidation, DefaultAdminValidation},
 };
+use crate::models::user_model::ApiKeyScope;
 use crate::services::user_service::{UserError, UserService};
 use rocket::get;
 use rocket::{
     delete, http::Status, post, response::status, routes, serde::json::Json, Route, State,
 };
 use std::sync::Arc;
+use validator::Validate;
 
 pub fn routes() -> Vec<Route> {
-    routes![create, get, get_all, delete]
+    routes![
+        create,
+        create_bulk,
+        claim_default_admin,
+        get,
+        get_by_name,
+        get_all,
+        delete
+    ]
 }
 
 #[post("/users", format = "application/json", data = "<create_user_dto>")]
@@ -26,19 +38,158 @@ pub async fn create(
     let admin_user = &admin_user.0;
     let name = &create_user_dto.name;
     let role = &create_user_dto.role;
+    let scope = &create_user_dto.scope;
 
-    match user_service.create(name, role, admin_user).await {
+    match user_service
+        .create_with_scope(name, role, scope, admin_user)
+        .await
+    {
         Ok(user) => Ok(status::Created::new("/users").body(Json(UserDto::from(user)))),
-        Err(err) => {
-            eprintln!("{}", err);
-            Err(status::Custom(
-                Status::InternalServerError,
+        Err(err) => match err {
+            UserError::AdminCreationDisabled => Err(status::Custom(
+                Status::Forbidden,
                 Json(ErrorResponse {
-                    error_message: String::from("An internal error occurred"),
+                    error_message: err.to_string(),
                 }),
-            ))
+            )),
+            UserError::NameTaken(_) => Err(status::Custom(
+                Status::Conflict,
+                Json(ErrorResponse {
+                    error_message: err.to_string(),
+                }),
+            )),
+            UserError::ReadOnlyAdmin => Err(status::Custom(
+                Status::UnprocessableEntity,
+                Json(ErrorResponse {
+                    error_message: err.to_string(),
+                }),
+            )),
+            _ => {
+                eprintln!("{}", err);
+                Err(status::Custom(
+                    Status::InternalServerError,
+                    Json(ErrorResponse {
+                        error_message: String::from("An internal error occurred"),
+                    }),
+                ))
+            }
+        },
+    }
+}
+
+#[post(
+    "/users/bulk",
+    format = "application/json",
+    data = "<create_users_dto>"
+)]
+pub async fn create_bulk(
+    user_service: &State<Arc<UserService>>,
+    admin_user: AdminValidation,
+    create_users_dto: CreateUsersDto,
+) -> Json<CreatedUsersDto> {
+    let admin_user = &admin_user.0;
+    let mut created_users = CreatedUsersDto::default();
+
+    // Continue past failed users, so one bad entry does not block onboarding the rest
+    for create_user_dto in create_users_dto.users {
+        let name = create_user_dto.name.clone();
+
+        if created_users.api_keys.contains_key(&name) || created_users.failed.contains_key(&name) {
+            created_users.failed.insert(
+                name,
+                String::from("The name is used more than once in the request"),
+            );
+            continue;
+        }
+
+        if create_user_dto.validate().is_err() {
+            created_users.failed.insert(
+                name,
+                String::from("name must be between 1 and 25 characters long"),
+            );
+            continue;
+        }
+
+        match user_service
+            .create_with_scope(
+                &name,
+                &create_user_dto.role,
+                &create_user_dto.scope,
+                admin_user,
+            )
+            .await
+        {
+            Ok(user) => {
+                created_users
+                    .api_keys
+                    .insert(name, user.api_key.unwrap_or_default());
+            }
+            Err(
+                err @ (UserError::AdminCreationDisabled
+                | UserError::NameTaken(_)
+                | UserError::ReadOnlyAdmin),
+            ) => {
+                created_users.failed.insert(name, err.to_string());
+            }
+            Err(err) => {
+                eprintln!("{}", err);
+                created_users
+                    .failed
+                    .insert(name, String::from("An internal error occurred"));
+            }
         }
     }
+
+    Json(created_users)
+}
+
+/// Replaces the default admin, that authenticates the request, with a personal admin
+#[post(
+    "/users/claim",
+    format = "application/json",
+    data = "<claim_admin_dto>"
+)]
+pub async fn claim_default_admin(
+    user_service: &State<Arc<UserService>>,
+    admin_user: DefaultAdminValidation,
+    claim_admin_dto: ClaimAdminDto,
+) -> Result<status::Created<Json<ClaimedAdminDto>>, status::Custom<Json<ErrorResponse>>> {
+    let default_admin = &admin_user.0;
+
+    match user_service
+        .claim_default_admin(default_admin, &claim_admin_dto.name)
+        .await
+    {
+        Ok((admin, default_admin_deleted)) => {
+            Ok(status::Created::new("/users").body(Json(ClaimedAdminDto {
+                admin: UserDto::from(admin),
+                default_admin_deleted,
+            })))
+        }
+        Err(err) => match err {
+            UserError::NotDefaultAdmin | UserError::AdminCreationDisabled => Err(status::Custom(
+                Status::Forbidden,
+                Json(ErrorResponse {
+                    error_message: err.to_string(),
+                }),
+            )),
+            UserError::NameTaken(_) => Err(status::Custom(
+                Status::Conflict,
+                Json(ErrorResponse {
+                    error_message: err.to_string(),
+                }),
+            )),
+            _ => {
+                eprintln!("{}", err);
+                Err(status::Custom(
+                    Status::InternalServerError,
+                    Json(ErrorResponse {
+                        error_message: String::from("An internal error occurred"),
+                    }),
+                ))
+            }
+        },
+    }
 }
 
 #[get("/users/<user_id>?<include_api_key>")]
@@ -48,10 +199,19 @@ pub async fn get(
     user_id: UuidValidation,
     include_api_key: Option<bool>,
 ) -> Result<Json<UserDto>, status::Custom<Json<ErrorResponse>>> {
-    let _ = admin_user.0;
     let user_id = &user_id.0.to_string();
     let include_api_key: bool = include_api_key.unwrap_or(false);
 
+    // Reading another user's API key would let a read-only key act with full access
+    if include_api_key && admin_user.0.scope == ApiKeyScope::ReadOnly {
+        return Err(status::Custom(
+            Status::Forbidden,
+            Json(ErrorResponse {
+                error_message: String::from("A read-only API key can't read API keys"),
+            }),
+        ));
+    }
+
     match user_service.get_by_id(user_id, include_api_key).await {
         Ok(user) => Ok(Json(UserDto::from(user))),
         Err(err) => match err {
@@ -74,6 +234,39 @@ pub async fn get(
     }
 }
 
+#[get("/users/by-name/<name>")]
+pub async fn get_by_name(
+    user_service: &State<Arc<UserService>>,
+    admin_user: AdminValidation,
+    name: &str,
+) -> Result<Json<Vec<UserDto>>, status::Custom<Json<ErrorResponse>>> {
+    let _ = admin_user.0;
+
+    match user_service.get_by_name(name).await {
+        Ok(users) => {
+            let user_dtos: Vec<UserDto> = users.into_iter().map(UserDto::from).collect();
+            Ok(Json(user_dtos))
+        }
+        Err(err) => match err {
+            UserError::NotFound => Err(status::Custom(
+                Status::NotFound,
+                Json(ErrorResponse {
+                    error_message: err.to_string(),
+                }),
+            )),
+            _ => {
+                eprintln!("{}", err);
+                Err(status::Custom(
+                    Status::InternalServerError,
+                    Json(ErrorResponse {
+                        error_message: String::from("An internal error occurred"),
+                    }),
+                ))
+            }
+        },
+    }
+}
+
 #[get("/users")]
 pub async fn get_all(
     user_service: &State<Arc<UserService>>,
@@ -137,3 +330,171 @@ pub async fn delete(
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        infrastructure::user_repository::{create_default_admin, DEFAULT_ADMIN_API_KEY},
+        models::user_model::{User, UserRole},
+        services::test_utils::test_client,
+    };
+    use rocket::http::{ContentType, Header};
+    use std::time::Duration;
+
+    #[rocket::async_test]
+    async fn create_bulk_continues_past_invalid_names() {
+        let (client, _, services) = test_client(routes()).await;
+        let admin = services.create_user("admin", &UserRole::Admin).await;
+
+        let response = client
+            .post("/users/bulk")
+            .header(ContentType::JSON)
+            .header(Header::new("x-api-key", admin.api_key.unwrap()))
+            .body(
+                r#"[
+                    {"name": "alice", "role": "USER"},
+                    {"name": "", "role": "USER"},
+                    {"name": "bob", "role": "ADMIN"}
+                ]"#,
+            )
+            .dispatch()
+            .await;
+
+        assert_eq!(response.status(), Status::Ok);
+        let created: CreatedUsersDto = response.into_json().await.unwrap();
+        assert_eq!(
+            created.api_keys.keys().collect::<Vec<_>>(),
+            vec!["alice", "bob"],
+            "Expected the valid users to be created"
+        );
+        assert!(
+            created.failed.contains_key(""),
+            "Expected the empty name to fail, got: {:?}",
+            created.failed
+        );
+
+        let alice = services
+            .user_service
+            .get_by_api_key(&created.api_keys["alice"])
+            .await
+            .unwrap();
+        assert_eq!(alice.name, "alice");
+    }
+
+    #[rocket::async_test]
+    async fn get_by_name_returns_every_user_with_the_name() {
+        let (client, _, services) = test_client(routes()).await;
+        let admin = services.create_user("admin", &UserRole::Admin).await;
+        for name in ["alice", "bob", "bob"] {
+            services
+                .user_service
+                .create(name, &UserRole::User, &admin)
+                .await
+                .unwrap();
+        }
+        let api_key = admin.api_key.unwrap();
+
+        for (name, expected_matches) in [("alice", 1), ("bob", 2)] {
+            let response = client
+                .get(format!("/users/by-name/{}", name))
+                .header(Header::new("x-api-key", api_key.clone()))
+                .dispatch()
+                .await;
+
+            assert_eq!(response.status(), Status::Ok);
+            let users: Vec<UserDto> = response.into_json().await.unwrap();
+            assert_eq!(users.len(), expected_matches);
+            assert!(users.iter().all(|u| u.name == name));
+            assert!(
+                users.iter().all(|u| u.api_key.is_none()),
+                "Expected no API keys in the lookup"
+            );
+        }
+
+        let response = client
+            .get("/users/by-name/carol")
+            .header(Header::new("x-api-key", api_key))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    #[rocket::async_test]
+    async fn claim_default_admin_replaces_the_default_admin() {
+        let (client, _, services) = test_client(routes()).await;
+        create_default_admin(services.user_service.repository.as_ref(), 1, Duration::ZERO)
+            .await
+            .unwrap();
+
+        let response = client
+            .post("/users/claim")
+            .header(Header::new("x-api-key", DEFAULT_ADMIN_API_KEY))
+            .header(ContentType::JSON)
+            .body(r#"{"name": "  alice  "}"#)
+            .dispatch()
+            .await;
+
+        assert_eq!(response.status(), Status::Created);
+        let claimed: ClaimedAdminDto = response.into_json().await.unwrap();
+        assert!(claimed.default_admin_deleted);
+        assert_eq!(claimed.admin.name, "alice");
+        assert_eq!(claimed.admin.role, UserRole::Admin);
+
+        // The default admin is gone, while the new admin's key works
+        let response = client
+            .get("/users")
+            .header(Header::new("x-api-key", DEFAULT_ADMIN_API_KEY))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Unauthorized);
+        let response = client
+            .get("/users")
+            .header(Header::new("x-api-key", claimed.admin.api_key.unwrap()))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+    }
+
+    #[rocket::async_test]
+    async fn read_only_admin_is_rejected_and_cannot_read_api_keys() {
+        let (client, user, services) = test_client(routes()).await;
+        let admin = services.create_user("admin", &UserRole::Admin).await;
+
+        let response = client
+            .post("/users")
+            .header(Header::new("x-api-key", admin.api_key.unwrap()))
+            .header(ContentType::JSON)
+            .body(r#"{"name": "viewer", "role": "ADMIN", "scope": "READ_ONLY"}"#)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+
+        // A read-only admin stored before creating them was rejected
+        let mut read_only_admin = User::new("viewer", &UserRole::Admin, "SYSTEM");
+        read_only_admin.scope = ApiKeyScope::ReadOnly;
+        services
+            .user_service
+            .repository
+            .create(&read_only_admin)
+            .await
+            .unwrap();
+        let api_key = read_only_admin.api_key.unwrap();
+
+        let response = client
+            .get(format!("/users/{}?include_api_key=true", user.id))
+            .header(Header::new("x-api-key", api_key.clone()))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Forbidden);
+
+        let response = client
+            .get(format!("/users/{}", user.id))
+            .header(Header::new("x-api-key", api_key))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let user: UserDto = response.into_json().await.unwrap();
+        assert!(user.api_key.is_none());
+    }
+}
@@ -1,8 +1,10 @@
 use std::sync::Arc;
 
-use crate::models::user_model::{User, UserRole};
+use crate::infrastructure::user_repository::DEFAULT_ADMIN_API_KEY;
+use crate::models::user_model::{ApiKeyScope, User, UserRole};
 use crate::services::user_service::UserService;
 use rocket::{
+    http::Method,
     outcome::Outcome,
     request::{self, FromRequest},
     Request, State,
@@ -18,9 +20,26 @@ pub enum UserValidationError {
     Unauthorized,
     #[error("Lacks the permissions to perform request")]
     Forbidden,
+    #[error("The API key is read-only")]
+    ReadOnly,
     #[error("Setup failed")]
     SetupFailed,
 }
+
+// Generated keys are 32 characters. The default admin's key is only accepted by DefaultAdminValidation.
+// Blank keys are rejected first, so they never reach the database.
+fn is_valid_api_key(api_key: &str) -> bool {
+    if api_key.trim().is_empty() {
+        return false;
+    }
+    api_key.len() == 32
+}
+
+// Read-only keys may only use methods that do not change anything
+fn is_allowed_by_scope(scope: &ApiKeyScope, method: Method) -> bool {
+    *scope == ApiKeyScope::ReadWrite || matches!(method, Method::Get | Method::Head)
+}
+
 pub struct UserValidation(pub User);
 
 #[rocket::async_trait]
@@ -54,14 +73,23 @@ impl<'r> FromRequest<'r> for UserValidation {
             )),
             1 => {
                 let api_key = keys[0];
-                if api_key.len() != 32 {
+                if !is_valid_api_key(api_key) {
                     return Outcome::Error((
                         rocket::http::Status::Unauthorized,
                         UserValidationError::Invalid,
                     ));
                 }
                 match user_service.get_by_api_key(api_key).await {
-                    Ok(user) => Outcome::Success(UserValidation(user)),
+                    Ok(user) => {
+                        if !is_allowed_by_scope(&user.scope, request.method()) {
+                            return Outcome::Error((
+                                rocket::http::Status::Forbidden,
+                                UserValidationError::ReadOnly,
+                            ));
+                        }
+
+                        Outcome::Success(UserValidation(user))
+                    }
                     Err(err) => {
                         eprintln!("{:#?}", err);
                         Outcome::Error((
@@ -112,7 +140,7 @@ impl<'r> FromRequest<'r> for AdminValidation {
             )),
             1 => {
                 let api_key = keys[0];
-                if api_key.len() != 32 {
+                if !is_valid_api_key(api_key) {
                     return Outcome::Error((
                         rocket::http::Status::Unauthorized,
                         UserValidationError::Invalid,
@@ -128,6 +156,13 @@ impl<'r> FromRequest<'r> for AdminValidation {
                             ));
                         }
 
+                        if !is_allowed_by_scope(&user.scope, request.method()) {
+                            return Outcome::Error((
+                                rocket::http::Status::Forbidden,
+                                UserValidationError::ReadOnly,
+                            ));
+                        }
+
                         // Return admin user
                         Outcome::Success(AdminValidation(user))
                     }
@@ -147,3 +182,172 @@ impl<'r> FromRequest<'r> for AdminValidation {
         }
     }
 }
+
+/// Only accepts the default admin's key, which is public, so it is only meant for claiming the default admin
+pub struct DefaultAdminValidation(pub User);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for DefaultAdminValidation {
+    type Error = UserValidationError;
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        // Retrieve the user service from the Rocket state
+        let user_service = match request.guard::<&State<Arc<UserService>>>().await {
+            Outcome::Success(service) => service,
+            Outcome::Error(_) => {
+                return Outcome::Error((
+                    rocket::http::Status::InternalServerError,
+                    UserValidationError::SetupFailed,
+                ))
+            }
+            Outcome::Forward(_) => {
+                return Outcome::Error((
+                    rocket::http::Status::Unauthorized,
+                    UserValidationError::Unauthorized,
+                ));
+            }
+        };
+
+        // Extract the API key from headers
+        let keys: Vec<_> = request.headers().get("x-api-key").collect();
+        match keys.len() {
+            0 => Outcome::Error((
+                rocket::http::Status::Unauthorized,
+                UserValidationError::Missing,
+            )),
+            1 => {
+                if keys[0] != DEFAULT_ADMIN_API_KEY {
+                    return Outcome::Error((
+                        rocket::http::Status::Forbidden,
+                        UserValidationError::Forbidden,
+                    ));
+                }
+                // The default admin no longer exists once it has been claimed or deleted
+                match user_service.get_by_api_key(DEFAULT_ADMIN_API_KEY).await {
+                    Ok(user) if user.role == UserRole::Admin => {
+                        Outcome::Success(DefaultAdminValidation(user))
+                    }
+                    Ok(_) => Outcome::Error((
+                        rocket::http::Status::Forbidden,
+                        UserValidationError::Forbidden,
+                    )),
+                    Err(err) => {
+                        eprintln!("{:#?}", err);
+                        Outcome::Error((
+                            rocket::http::Status::Unauthorized,
+                            UserValidationError::Unauthorized,
+                        ))
+                    }
+                }
+            }
+            _ => Outcome::Error((
+                rocket::http::Status::BadRequest,
+                UserValidationError::Invalid,
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        infrastructure::user_repository::create_default_admin, services::test_utils::test_client,
+    };
+    use rocket::{get, http::Header, local::asynchronous::Client, routes};
+
+    #[get("/guarded")]
+    fn guarded(user: UserValidation) -> String {
+        user.0.name
+    }
+
+    #[get("/admin_guarded")]
+    fn admin_guarded(admin: AdminValidation) -> String {
+        admin.0.name
+    }
+
+    #[get("/default_admin_guarded")]
+    fn default_admin_guarded(admin: DefaultAdminValidation) -> String {
+        admin.0.name
+    }
+
+    async fn client() -> Client {
+        test_client(routes![guarded, admin_guarded, default_admin_guarded])
+            .await
+            .0
+    }
+
+    #[test]
+    fn blank_api_keys_are_invalid() {
+        assert!(!is_valid_api_key(""));
+        assert!(!is_valid_api_key(&" ".repeat(32)));
+        assert!(!is_valid_api_key(&"\t".repeat(32)));
+        assert!(is_valid_api_key(&"a".repeat(32)));
+        assert!(!is_valid_api_key(DEFAULT_ADMIN_API_KEY));
+    }
+
+    #[rocket::async_test]
+    async fn empty_and_whitespace_api_key_headers_are_unauthorized() {
+        let client = client().await;
+
+        for api_key in [String::new(), " ".repeat(32)] {
+            for path in ["/guarded", "/admin_guarded"] {
+                let response = client
+                    .get(path)
+                    .header(Header::new("x-api-key", api_key.clone()))
+                    .dispatch()
+                    .await;
+                assert_eq!(
+                    response.status(),
+                    rocket::http::Status::Unauthorized,
+                    "Expected {:?} to be rejected on {}",
+                    api_key,
+                    path
+                );
+            }
+        }
+    }
+
+    #[rocket::async_test]
+    async fn default_admin_key_is_only_accepted_by_the_default_admin_guard() {
+        let (client, _, services) =
+            test_client(routes![guarded, admin_guarded, default_admin_guarded]).await;
+        create_default_admin(
+            services.user_service.repository.as_ref(),
+            1,
+            std::time::Duration::ZERO,
+        )
+        .await
+        .unwrap();
+
+        for path in ["/guarded", "/admin_guarded"] {
+            let response = client
+                .get(path)
+                .header(Header::new("x-api-key", DEFAULT_ADMIN_API_KEY))
+                .dispatch()
+                .await;
+            assert_eq!(
+                response.status(),
+                rocket::http::Status::Unauthorized,
+                "Expected the default admin's key to be rejected on {}",
+                path
+            );
+        }
+
+        let response = client
+            .get("/default_admin_guarded")
+            .header(Header::new("x-api-key", DEFAULT_ADMIN_API_KEY))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), rocket::http::Status::Ok);
+
+        // A personal admin is not the default admin
+        let admin = services.create_user("alice", &UserRole::Admin).await;
+        let response = client
+            .get("/default_admin_guarded")
+            .header(Header::new("x-api-key", admin.api_key.unwrap()))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), rocket::http::Status::Forbidden);
+    }
+}
@@ -1,5 +1,7 @@
+use async_trait::async_trait;
 use aws_config;
-use aws_sdk_dynamodb::Client;
+use aws_sdk_dynamodb::{error::SdkError, operation::describe_table::DescribeTableError, Client};
+use std::fmt::Debug;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -8,6 +10,12 @@ pub enum DbError {
     NotFound,
     #[error("Item from table '{table}' failed to be converted for id: {id}")]
     Convertion { table: String, id: String },
+    #[error("Invalid cursor: {0}")]
+    InvalidCursor(String),
+    #[error("Limit reached: {0}")]
+    LimitReached(String),
+    #[error("The index {0} is still being created")]
+    IndexNotReady(String),
     #[error("Unknown error: {0}")]
     Unknown(String),
 }
@@ -24,3 +32,46 @@ impl Database {
         Database { client }
     }
 }
+
+#[async_trait]
+pub trait TableStatusStore: Send + Sync + Debug {
+    /// Returns the status of the table, e.g. ACTIVE or CREATING, and NotFound if it does not exist
+    async fn table_status(&self, table_name: &str) -> Result<String, DbError>;
+}
+
+#[async_trait]
+impl TableStatusStore for Database {
+    async fn table_status(&self, table_name: &str) -> Result<String, DbError> {
+        let result = self
+            .client
+            .describe_table()
+            .table_name(table_name)
+            .send()
+            .await;
+
+        match result {
+            Ok(output) => output
+                .table()
+                .and_then(|table| table.table_status())
+                .map(|status| status.as_str().to_string())
+                .ok_or_else(|| {
+                    DbError::Unknown(format!(
+                        "{}: describe_table() returned no status",
+                        table_name
+                    ))
+                }),
+            Err(SdkError::ServiceError(service_err))
+                if matches!(
+                    service_err.err(),
+                    DescribeTableError::ResourceNotFoundException(_)
+                ) =>
+            {
+                Err(DbError::NotFound)
+            }
+            Err(err) => Err(DbError::Unknown(format!(
+                "{}: describe_table(): {:#?}",
+                table_name, err
+            ))),
+        }
+    }
+}
@@ -0,0 +1,360 @@
+use super::{
+    database::DbError, project_repository::ProjectStore, time_track_repository::TimeTrackStore,
+    user_repository::UserStore,
+};
+use crate::models::{
+    project_model::Project,
+    time_track_model::{TimeTrack, TimeTrackNote, TimeTrackStatus, MAX_NOTES},
+    user_model::User,
+};
+use async_trait::async_trait;
+use chrono::Utc;
+use std::sync::Mutex;
+
+// In-memory implementations of the stores, so the services can be tested without DynamoDB.
+// They mirror the keys and conditions used by the DynamoDB repositories.
+
+#[derive(Debug, Default)]
+pub struct InMemoryProjectStore {
+    projects: Mutex<Vec<Project>>,
+}
+
+#[async_trait]
+impl ProjectStore for InMemoryProjectStore {
+    async fn create(&self, project: &Project) -> Result<(), DbError> {
+        self.projects.lock().unwrap().push(project.clone());
+        Ok(())
+    }
+
+    async fn get(&self, user: &User, project_id: &str) -> Result<Project, DbError> {
+        self.projects
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|p| p.created_by == user.id && p.id == project_id)
+            .cloned()
+            .ok_or(DbError::NotFound)
+    }
+
+    async fn get_all(&self, user: &User) -> Result<Vec<Project>, DbError> {
+        Ok(self
+            .projects
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|p| p.created_by == user.id)
+            .cloned()
+            .collect())
+    }
+
+    async fn update(&self, user: &User, project: &mut Project) -> Result<Project, DbError> {
+        project.modified_at = Some(Utc::now());
+        project.modified_by = Some(user.id.to_string());
+
+        let mut projects = self.projects.lock().unwrap();
+        let existing = projects
+            .iter_mut()
+            .find(|p| p.created_by == project.created_by && p.id == project.id);
+
+        // Like the repository's conditional update_item, a missing item is not created
+        match existing {
+            Some(existing) => {
+                existing.name = project.name.clone();
+                existing.status = project.status.clone();
+                existing.total_duration = project.total_duration;
+                existing.tags = project.tags.clone();
+                existing.modified_at = project.modified_at;
+                existing.modified_by = project.modified_by.clone();
+                Ok(existing.clone())
+            }
+            None => Err(DbError::NotFound),
+        }
+    }
+
+    async fn delete(&self, user: &User, project_id: &str) -> Result<(), DbError> {
+        let mut projects = self.projects.lock().unwrap();
+        let index = projects
+            .iter()
+            .position(|p| p.created_by == user.id && p.id == project_id)
+            .ok_or(DbError::NotFound)?;
+
+        projects.remove(index);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct InMemoryTimeTrackStore {
+    time_tracks: Mutex<Vec<TimeTrack>>,
+}
+
+#[async_trait]
+impl TimeTrackStore for InMemoryTimeTrackStore {
+    async fn create(&self, time_track: &TimeTrack) -> Result<(), DbError> {
+        self.time_tracks.lock().unwrap().push(time_track.clone());
+        Ok(())
+    }
+
+    async fn get(&self, project_id: String, time_track_id: String) -> Result<TimeTrack, DbError> {
+        self.time_tracks
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|t| t.project_id == project_id && t.id == time_track_id)
+            .cloned()
+            .ok_or(DbError::NotFound)
+    }
+
+    async fn get_in_progress(&self, user: &User, project_id: &str) -> Result<TimeTrack, DbError> {
+        self.time_tracks
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|t| {
+                t.project_id == project_id
+                    && t.status == TimeTrackStatus::InProgress
+                    && t.created_by == user.id
+            })
+            .cloned()
+            .ok_or(DbError::NotFound)
+    }
+
+    async fn get_all(&self, project_id: &str, user: &User) -> Result<Vec<TimeTrack>, DbError> {
+        Ok(self
+            .time_tracks
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|t| t.project_id == project_id && t.created_by == user.id)
+            .cloned()
+            .collect())
+    }
+
+    // The cursor is the id of the last item on the previous page
+    async fn get_all_for_user(
+        &self,
+        user: &User,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> Result<(Vec<TimeTrack>, Option<String>), DbError> {
+        let mut time_tracks: Vec<TimeTrack> = self
+            .time_tracks
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|t| t.created_by == user.id)
+            .cloned()
+            .collect();
+        time_tracks.sort_by_key(|t| std::cmp::Reverse(t.started_at));
+
+        let start = match cursor {
+            Some(cursor) => {
+                time_tracks
+                    .iter()
+                    .position(|t| t.id == cursor)
+                    .ok_or_else(|| DbError::InvalidCursor(cursor.to_string()))?
+                    + 1
+            }
+            None => 0,
+        };
+
+        let page: Vec<TimeTrack> = time_tracks.into_iter().skip(start).collect();
+        let has_more = page.len() > limit;
+        let page: Vec<TimeTrack> = page.into_iter().take(limit).collect();
+        let next_cursor = match page.last() {
+            Some(last) if has_more => Some(last.id.clone()),
+            _ => None,
+        };
+
+        Ok((page, next_cursor))
+    }
+
+    async fn update(&self, time_track: &TimeTrack) -> Result<(), DbError> {
+        let mut time_tracks = self.time_tracks.lock().unwrap();
+        let existing = time_tracks
+            .iter_mut()
+            .find(|t| t.project_id == time_track.project_id && t.id == time_track.id);
+
+        // Like the repository's conditional update_item, a missing item is not created
+        // and the owner must match
+        match existing {
+            Some(existing) if existing.created_by == time_track.created_by => {
+                existing.status = time_track.status.clone();
+                existing.started_at = time_track.started_at;
+                existing.total_duration = time_track.total_duration;
+                existing.comment = time_track.comment.clone();
+                if time_track.stopped_at.is_some() {
+                    existing.stopped_at = time_track.stopped_at;
+                }
+                if time_track.last_heartbeat_at.is_some() {
+                    existing.last_heartbeat_at = time_track.last_heartbeat_at;
+                }
+            }
+            _ => return Err(DbError::NotFound),
+        }
+
+        Ok(())
+    }
+
+    async fn append_note(
+        &self,
+        user: &User,
+        project_id: &str,
+        time_track_id: &str,
+        note: &TimeTrackNote,
+    ) -> Result<TimeTrack, DbError> {
+        let mut time_tracks = self.time_tracks.lock().unwrap();
+        let time_track = time_tracks
+            .iter_mut()
+            .find(|t| {
+                t.project_id == project_id && t.id == time_track_id && t.created_by == user.id
+            })
+            .ok_or(DbError::NotFound)?;
+
+        if time_track.notes.len() >= MAX_NOTES {
+            return Err(DbError::LimitReached(format!(
+                "time track '{}' has {} notes",
+                time_track_id, MAX_NOTES
+            )));
+        }
+
+        time_track.notes.push(note.clone());
+        Ok(time_track.clone())
+    }
+
+    async fn delete(
+        &self,
+        user: &User,
+        project_id: String,
+        time_track_id: String,
+    ) -> Result<TimeTrack, DbError> {
+        let mut time_tracks = self.time_tracks.lock().unwrap();
+        let index = time_tracks
+            .iter()
+            .position(|t| {
+                t.project_id == project_id && t.id == time_track_id && t.created_by == user.id
+            })
+            .ok_or(DbError::NotFound)?;
+
+        Ok(time_tracks.remove(index))
+    }
+
+    async fn delete_for_project(&self, project_id: &str) -> Result<(), DbError> {
+        self.time_tracks
+            .lock()
+            .unwrap()
+            .retain(|t| t.project_id != project_id);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct InMemoryUserStore {
+    users: Mutex<Vec<User>>,
+}
+
+#[async_trait]
+impl UserStore for InMemoryUserStore {
+    async fn create(&self, user: &User) -> Result<(), DbError> {
+        self.users.lock().unwrap().push(user.clone());
+        Ok(())
+    }
+
+    async fn get_by_api_key(&self, api_key: &str) -> Result<User, DbError> {
+        self.users
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|u| u.api_key.as_deref() == Some(api_key))
+            .cloned()
+            .ok_or(DbError::NotFound)
+    }
+
+    async fn get_by_id(&self, id: &str) -> Result<User, DbError> {
+        self.users
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|u| u.id == id)
+            .cloned()
+            .ok_or(DbError::NotFound)
+    }
+
+    async fn get_by_name(&self, name: &str) -> Result<Vec<User>, DbError> {
+        Ok(self
+            .users
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|u| u.name == name)
+            .cloned()
+            .collect())
+    }
+
+    async fn get_all(&self) -> Result<Vec<User>, DbError> {
+        let users = self.users.lock().unwrap();
+
+        // The DynamoDB repository returns NotFound for an empty table
+        if users.is_empty() {
+            return Err(DbError::NotFound);
+        }
+
+        Ok(users.clone())
+    }
+
+    async fn delete(&self, user: &User) -> Result<(), DbError> {
+        let mut users = self.users.lock().unwrap();
+        let index = users
+            .iter()
+            .position(|u| u.id == user.id && u.api_key == user.api_key)
+            .ok_or(DbError::NotFound)?;
+
+        users.remove(index);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::user_model::UserRole;
+
+    #[tokio::test]
+    async fn get_all_for_user_spans_projects() {
+        let store = InMemoryTimeTrackStore::default();
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let other_user = User::new("other", &UserRole::User, "SYSTEM");
+        let now = Utc::now();
+
+        for (i, project_id) in ["project_a", "project_b", "project_c"].iter().enumerate() {
+            let mut time_track = TimeTrack::new(*project_id, &user);
+            time_track.started_at = now - chrono::Duration::hours(i as i64);
+            store.create(&time_track).await.unwrap();
+        }
+        store
+            .create(&TimeTrack::new("project_a", &other_user))
+            .await
+            .unwrap();
+
+        let (first_page, cursor) = store.get_all_for_user(&user, 2, None).await.unwrap();
+        let (last_page, last_cursor) = store
+            .get_all_for_user(&user, 2, cursor.as_deref())
+            .await
+            .unwrap();
+
+        let project_ids: Vec<&str> = first_page
+            .iter()
+            .chain(last_page.iter())
+            .map(|t| t.project_id.as_str())
+            .collect();
+        assert_eq!(
+            project_ids,
+            vec!["project_a", "project_b", "project_c"],
+            "Expected the user's time tracks across projects, newest first"
+        );
+        assert!(
+            last_cursor.is_none(),
+            "Expected no cursor after the last page"
+        );
+    }
+}
@@ -1,4 +1,6 @@
 pub mod database;
+#[cfg(test)]
+pub mod in_memory;
 pub mod project_repository;
 pub mod time_track_repository;
 pub mod user_repository;
@@ -1,23 +1,33 @@
 use super::{
     database::{Database, DbError},
-    utils::{get_datetime_value, get_string_value},
+    utils::{get_datetime_value, get_string_set_value, get_string_value},
 };
 use crate::models::{project_model::{Project, ProjectStatus}, user_model::User};
+use async_trait::async_trait;
 use aws_sdk_dynamodb::{
     error::SdkError,
-    operation::create_table::CreateTableError,
+    operation::{create_table::CreateTableError, update_item::UpdateItemError},
     types::{AttributeDefinition, AttributeValue, KeySchemaElement, KeyType, ScalarAttributeType},
 };
 use chrono::{DateTime, Utc};
 use humantime::{format_duration, parse_duration};
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, fmt::Debug, sync::Arc};
+
+#[async_trait]
+pub trait ProjectStore: Send + Sync + Debug {
+    async fn create(&self, project: &Project) -> Result<(), DbError>;
+    async fn get(&self, user: &User, project_id: &str) -> Result<Project, DbError>;
+    async fn get_all(&self, user: &User) -> Result<Vec<Project>, DbError>;
+    async fn update(&self, user: &User, project: &mut Project) -> Result<Project, DbError>;
+    async fn delete(&self, user: &User, project_id: &str) -> Result<(), DbError>;
+}
 
 #[derive(Debug)]
 pub struct ProjectRepository {
     db: Arc<Database>,
 }
 
-static TABLE_NAME: &str = "projects";
+pub static TABLE_NAME: &str = "projects";
 
 impl ProjectRepository {
     pub async fn build(db: Arc<Database>) -> Result<ProjectRepository, DbError> {
@@ -67,7 +77,7 @@ impl ProjectRepository {
                     // If the error is not, that the table already exists => throw error
                     if info
                         .message()
-                        .map_or(true, |msg| !msg.contains("Table already exists"))
+                        .is_none_or(|msg| !msg.contains("Table already exists"))
                     {
                         return Err(DbError::Unknown(format!("{:#?}", service_err)));
                     }
@@ -79,75 +89,9 @@ impl ProjectRepository {
         Ok(Self { db })
     }
 
-    pub async fn create(&self, project: &Project) -> Result<(), DbError> {
-        let item = ProjectRepository::convert_project_to_item(project);
-
-        self.db
-            .client
-            .put_item()
-            .table_name(TABLE_NAME)
-            .set_item(Some(item))
-            .send()
-            .await
-            .map(|_| ())
-            .map_err(|err| DbError::Unknown(format!("{}: {:#?}", TABLE_NAME, err)))
-    }
-
-    pub async fn get(&self, user: &User, project_id: &str) -> Result<Project, DbError> {
-        let result = self
-            .db
-            .client
-            .get_item()
-            .table_name(TABLE_NAME)
-            .key("created_by", AttributeValue::S(user.id.to_string()))
-            .key("id", AttributeValue::S(project_id.to_string()))
-            .send()
-            .await;
-
-        match result {
-            Ok(output) => match &output.item {
-                Some(item) => {
-                    let project = Self::convert_item_to_project(item)?;
-                    Ok(project)
-                },
-                None => Err(DbError::NotFound),
-            },
-            Err(err) => Err(DbError::Unknown(format!("{}: {:#?}", TABLE_NAME, err))),
-        }
-    }
-
-    pub async fn get_all(&self, user: &User) -> Result<Vec<Project>, DbError> {
-        let result = self
-            .db
-            .client
-            .query()
-            .table_name(TABLE_NAME)
-            .key_condition_expression("created_by = :created_by")
-            .expression_attribute_values(":created_by", AttributeValue::S(user.id.to_string()))
-            .send()
-            .await;
-
-        match result {
-            Ok(items) => match items.items {
-                Some(items) => {
-                    let mut projects = Vec::new();
-                    for item in items.iter() {
-                        let project = Self::convert_item_to_project(item)?;
-                        projects.push(project);
-                    }
-                    Ok(projects)
-                }
-                None => Err(DbError::NotFound),
-            },
-            Err(err) => Err(DbError::Unknown(format!("{}: {:#?}", TABLE_NAME, err))),
-        }
-    }
-
-    pub async fn update(&self, user: &User, project: &mut Project) -> Result<Project, DbError> {
-        // Update modified at & by
-        project.modified_at = Some(Utc::now());
-        project.modified_by = Some(user.id.to_string());
-
+    /// Builds the update expression for the mutable fields of the project.
+    /// `id`, `created_at` and `created_by` are immutable and never part of it.
+    fn update_expression(project: &Project) -> (String, HashMap<String, AttributeValue>) {
         let mut item = HashMap::new();
 
         // Create a list of updates that need to happen to the DynamoDB item
@@ -181,56 +125,23 @@ impl ProjectRepository {
             item.insert(String::from(":modified_by"), AttributeValue::S(modified_by));
         }
 
-        // Add the SET keyword only once
-        let update_expression = format!("SET {}", updates.join(", "));
-
-        let result = self
-            .db
-            .client
-            .update_item()
-            .table_name(TABLE_NAME)
-            .key(
-                "created_by",
-                AttributeValue::S(project.created_by.to_string()),
-            )
-            .key("id", AttributeValue::S(project.id.to_string()))
-            .update_expression(update_expression)
-            .set_expression_attribute_values(Some(item))
-            .return_values(aws_sdk_dynamodb::types::ReturnValue::AllNew)
-            .send()
-            .await;
-
-        match result {
-            Ok(item) => match &item.attributes {
-                Some(attr) => {
-                    let project = Self::convert_item_to_project(attr)?;
-                    Ok(project)
-                }
-                None => Err(DbError::NotFound),
-            },
-            Err(err) => Err(DbError::Unknown(format!("{}: {:#?}", TABLE_NAME, err))),
+        if !project.tags.is_empty() {
+            updates.push("tags = :tags");
+            item.insert(
+                String::from(":tags"),
+                AttributeValue::Ss(project.tags.clone()),
+            );
         }
-    }
 
-    pub async fn delete(&self, user: &User, project_id: &str) -> Result<(), DbError> {
-        let result = self
-            .db
-            .client
-            .delete_item()
-            .table_name(TABLE_NAME)
-            .key("created_by", AttributeValue::S(user.id.to_string()))
-            .key("id", AttributeValue::S(project_id.to_string()))
-            .return_values(aws_sdk_dynamodb::types::ReturnValue::AllOld)
-            .send()
-            .await;
+        // Add the SET keyword only once
+        let mut update_expression = format!("SET {}", updates.join(", "));
 
-        match result {
-            Ok(item) => match item.attributes {
-                Some(_) => Ok(()),
-                None => Err(DbError::NotFound),
-            },
-            Err(err) => Err(DbError::Unknown(format!("{}: {:#?}", TABLE_NAME, err))),
+        // An empty string set can not be stored, so removing all tags removes the attribute
+        if project.tags.is_empty() {
+            update_expression.push_str(" REMOVE tags");
         }
+
+        (update_expression, item)
     }
 
     fn convert_project_to_item(project: &Project) -> HashMap<String, AttributeValue> {
@@ -272,6 +183,14 @@ impl ProjectRepository {
             item.insert(key_modified_by, AttributeValue::S(modified_by.to_string()));
         }
 
+        // DynamoDB does not store empty sets
+        if !project.tags.is_empty() {
+            item.insert(
+                String::from("tags"),
+                AttributeValue::Ss(project.tags.clone()),
+            );
+        }
+
         item
     }
 
@@ -300,6 +219,9 @@ impl ProjectRepository {
                 }
             }
         };
+        // A string set comes back in any order
+        let mut tags = get_string_set_value(item, "tags");
+        tags.sort();
         let created_at = get_datetime_value(item, "created_at")?;
         let created_by = get_string_value(item, "created_by")?;
 
@@ -319,6 +241,7 @@ impl ProjectRepository {
             name,
             status,
             total_duration,
+            tags,
             created_at,
             created_by,
             modified_at,
@@ -326,3 +249,218 @@ impl ProjectRepository {
         })
     }
 }
+
+#[async_trait]
+impl ProjectStore for ProjectRepository {
+    async fn create(&self, project: &Project) -> Result<(), DbError> {
+        let item = ProjectRepository::convert_project_to_item(project);
+
+        self.db
+            .client
+            .put_item()
+            .table_name(TABLE_NAME)
+            .set_item(Some(item))
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(|err| DbError::Unknown(format!("{}: {:#?}", TABLE_NAME, err)))
+    }
+
+    async fn get(&self, user: &User, project_id: &str) -> Result<Project, DbError> {
+        let result = self
+            .db
+            .client
+            .get_item()
+            .table_name(TABLE_NAME)
+            .key("created_by", AttributeValue::S(user.id.to_string()))
+            .key("id", AttributeValue::S(project_id.to_string()))
+            // Strongly consistent, as start and stop decide on the status written just before
+            .consistent_read(true)
+            .send()
+            .await;
+
+        match result {
+            Ok(output) => match &output.item {
+                Some(item) => {
+                    let project = Self::convert_item_to_project(item)?;
+                    Ok(project)
+                },
+                None => Err(DbError::NotFound),
+            },
+            Err(err) => Err(DbError::Unknown(format!("{}: {:#?}", TABLE_NAME, err))),
+        }
+    }
+
+    async fn get_all(&self, user: &User) -> Result<Vec<Project>, DbError> {
+        let result = self
+            .db
+            .client
+            .query()
+            .table_name(TABLE_NAME)
+            .key_condition_expression("created_by = :created_by")
+            .expression_attribute_values(":created_by", AttributeValue::S(user.id.to_string()))
+            .send()
+            .await;
+
+        match result {
+            Ok(items) => match items.items {
+                Some(items) => {
+                    let mut projects = Vec::new();
+                    for item in items.iter() {
+                        let project = Self::convert_item_to_project(item)?;
+                        projects.push(project);
+                    }
+                    Ok(projects)
+                }
+                None => Err(DbError::NotFound),
+            },
+            Err(err) => Err(DbError::Unknown(format!("{}: {:#?}", TABLE_NAME, err))),
+        }
+    }
+
+    async fn update(&self, user: &User, project: &mut Project) -> Result<Project, DbError> {
+        // Update modified at & by
+        project.modified_at = Some(Utc::now());
+        project.modified_by = Some(user.id.to_string());
+
+        let (update_expression, item) = Self::update_expression(project);
+
+        let result = self
+            .db
+            .client
+            .update_item()
+            .table_name(TABLE_NAME)
+            .key(
+                "created_by",
+                AttributeValue::S(project.created_by.to_string()),
+            )
+            .key("id", AttributeValue::S(project.id.to_string()))
+            .update_expression(update_expression)
+            // Never create a new item, e.g. if the key fields were changed on the project
+            .condition_expression("attribute_exists(id)")
+            .set_expression_attribute_values(Some(item))
+            .return_values(aws_sdk_dynamodb::types::ReturnValue::AllNew)
+            .send()
+            .await;
+
+        match result {
+            Ok(item) => match &item.attributes {
+                Some(attr) => {
+                    let project = Self::convert_item_to_project(attr)?;
+                    Ok(project)
+                }
+                None => Err(DbError::NotFound),
+            },
+            Err(SdkError::ServiceError(service_err))
+                if matches!(
+                    service_err.err(),
+                    UpdateItemError::ConditionalCheckFailedException(_)
+                ) =>
+            {
+                Err(DbError::NotFound)
+            }
+            Err(err) => Err(DbError::Unknown(format!("{}: {:#?}", TABLE_NAME, err))),
+        }
+    }
+
+    async fn delete(&self, user: &User, project_id: &str) -> Result<(), DbError> {
+        let result = self
+            .db
+            .client
+            .delete_item()
+            .table_name(TABLE_NAME)
+            .key("created_by", AttributeValue::S(user.id.to_string()))
+            .key("id", AttributeValue::S(project_id.to_string()))
+            .return_values(aws_sdk_dynamodb::types::ReturnValue::AllOld)
+            .send()
+            .await;
+
+        match result {
+            Ok(item) => match item.attributes {
+                Some(_) => Ok(()),
+                None => Err(DbError::NotFound),
+            },
+            Err(err) => Err(DbError::Unknown(format!("{}: {:#?}", TABLE_NAME, err))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update_expression_never_sets_immutable_fields() {
+        let mut project = Project::new(String::from("sitt"), "user_id");
+        project.modified_at = Some(Utc::now());
+        project.modified_by = Some(String::from("user_id"));
+
+        let (update_expression, values) = ProjectRepository::update_expression(&project);
+
+        for field in ["id", "created_at", "created_by"] {
+            assert!(
+                !update_expression
+                    .split(", ")
+                    .any(|update| update.trim_start_matches("SET ").starts_with(field)),
+                "Expected {} to not be updated, got: {}",
+                field,
+                update_expression
+            );
+            assert!(!values.contains_key(&format!(":{}", field)));
+        }
+    }
+
+    #[test]
+    fn tags_are_stored_as_a_string_set() {
+        let mut project = Project::new(String::from("sitt"), "user_id");
+        project.tags = vec![String::from("billable"), String::from("client")];
+
+        let item = ProjectRepository::convert_project_to_item(&project);
+        assert_eq!(
+            item.get("tags"),
+            Some(&AttributeValue::Ss(project.tags.clone()))
+        );
+
+        let (update_expression, values) = ProjectRepository::update_expression(&project);
+        assert!(update_expression.contains("tags = :tags"));
+        assert_eq!(
+            values.get(":tags"),
+            Some(&AttributeValue::Ss(project.tags.clone()))
+        );
+
+        let converted = ProjectRepository::convert_item_to_project(&item).unwrap();
+        assert_eq!(converted.tags, project.tags);
+    }
+
+    #[test]
+    fn tags_are_sorted_when_read() {
+        let project = Project::new(String::from("sitt"), "user_id");
+        let mut item = ProjectRepository::convert_project_to_item(&project);
+        item.insert(
+            String::from("tags"),
+            AttributeValue::Ss(vec![String::from("internal"), String::from("billable")]),
+        );
+
+        let converted = ProjectRepository::convert_item_to_project(&item).unwrap();
+        assert_eq!(converted.tags, vec!["billable", "internal"]);
+    }
+
+    #[test]
+    fn empty_tags_are_removed_instead_of_stored() {
+        let project = Project::new(String::from("sitt"), "user_id");
+
+        let item = ProjectRepository::convert_project_to_item(&project);
+        assert!(!item.contains_key("tags"), "Expected no empty string set");
+
+        let (update_expression, values) = ProjectRepository::update_expression(&project);
+        assert!(
+            update_expression.ends_with(" REMOVE tags"),
+            "Expected the tags to be removed, got: {}",
+            update_expression
+        );
+        assert!(!values.contains_key(":tags"));
+
+        let converted = ProjectRepository::convert_item_to_project(&item).unwrap();
+        assert!(converted.tags.is_empty());
+    }
+}
@@ -3,24 +3,76 @@ use super::{
     utils::{get_datetime_value, get_string_value},
 };
 use crate::models::{
-    time_track_model::{TimeTrack, TimeTrackStatus},
+    time_track_model::{duration_between, TimeTrack, TimeTrackNote, TimeTrackStatus, MAX_NOTES},
     user_model::User,
 };
+use async_trait::async_trait;
 use aws_sdk_dynamodb::{
     error::SdkError,
-    operation::{create_table::CreateTableError, delete_item::DeleteItemError},
-    types::{AttributeDefinition, AttributeValue, KeySchemaElement, KeyType, ScalarAttributeType},
+    operation::{
+        create_table::CreateTableError, delete_item::DeleteItemError, update_item::UpdateItemError,
+        update_table::UpdateTableError,
+    },
+    types::{
+        AttributeDefinition, AttributeValue, CreateGlobalSecondaryIndexAction,
+        GlobalSecondaryIndex, GlobalSecondaryIndexUpdate, IndexStatus, KeySchemaElement, KeyType,
+        Projection, ProjectionType, ReturnValuesOnConditionCheckFailure, ScalarAttributeType,
+    },
 };
 use chrono::{DateTime, Utc};
 use humantime::{format_duration, parse_duration};
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+#[async_trait]
+pub trait TimeTrackStore: Send + Sync + Debug {
+    async fn create(&self, time_track: &TimeTrack) -> Result<(), DbError>;
+    async fn get(&self, project_id: String, time_track_id: String) -> Result<TimeTrack, DbError>;
+    async fn get_in_progress(&self, user: &User, project_id: &str) -> Result<TimeTrack, DbError>;
+    async fn get_all(&self, project_id: &str, user: &User) -> Result<Vec<TimeTrack>, DbError>;
+    /// Returns the time tracks of the user across all projects, newest first, together with
+    /// the cursor to the next page (if there are more items)
+    async fn get_all_for_user(
+        &self,
+        user: &User,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> Result<(Vec<TimeTrack>, Option<String>), DbError>;
+    async fn update(&self, time_track: &TimeTrack) -> Result<(), DbError>;
+    /// Appends the note to the notes of the user's time track and returns the updated time track.
+    /// Fails with `DbError::LimitReached` when the time track already has `MAX_NOTES` notes.
+    async fn append_note(
+        &self,
+        user: &User,
+        project_id: &str,
+        time_track_id: &str,
+        note: &TimeTrackNote,
+    ) -> Result<TimeTrack, DbError>;
+    async fn delete(
+        &self,
+        user: &User,
+        project_id: String,
+        time_track_id: String,
+    ) -> Result<TimeTrack, DbError>;
+    async fn delete_for_project(&self, project_id: &str) -> Result<(), DbError>;
+}
 
 #[derive(Debug)]
 pub struct TimeTrackRepository {
     db: Arc<Database>,
+    // Until the created_by index is active, the time tracks of a user can't be queried
+    created_by_index_active: AtomicBool,
 }
 
-static TABLE_NAME: &str = "time_trackings";
+pub static TABLE_NAME: &str = "time_trackings";
+static CREATED_BY_INDEX: &str = "created_by_index";
 
 impl TimeTrackRepository {
     pub async fn build(db: Arc<Database>) -> Result<Self, DbError> {
@@ -63,17 +115,20 @@ impl TimeTrackRepository {
             .key_schema(keyschema_part)
             .attribute_definitions(attr_sort)
             .key_schema(keyschema_sort)
+            .attribute_definitions(Self::created_by_index_attribute("created_by"))
+            .attribute_definitions(Self::created_by_index_attribute("started_at"))
+            .global_secondary_indexes(Self::created_by_index())
             .send()
             .await;
 
         // Check if there is an error creating the table
-        if let Err(SdkError::ServiceError(service_err)) = result {
+        if let Err(SdkError::ServiceError(service_err)) = &result {
             match service_err.err() {
                 CreateTableError::ResourceInUseException(info) => {
                     // If the error is not, that the table already exists => throw error
                     if info
                         .message()
-                        .map_or(true, |msg| !msg.contains("Table already exists"))
+                        .is_none_or(|msg| !msg.contains("Table already exists"))
                     {
                         return Err(DbError::Unknown(format!("{:#?}", service_err)));
                     }
@@ -82,10 +137,461 @@ impl TimeTrackRepository {
             }
         }
 
-        Ok(Self { db })
+        // A created table has the index from the start, only existing tables may have to get it
+        let repository = Self {
+            db,
+            created_by_index_active: AtomicBool::new(result.is_ok()),
+        };
+        if result.is_err() {
+            repository.ensure_created_by_index().await?;
+        }
+
+        Ok(repository)
+    }
+
+    fn created_by_index_attribute(name: &str) -> AttributeDefinition {
+        AttributeDefinition::builder()
+            .attribute_name(name)
+            .attribute_type(ScalarAttributeType::S)
+            .build()
+            .unwrap_or_else(|_| {
+                panic!(
+                    "Error building the attribute '{}' for the {} index",
+                    name, CREATED_BY_INDEX
+                )
+            })
+    }
+
+    fn created_by_index_key_schema() -> Vec<KeySchemaElement> {
+        [
+            ("created_by", KeyType::Hash),
+            ("started_at", KeyType::Range),
+        ]
+        .into_iter()
+        .map(|(name, key_type)| {
+            KeySchemaElement::builder()
+                .attribute_name(name)
+                .key_type(key_type)
+                .build()
+                .unwrap_or_else(|_| {
+                    panic!(
+                        "Error building the key schema '{}' for the {} index",
+                        name, CREATED_BY_INDEX
+                    )
+                })
+        })
+        .collect()
+    }
+
+    // Index on created_by sorted by started_at, so a user's time tracks across all projects
+    // can be queried without scanning the table
+    fn created_by_index() -> GlobalSecondaryIndex {
+        GlobalSecondaryIndex::builder()
+            .index_name(CREATED_BY_INDEX)
+            .set_key_schema(Some(Self::created_by_index_key_schema()))
+            .projection(
+                Projection::builder()
+                    .projection_type(ProjectionType::All)
+                    .build(),
+            )
+            .build()
+            .unwrap_or_else(|_| {
+                panic!(
+                    "Error building the {} index for table: {}",
+                    CREATED_BY_INDEX, TABLE_NAME
+                )
+            })
+    }
+
+    async fn created_by_index_status(&self) -> Result<Option<IndexStatus>, DbError> {
+        let table = self
+            .db
+            .client
+            .describe_table()
+            .table_name(TABLE_NAME)
+            .send()
+            .await
+            .map_err(|err| {
+                DbError::Unknown(format!("{}, describe_table(): {:#?}", TABLE_NAME, err))
+            })?;
+
+        let status = table.table().and_then(|table| {
+            table
+                .global_secondary_indexes()
+                .iter()
+                .find(|index| index.index_name() == Some(CREATED_BY_INDEX))
+                .map(|index| {
+                    index
+                        .index_status()
+                        .cloned()
+                        .unwrap_or(IndexStatus::Creating)
+                })
+        });
+
+        Ok(status)
+    }
+
+    // Fails with `DbError::IndexNotReady` while the index is still being created, which can take
+    // a while on a table with many time tracks
+    async fn ensure_created_by_index_active(&self) -> Result<(), DbError> {
+        if self.created_by_index_active.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        if self.created_by_index_status().await? != Some(IndexStatus::Active) {
+            return Err(DbError::IndexNotReady(CREATED_BY_INDEX.to_string()));
+        }
+        self.created_by_index_active.store(true, Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    // Tables created before the index existed do not get it from create_table, so it is added
+    async fn ensure_created_by_index(&self) -> Result<(), DbError> {
+        match self.created_by_index_status().await? {
+            Some(IndexStatus::Active) => {
+                self.created_by_index_active.store(true, Ordering::Relaxed);
+                return Ok(());
+            }
+            Some(status) => {
+                println!(
+                    "The {} index is {}, recent time tracks can't be listed until it is active",
+                    CREATED_BY_INDEX,
+                    status.as_str()
+                );
+                return Ok(());
+            }
+            None => println!(
+                "Adding the {} index, recent time tracks can't be listed until it is active",
+                CREATED_BY_INDEX
+            ),
+        }
+
+        let create_index = CreateGlobalSecondaryIndexAction::builder()
+            .index_name(CREATED_BY_INDEX)
+            .set_key_schema(Some(Self::created_by_index_key_schema()))
+            .projection(
+                Projection::builder()
+                    .projection_type(ProjectionType::All)
+                    .build(),
+            )
+            .build()
+            .map_err(|err| DbError::Unknown(format!("{:#?}", err)))?;
+
+        let result = self
+            .db
+            .client
+            .update_table()
+            .table_name(TABLE_NAME)
+            .attribute_definitions(Self::created_by_index_attribute("created_by"))
+            .attribute_definitions(Self::created_by_index_attribute("started_at"))
+            .global_secondary_index_updates(
+                GlobalSecondaryIndexUpdate::builder()
+                    .create(create_index)
+                    .build(),
+            )
+            .send()
+            .await;
+
+        match result {
+            Ok(_) => Ok(()),
+            // Another instance may be adding the index at the same time
+            Err(SdkError::ServiceError(service_err))
+                if matches!(
+                    service_err.err(),
+                    UpdateTableError::ResourceInUseException(_)
+                ) =>
+            {
+                Ok(())
+            }
+            Err(err) => Err(DbError::Unknown(format!(
+                "{}, update_table(): {:#?}",
+                TABLE_NAME, err
+            ))),
+        }
+    }
+
+    /// Builds the update expression for the mutable fields of the time track.
+    /// `id`, `project_id`, `created_at` and `created_by` are immutable and never part of it.
+    fn update_expression(time_track: &TimeTrack) -> (String, HashMap<String, AttributeValue>) {
+        let mut item = HashMap::new();
+
+        // Create a list of updates that need to happen to the DynamoDB item
+        let mut updates = vec![
+            "time_tracking_status = :time_tracking_status",
+            "started_at = :started_at",
+            "total_duration = :total_duration",
+        ];
+
+        item.insert(
+            String::from(":time_tracking_status"),
+            AttributeValue::S(time_track.status.to_string()),
+        );
+        item.insert(
+            String::from(":started_at"),
+            AttributeValue::S(time_track.started_at.to_string()),
+        );
+        item.insert(
+            String::from(":total_duration"),
+            AttributeValue::S(format_duration(time_track.total_duration).to_string()),
+        );
+
+        if let Some(stopped_at) = time_track.stopped_at {
+            updates.push("stopped_at = :stopped_at");
+            item.insert(
+                String::from(":stopped_at"),
+                AttributeValue::S(stopped_at.to_string()),
+            );
+        }
+
+        if let Some(last_heartbeat_at) = time_track.last_heartbeat_at {
+            updates.push("last_heartbeat_at = :last_heartbeat_at");
+            item.insert(
+                String::from(":last_heartbeat_at"),
+                AttributeValue::S(last_heartbeat_at.to_string()),
+            );
+        }
+
+        if let Some(comment) = &time_track.comment {
+            updates.push("comment = :comment");
+            item.insert(
+                String::from(":comment"),
+                AttributeValue::S(comment.to_string()),
+            );
+        }
+
+        // The owner is not updated, only checked by the condition expression
+        item.insert(
+            String::from(":created_by"),
+            AttributeValue::S(time_track.created_by.to_string()),
+        );
+
+        let mut update_expression = format!("SET {}", updates.join(", "));
+        // A time track without a comment has no comment attribute, so a removed comment is removed
+        if time_track.comment.is_none() {
+            update_expression.push_str(" REMOVE comment");
+        }
+
+        (update_expression, item)
+    }
+
+    // A page followed by the extra item continues after its last item. A query that stopped short
+    // of the extra item at the 1 MB limit continues where DynamoDB stopped.
+    fn next_cursor(
+        has_more: bool,
+        page: &[HashMap<String, AttributeValue>],
+        last_evaluated_key: Option<&HashMap<String, AttributeValue>>,
+    ) -> Option<String> {
+        if has_more {
+            let last = page.last()?;
+            let key: HashMap<String, AttributeValue> =
+                ["project_id", "id", "created_by", "started_at"]
+                    .into_iter()
+                    .filter_map(|name| {
+                        last.get(name)
+                            .map(|value| (name.to_string(), value.clone()))
+                    })
+                    .collect();
+            return Self::encode_cursor(&key);
+        }
+
+        last_evaluated_key.and_then(Self::encode_cursor)
+    }
+
+    fn encode_cursor(key: &HashMap<String, AttributeValue>) -> Option<String> {
+        let key: HashMap<&String, &String> = key
+            .iter()
+            .filter_map(|(name, value)| value.as_s().ok().map(|value| (name, value)))
+            .collect();
+
+        serde_json::to_string(&key).ok()
+    }
+
+    fn decode_cursor(cursor: &str) -> Result<HashMap<String, AttributeValue>, DbError> {
+        let key: HashMap<String, String> =
+            serde_json::from_str(cursor).map_err(|_| DbError::InvalidCursor(cursor.to_string()))?;
+
+        Ok(key
+            .into_iter()
+            .map(|(name, value)| (name, AttributeValue::S(value)))
+            .collect())
+    }
+
+    fn convert_time_track_to_item(tt: &TimeTrack) -> HashMap<String, AttributeValue> {
+        let mut item = HashMap::new();
+
+        item.insert("id".to_string(), AttributeValue::S(tt.id.to_string()));
+        item.insert(
+            "project_id".to_string(),
+            AttributeValue::S(tt.project_id.to_string()),
+        );
+        item.insert(
+            "time_tracking_status".to_string(),
+            AttributeValue::S(tt.status.to_string()),
+        );
+        item.insert(
+            "started_at".to_string(),
+            AttributeValue::S(tt.started_at.to_string()),
+        );
+        if let Some(stopped_at) = tt.stopped_at {
+            item.insert(
+                "stopped_at".to_string(),
+                AttributeValue::S(stopped_at.to_string()),
+            );
+        }
+        item.insert(
+            "total_duration".to_string(),
+            AttributeValue::S(format_duration(tt.total_duration).to_string()),
+        );
+        if let Some(comment) = &tt.comment {
+            item.insert(
+                "comment".to_string(),
+                AttributeValue::S(comment.to_string()),
+            );
+        }
+        if !tt.notes.is_empty() {
+            item.insert("notes".to_string(), Self::notes_to_attribute(&tt.notes));
+        }
+        if let Some(last_heartbeat_at) = tt.last_heartbeat_at {
+            item.insert(
+                "last_heartbeat_at".to_string(),
+                AttributeValue::S(last_heartbeat_at.to_string()),
+            );
+        }
+        item.insert(
+            "created_at".to_string(),
+            AttributeValue::S(tt.created_at.to_string()),
+        );
+        item.insert(
+            "created_by".to_string(),
+            AttributeValue::S(tt.created_by.to_string()),
+        );
+
+        item
+    }
+
+    // Stored as a list of maps, so notes can be appended with `list_append`
+    fn notes_to_attribute(notes: &[TimeTrackNote]) -> AttributeValue {
+        let notes = notes
+            .iter()
+            .map(|note| {
+                AttributeValue::M(HashMap::from([
+                    (String::from("text"), AttributeValue::S(note.text.clone())),
+                    (
+                        String::from("created_at"),
+                        AttributeValue::S(note.created_at.to_string()),
+                    ),
+                ]))
+            })
+            .collect();
+
+        AttributeValue::L(notes)
+    }
+
+    fn convert_attribute_to_notes(
+        notes: &AttributeValue,
+        id: &str,
+    ) -> Result<Vec<TimeTrackNote>, DbError> {
+        let notes = notes
+            .as_l()
+            .map_err(|_| DbError::Unknown(format!("Invalid notes of item with id {}", id)))?;
+
+        notes
+            .iter()
+            .map(|note| {
+                let note = note.as_m().map_err(|_| {
+                    DbError::Unknown(format!("Invalid note of item with id {}", id))
+                })?;
+
+                Ok(TimeTrackNote {
+                    text: get_string_value(note, "text")?,
+                    created_at: get_datetime_value(note, "created_at")?,
+                })
+            })
+            .collect()
     }
 
-    pub async fn create(&self, time_track: &TimeTrack) -> Result<(), DbError> {
+    fn convert_item_to_time_track(
+        item: &HashMap<String, AttributeValue>,
+    ) -> Result<TimeTrack, DbError> {
+        let id = get_string_value(item, "id")?;
+        let project_id = get_string_value(item, "project_id")?;
+        let status = {
+            let status_str = get_string_value(item, "time_tracking_status")?;
+            status_str.parse::<TimeTrackStatus>().map_err(|_| {
+                DbError::Unknown(format!(
+                    "Invalid status value '{}' for item with id {}",
+                    status_str, id
+                ))
+            })?
+        };
+        let started_at = get_datetime_value(item, "started_at")?;
+        let created_by = get_string_value(item, "created_by")?;
+
+        // Time tracks stored before `created_at` existed were logged when they were started
+        let created_at = match item.get("created_at") {
+            Some(_) => get_datetime_value(item, "created_at")?,
+            None => started_at,
+        };
+
+        let mut comment: Option<String> = None;
+        if item.get("comment").is_some() {
+            comment = Some(get_string_value(item, "comment")?);
+        }
+
+        // Time tracks without appended notes have no notes attribute
+        let notes = match item.get("notes") {
+            Some(notes) => Self::convert_attribute_to_notes(notes, &id)?,
+            None => Vec::new(),
+        };
+
+        let mut stopped_at: Option<DateTime<Utc>> = None;
+        if item.get("stopped_at").is_some() {
+            let datetime = get_datetime_value(item, "stopped_at")?;
+            stopped_at = Some(datetime)
+        }
+
+        let mut last_heartbeat_at: Option<DateTime<Utc>> = None;
+        if item.get("last_heartbeat_at").is_some() {
+            last_heartbeat_at = Some(get_datetime_value(item, "last_heartbeat_at")?);
+        }
+
+        let total_duration = if item.get("total_duration").is_some() {
+            let duration_as_str = get_string_value(item, "total_duration")?;
+            match parse_duration(&duration_as_str) {
+                Ok(duration) => duration,
+                Err(_) => {
+                    return Err(DbError::Unknown(format!(
+                        "Failed to parse str duration '{}' of item {}",
+                        duration_as_str, id
+                    )));
+                }
+            }
+        } else {
+            calculate_duration_to_now(&started_at)
+        };
+
+        let time_track = TimeTrack {
+            id,
+            project_id,
+            status,
+            started_at,
+            stopped_at,
+            total_duration,
+            comment,
+            notes,
+            last_heartbeat_at,
+            created_at,
+            created_by,
+        };
+
+        Ok(time_track)
+    }
+}
+
+#[async_trait]
+impl TimeTrackStore for TimeTrackRepository {
+    async fn create(&self, time_track: &TimeTrack) -> Result<(), DbError> {
         let item = TimeTrackRepository::convert_time_track_to_item(time_track);
 
         self.db
@@ -99,11 +605,7 @@ impl TimeTrackRepository {
             .map_err(|err| DbError::Unknown(format!("{}, create(): {:#?}", TABLE_NAME, err)))
     }
 
-    pub async fn get(
-        &self,
-        project_id: String,
-        time_track_id: String,
-    ) -> Result<TimeTrack, DbError> {
+    async fn get(&self, project_id: String, time_track_id: String) -> Result<TimeTrack, DbError> {
         let result = self
             .db
             .client
@@ -111,6 +613,8 @@ impl TimeTrackRepository {
             .table_name(TABLE_NAME)
             .key("project_id", AttributeValue::S(project_id.clone()))
             .key("id", AttributeValue::S(time_track_id))
+            // Strongly consistent, as the item is read to be modified
+            .consistent_read(true)
             .send()
             .await;
 
@@ -129,11 +633,7 @@ impl TimeTrackRepository {
         }
     }
 
-    pub async fn get_in_progress(
-        &self,
-        user: &User,
-        project_id: &str,
-    ) -> Result<TimeTrack, DbError> {
+    async fn get_in_progress(&self, user: &User, project_id: &str) -> Result<TimeTrack, DbError> {
         let mut expression_attribute_values = HashMap::new();
         expression_attribute_values.insert(
             ":project_id".to_string(),
@@ -158,6 +658,8 @@ impl TimeTrackRepository {
                 "time_tracking_status = :time_tracking_status AND created_by = :created_by",
             )
             .set_expression_attribute_values(Some(expression_attribute_values))
+            // Strongly consistent, as a stop right after a start must see the new time track
+            .consistent_read(true)
             .send()
             .await;
 
@@ -181,7 +683,7 @@ impl TimeTrackRepository {
         }
     }
 
-    pub async fn get_all(&self, project_id: &str, user: &User) -> Result<Vec<TimeTrack>, DbError> {
+    async fn get_all(&self, project_id: &str, user: &User) -> Result<Vec<TimeTrack>, DbError> {
         let mut expression_attribute_values = HashMap::new();
         expression_attribute_values.insert(
             ":project_id".to_string(),
@@ -222,38 +724,61 @@ impl TimeTrackRepository {
         }
     }
 
-    pub async fn update(&self, time_track: &TimeTrack) -> Result<(), DbError> {
-        let mut item = HashMap::new();
+    async fn get_all_for_user(
+        &self,
+        user: &User,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> Result<(Vec<TimeTrack>, Option<String>), DbError> {
+        let mut expression_attribute_values = HashMap::new();
+        expression_attribute_values.insert(
+            ":created_by".to_string(),
+            AttributeValue::S(user.id.to_string()),
+        );
 
-        // Create a list of updates that need to happen to the DynamoDB item
-        let mut updates = vec![
-            "time_tracking_status = :time_tracking_status",
-            "started_at = :started_at",
-            "total_duration = :total_duration",
-        ];
+        self.ensure_created_by_index_active().await?;
 
-        item.insert(
-            String::from(":time_tracking_status"),
-            AttributeValue::S(time_track.status.to_string()),
-        );
-        item.insert(
-            String::from(":started_at"),
-            AttributeValue::S(time_track.started_at.to_string()),
-        );
-        item.insert(
-            String::from(":total_duration"),
-            AttributeValue::S(format_duration(time_track.total_duration).to_string()),
-        );
+        let exclusive_start_key = cursor.map(Self::decode_cursor).transpose()?;
 
-        if let Some(stopped_at) = time_track.stopped_at {
-            updates.push("stopped_at = :stopped_at");
-            item.insert(
-                String::from(":stopped_at"),
-                AttributeValue::S(stopped_at.to_string()),
-            );
+        let result = self
+            .db
+            .client
+            .query()
+            .table_name(TABLE_NAME)
+            .index_name(CREATED_BY_INDEX)
+            .key_condition_expression("created_by = :created_by")
+            .set_expression_attribute_values(Some(expression_attribute_values))
+            .set_exclusive_start_key(exclusive_start_key)
+            // Newest first
+            .scan_index_forward(false)
+            // One more than the page, as LastEvaluatedKey is also set when the page is exactly full
+            .limit(limit as i32 + 1)
+            .send()
+            .await;
+
+        match result {
+            Ok(query) => {
+                let items = query.items();
+                let page = &items[..items.len().min(limit)];
+                let mut time_track_items = Vec::new();
+                for item in page {
+                    let time_track = Self::convert_item_to_time_track(item)?;
+                    time_track_items.push(time_track)
+                }
+                let next_cursor =
+                    Self::next_cursor(items.len() > limit, page, query.last_evaluated_key());
+
+                Ok((time_track_items, next_cursor))
+            }
+            Err(err) => Err(DbError::Unknown(format!(
+                "{}: get_all_for_user(): {:#?}",
+                TABLE_NAME, err
+            ))),
         }
+    }
 
-        let update_expression = format!("SET {}", updates.join(", "));
+    async fn update(&self, time_track: &TimeTrack) -> Result<(), DbError> {
+        let (update_expression, item) = Self::update_expression(time_track);
 
         self.db
             .client
@@ -265,14 +790,90 @@ impl TimeTrackRepository {
             )
             .key("id", AttributeValue::S(time_track.id.to_string()))
             .update_expression(update_expression)
+            // Never create a new item or update a time track owned by another user
+            .condition_expression("attribute_exists(id) AND created_by = :created_by")
             .set_expression_attribute_values(Some(item))
             .send()
             .await
             .map(|_| ())
-            .map_err(|err| DbError::Unknown(format!("{}, update(): {:#?}", TABLE_NAME, err)))
+            .map_err(|err| match err {
+                SdkError::ServiceError(service_err)
+                    if matches!(
+                        service_err.err(),
+                        UpdateItemError::ConditionalCheckFailedException(_)
+                    ) =>
+                {
+                    DbError::NotFound
+                }
+                err => DbError::Unknown(format!("{}, update(): {:#?}", TABLE_NAME, err)),
+            })
     }
 
-    pub async fn delete(
+    async fn append_note(
+        &self,
+        user: &User,
+        project_id: &str,
+        time_track_id: &str,
+        note: &TimeTrackNote,
+    ) -> Result<TimeTrack, DbError> {
+        let result = self
+            .db
+            .client
+            .update_item()
+            .table_name(TABLE_NAME)
+            .key("project_id", AttributeValue::S(project_id.to_string()))
+            .key("id", AttributeValue::S(time_track_id.to_string()))
+            // Appended in place, so concurrent notes are not lost
+            .update_expression("SET notes = list_append(if_not_exists(notes, :no_notes), :notes)")
+            // Never create a new item, append to a time track owned by another user or exceed the
+            // maximum of notes
+            .condition_expression(
+                "attribute_exists(id) AND created_by = :created_by \
+                AND (attribute_not_exists(notes) OR size(notes) < :max_notes)",
+            )
+            .expression_attribute_values(":no_notes", AttributeValue::L(Vec::new()))
+            .expression_attribute_values(":max_notes", AttributeValue::N(MAX_NOTES.to_string()))
+            .expression_attribute_values(
+                ":notes",
+                Self::notes_to_attribute(std::slice::from_ref(note)),
+            )
+            .expression_attribute_values(":created_by", AttributeValue::S(user.id.clone()))
+            .return_values(aws_sdk_dynamodb::types::ReturnValue::AllNew)
+            // Tells a time track with the maximum of notes apart from a missing one
+            .return_values_on_condition_check_failure(ReturnValuesOnConditionCheckFailure::AllOld)
+            .send()
+            .await;
+
+        match result {
+            Ok(item) => match item.attributes {
+                Some(item) => Self::convert_item_to_time_track(&item),
+                None => Err(DbError::NotFound),
+            },
+            Err(SdkError::ServiceError(service_err)) => match service_err.err() {
+                UpdateItemError::ConditionalCheckFailedException(err) => match err.item() {
+                    Some(item)
+                        if item.get("created_by") == Some(&AttributeValue::S(user.id.clone())) =>
+                    {
+                        Err(DbError::LimitReached(format!(
+                            "time track '{}' has {} notes",
+                            time_track_id, MAX_NOTES
+                        )))
+                    }
+                    _ => Err(DbError::NotFound),
+                },
+                err => Err(DbError::Unknown(format!(
+                    "{}: append_note(): {:#?}",
+                    TABLE_NAME, err
+                ))),
+            },
+            Err(err) => Err(DbError::Unknown(format!(
+                "{}: append_note(): {:#?}",
+                TABLE_NAME, err
+            ))),
+        }
+    }
+
+    async fn delete(
         &self,
         user: &User,
         project_id: String,
@@ -308,7 +909,7 @@ impl TimeTrackRepository {
         }
     }
 
-    pub async fn delete_for_project(&self, project_id: &str) -> Result<(), DbError> {
+    async fn delete_for_project(&self, project_id: &str) -> Result<(), DbError> {
         let mut expression_attribute_values = HashMap::new();
         expression_attribute_values.insert(
             String::from(":project_id"),
@@ -364,95 +965,147 @@ impl TimeTrackRepository {
             ))),
         }
     }
+}
 
-    fn convert_time_track_to_item(tt: &TimeTrack) -> HashMap<String, AttributeValue> {
-        let mut item = HashMap::new();
+fn calculate_duration_to_now(started_at: &DateTime<Utc>) -> Duration {
+    duration_between(*started_at, Utc::now())
+}
 
-        item.insert("id".to_string(), AttributeValue::S(tt.id.to_string()));
-        item.insert(
-            "project_id".to_string(),
-            AttributeValue::S(tt.project_id.to_string()),
-        );
-        item.insert(
-            "time_tracking_status".to_string(),
-            AttributeValue::S(tt.status.to_string()),
-        );
-        item.insert(
-            "started_at".to_string(),
-            AttributeValue::S(tt.started_at.to_string()),
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::user_model::UserRole;
+
+    #[test]
+    fn comment_survives_item_round_trip() {
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let mut time_track = TimeTrack::new("proj_12345", &user);
+        time_track.status = TimeTrackStatus::Finished;
+        time_track.stopped_at = Some(time_track.started_at + chrono::Duration::hours(1));
+        time_track.total_duration = Duration::new(3600, 0);
+        time_track.comment = Some(String::from("Fixed the login bug"));
+
+        let item = TimeTrackRepository::convert_time_track_to_item(&time_track);
+        let read_back = TimeTrackRepository::convert_item_to_time_track(&item).unwrap();
+
+        assert_eq!(
+            read_back.comment,
+            Some(String::from("Fixed the login bug")),
+            "Expected comment to be read back, got '{:?}'",
+            read_back.comment
         );
-        if let Some(stopped_at) = tt.stopped_at {
-            item.insert(
-                "stopped_at".to_string(),
-                AttributeValue::S(stopped_at.to_string()),
-            );
-        }
-        item.insert(
-            "total_duration".to_string(),
-            AttributeValue::S(format_duration(tt.total_duration).to_string()),
+    }
+
+    #[test]
+    fn missing_comment_is_read_as_none() {
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let time_track = TimeTrack::new("proj_12345", &user);
+
+        let item = TimeTrackRepository::convert_time_track_to_item(&time_track);
+        let read_back = TimeTrackRepository::convert_item_to_time_track(&item).unwrap();
+
+        assert!(
+            !item.contains_key("comment"),
+            "Expected no comment attribute on the item"
         );
-        item.insert(
-            "created_by".to_string(),
-            AttributeValue::S(tt.created_by.to_string()),
+        assert!(
+            read_back.comment.is_none(),
+            "Expected comment 'None', got '{:?}'",
+            read_back.comment
         );
+    }
 
-        item
+    #[test]
+    fn notes_survive_item_round_trip() {
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let mut time_track = TimeTrack::new("proj_12345", &user);
+        time_track.notes = vec![
+            TimeTrackNote::new("Started on the login bug"),
+            TimeTrackNote::new("Found the cause"),
+        ];
+
+        let item = TimeTrackRepository::convert_time_track_to_item(&time_track);
+        let read_back = TimeTrackRepository::convert_item_to_time_track(&item).unwrap();
+
+        assert_eq!(
+            read_back.notes, time_track.notes,
+            "Expected the notes to be read back in order"
+        );
     }
 
-    fn convert_item_to_time_track(
-        item: &HashMap<String, AttributeValue>,
-    ) -> Result<TimeTrack, DbError> {
-        let id = get_string_value(item, "id")?;
-        let project_id = get_string_value(item, "project_id")?;
-        let status = {
-            let status_str = get_string_value(item, "time_tracking_status")?;
-            status_str.parse::<TimeTrackStatus>().map_err(|_| {
-                DbError::Unknown(format!(
-                    "Invalid status value '{}' for item with id {}",
-                    status_str, id
+    #[test]
+    fn next_cursor_is_only_set_when_more_items_follow() {
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let page: Vec<HashMap<String, AttributeValue>> = (0..2)
+            .map(|_| {
+                TimeTrackRepository::convert_time_track_to_item(&TimeTrack::new(
+                    "project_id",
+                    &user,
                 ))
-            })?
-        };
-        let started_at = get_datetime_value(item, "started_at")?;
-        let created_by = get_string_value(item, "created_by")?;
+            })
+            .collect();
 
-        let mut stopped_at: Option<DateTime<Utc>> = None;
-        if item.get("stopped_at").is_some() {
-            let datetime = get_datetime_value(item, "stopped_at")?;
-            stopped_at = Some(datetime)
+        let cursor = TimeTrackRepository::next_cursor(true, &page, None).unwrap();
+        let key = TimeTrackRepository::decode_cursor(&cursor).unwrap();
+        let last = &page[1];
+        for name in ["project_id", "id", "created_by", "started_at"] {
+            assert_eq!(
+                key.get(name),
+                last.get(name),
+                "Expected {} in the cursor",
+                name
+            );
         }
+        assert_eq!(key.len(), 4, "Expected only the keys in the cursor");
 
-        let mut total_duration = Duration::new(0, 0);
-        if item.get("total_duration").is_some() {
-            let duration_as_str = get_string_value(item, "total_duration")?;
-            total_duration = match parse_duration(&duration_as_str) {
-                Ok(duration) => duration,
-                Err(_) => {
-                    return Err(DbError::Unknown(format!(
-                        "Failed to parse str duration '{}' of item {}",
-                        duration_as_str, id
-                    )));
-                }
-            }
-        } else {
-            total_duration = calculate_duration_to_now(&started_at);
-        }
+        assert_eq!(
+            TimeTrackRepository::next_cursor(false, &page, None),
+            None,
+            "Expected no cursor when the page is exactly the rest"
+        );
+    }
 
-        let time_track = TimeTrack {
-            id,
-            project_id,
-            status,
-            started_at,
-            stopped_at,
-            total_duration,
-            created_by,
-        };
+    #[test]
+    fn update_expression_sets_or_removes_the_comment() {
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let mut time_track = TimeTrack::new("project_id", &user);
+        time_track.comment = Some(String::from("Fixed the login bug"));
 
-        Ok(time_track)
+        let (update_expression, values) = TimeTrackRepository::update_expression(&time_track);
+        assert!(update_expression.contains("comment = :comment"));
+        assert_eq!(
+            values.get(":comment"),
+            Some(&AttributeValue::S(String::from("Fixed the login bug")))
+        );
+
+        time_track.comment = None;
+        let (update_expression, values) = TimeTrackRepository::update_expression(&time_track);
+        assert!(
+            update_expression.ends_with(" REMOVE comment"),
+            "Expected the comment to be removed, got: {}",
+            update_expression
+        );
+        assert!(!values.contains_key(":comment"));
     }
-}
 
-fn calculate_duration_to_now(started_at: &DateTime<Utc>) -> Duration {
-    let time_delta = Utc::now() - started_at;
-    Duration::new(time_delta.num_seconds() as u64, 0)
+    #[test]
+    fn update_expression_never_sets_immutable_fields() {
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let mut time_track = TimeTrack::new("project_id", &user);
+        time_track.stopped_at = Some(Utc::now());
+        time_track.last_heartbeat_at = Some(Utc::now());
+
+        let (update_expression, _) = TimeTrackRepository::update_expression(&time_track);
+
+        for field in ["id", "project_id", "created_at", "created_by"] {
+            assert!(
+                !update_expression
+                    .split(", ")
+                    .any(|update| update.trim_start_matches("SET ").starts_with(field)),
+                "Expected {} to not be updated, got: {}",
+                field,
+                update_expression
+            );
+        }
+    }
 }
@@ -1,10 +1,24 @@
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    env,
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
-use crate::models::user_model::{User, UserRole};
+use crate::models::user_model::{ApiKeyScope, User, UserRole};
+use async_trait::async_trait;
 use aws_sdk_dynamodb::{
     error::SdkError,
-    operation::create_table::CreateTableError,
-    types::{AttributeDefinition, AttributeValue, KeySchemaElement, KeyType, ScalarAttributeType},
+    operation::{create_table::CreateTableError, update_table::UpdateTableError},
+    types::{
+        AttributeDefinition, AttributeValue, CreateGlobalSecondaryIndexAction,
+        GlobalSecondaryIndex, GlobalSecondaryIndexUpdate, IndexStatus, KeySchemaElement, KeyType,
+        Projection, ProjectionType, ScalarAttributeType,
+    },
 };
 use tokio::time::sleep;
 
@@ -13,12 +27,28 @@ use super::{
     utils::{get_datetime_value, get_string_value},
 };
 
+#[async_trait]
+pub trait UserStore: Send + Sync + Debug {
+    async fn create(&self, user: &User) -> Result<(), DbError>;
+    async fn get_by_api_key(&self, api_key: &str) -> Result<User, DbError>;
+    async fn get_by_id(&self, id: &str) -> Result<User, DbError>;
+    /// Returns all users with the name, which is empty if there are none, as names are not unique
+    async fn get_by_name(&self, name: &str) -> Result<Vec<User>, DbError>;
+    async fn get_all(&self) -> Result<Vec<User>, DbError>;
+    async fn delete(&self, user: &User) -> Result<(), DbError>;
+}
+
 #[derive(Debug)]
 pub struct UserRepository {
     db: Arc<Database>,
+    // Until the user_name index is active, users are looked up by name with a scan
+    user_name_index_active: AtomicBool,
 }
 
-static TABLE_NAME: &str = "users";
+pub static TABLE_NAME: &str = "users";
+/// The API key of the admin user created with the table
+pub static DEFAULT_ADMIN_API_KEY: &str = "admin";
+static USER_NAME_INDEX: &str = "user_name_index";
 
 impl UserRepository {
     pub async fn build(db: Arc<Database>) -> Result<UserRepository, DbError> {
@@ -78,6 +108,8 @@ impl UserRepository {
             .key_schema(keyschema_part)
             .attribute_definitions(attr_sort)
             .key_schema(keyschema_sort)
+            .attribute_definitions(Self::user_name_index_attribute()?)
+            .global_secondary_indexes(Self::user_name_index()?)
             .send()
             .await;
 
@@ -88,7 +120,7 @@ impl UserRepository {
                     // If the error is not, that the table already exists => throw error
                     if info
                         .message()
-                        .map_or(true, |msg| !msg.contains("Table already exists"))
+                        .is_none_or(|msg| !msg.contains("Table already exists"))
                     {
                         return Err(DbError::Unknown(format!(
                             "CreateTableError::ResourceInUseException: {:#?}",
@@ -100,46 +132,322 @@ impl UserRepository {
             }
         }
 
-        let user_repository = Self { db };
+        // A created table has the index from the start, only existing tables may have to get it
+        let user_repository = Self {
+            db,
+            user_name_index_active: AtomicBool::new(result.is_ok()),
+        };
+        if result.is_err() {
+            user_repository.ensure_user_name_index().await?;
+        }
 
         // If the table was just created, add a default admin user
-        let admin_name = "admin";
-
         if result.is_ok() {
-            let mut default_admin_user = User::new(admin_name, &UserRole::Admin, "SYSTEM");
-            default_admin_user.api_key = Some(String::from(admin_name));
-            let one_sec = Duration::new(1, 0);
-            let max_retries = 30;
-            let mut attempt = 0;
-
-            // Loop because it can take some time for the DynamoDB table to get created
-            loop {
-                if attempt >= max_retries {
-                    eprintln!(
-                        "Failed to create ADMIN user after {} attempts. Exiting.",
-                        max_retries
-                    );
-                    std::process::exit(1);
-                }
+            // It can take some time for the DynamoDB table to get created
+            let max_attempts = env::var("ADMIN_CREATE_ATTEMPTS")
+                .ok()
+                .and_then(|val| val.parse().ok())
+                .unwrap_or(30);
+            let retry_interval = env::var("ADMIN_CREATE_RETRY_INTERVAL_SECS")
+                .ok()
+                .and_then(|val| val.parse().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(Duration::from_secs(1));
+
+            create_default_admin(&user_repository, max_attempts, retry_interval).await?;
+            println!("Created ADMIN user. Make sure to create your own ADMIN user and delete this one.");
+        }
+
+        Ok(user_repository)
+    }
+
+    fn user_name_index_attribute() -> Result<AttributeDefinition, DbError> {
+        AttributeDefinition::builder()
+            .attribute_name("user_name")
+            .attribute_type(ScalarAttributeType::S)
+            .build()
+            .map_err(|e| {
+                DbError::Unknown(format!(
+                    "Error building the attribute 'user_name' for the {} index: {:?}",
+                    USER_NAME_INDEX, e
+                ))
+            })
+    }
+
+    fn user_name_index_key_schema() -> Result<KeySchemaElement, DbError> {
+        KeySchemaElement::builder()
+            .attribute_name("user_name")
+            .key_type(KeyType::Hash)
+            .build()
+            .map_err(|e| {
+                DbError::Unknown(format!(
+                    "Error building the key schema 'user_name' for the {} index: {:?}",
+                    USER_NAME_INDEX, e
+                ))
+            })
+    }
+
+    // Index on user_name, so admins can look up users by name without scanning the table
+    fn user_name_index() -> Result<GlobalSecondaryIndex, DbError> {
+        GlobalSecondaryIndex::builder()
+            .index_name(USER_NAME_INDEX)
+            .key_schema(Self::user_name_index_key_schema()?)
+            .projection(
+                Projection::builder()
+                    .projection_type(ProjectionType::All)
+                    .build(),
+            )
+            .build()
+            .map_err(|e| {
+                DbError::Unknown(format!(
+                    "Error building the {} index for table {}: {:?}",
+                    USER_NAME_INDEX, TABLE_NAME, e
+                ))
+            })
+    }
+
+    async fn user_name_index_status(&self) -> Result<Option<IndexStatus>, DbError> {
+        let table = self
+            .db
+            .client
+            .describe_table()
+            .table_name(TABLE_NAME)
+            .send()
+            .await
+            .map_err(|err| {
+                DbError::Unknown(format!("{}, describe_table(): {:#?}", TABLE_NAME, err))
+            })?;
+
+        let status = table.table().and_then(|table| {
+            table
+                .global_secondary_indexes()
+                .iter()
+                .find(|index| index.index_name() == Some(USER_NAME_INDEX))
+                .map(|index| {
+                    index
+                        .index_status()
+                        .cloned()
+                        .unwrap_or(IndexStatus::Creating)
+                })
+        });
+
+        Ok(status)
+    }
+
+    // Whether users can be looked up by name with the index. Checks the table while the index is
+    // still being created, which can take a while on a table with many users.
+    async fn is_user_name_index_active(&self) -> Result<bool, DbError> {
+        if self.user_name_index_active.load(Ordering::Relaxed) {
+            return Ok(true);
+        }
+
+        let active = self.user_name_index_status().await? == Some(IndexStatus::Active);
+        self.user_name_index_active.store(active, Ordering::Relaxed);
+
+        Ok(active)
+    }
+
+    // Tables created before the index existed do not get it from create_table, so it is added
+    async fn ensure_user_name_index(&self) -> Result<(), DbError> {
+        match self.user_name_index_status().await? {
+            Some(IndexStatus::Active) => {
+                self.user_name_index_active.store(true, Ordering::Relaxed);
+                return Ok(());
+            }
+            Some(status) => {
                 println!(
-                    "Trying to create ADMIN user (attempt {})...",
-                    attempt + 1
+                    "The {} index is {}, users are looked up by name with a scan until it is active",
+                    USER_NAME_INDEX,
+                    status.as_str()
                 );
-                let result = user_repository.create(&default_admin_user).await;
-                if result.is_ok() {
-                    break;
-                } else {
-                    sleep(one_sec).await;
-                    attempt += 1;
-                }
+                return Ok(());
             }
-            println!("Created ADMIN user. Make sure to create your own ADMIN user and delete this one.");
+            None => println!(
+                "Adding the {} index, users are looked up by name with a scan until it is active",
+                USER_NAME_INDEX
+            ),
         }
 
-        Ok(user_repository)
+        let create_index = CreateGlobalSecondaryIndexAction::builder()
+            .index_name(USER_NAME_INDEX)
+            .key_schema(Self::user_name_index_key_schema()?)
+            .projection(
+                Projection::builder()
+                    .projection_type(ProjectionType::All)
+                    .build(),
+            )
+            .build()
+            .map_err(|err| DbError::Unknown(format!("{:#?}", err)))?;
+
+        let result = self
+            .db
+            .client
+            .update_table()
+            .table_name(TABLE_NAME)
+            .attribute_definitions(Self::user_name_index_attribute()?)
+            .global_secondary_index_updates(
+                GlobalSecondaryIndexUpdate::builder()
+                    .create(create_index)
+                    .build(),
+            )
+            .send()
+            .await;
+
+        match result {
+            Ok(_) => Ok(()),
+            // Another instance may be adding the index at the same time
+            Err(SdkError::ServiceError(service_err))
+                if matches!(
+                    service_err.err(),
+                    UpdateTableError::ResourceInUseException(_)
+                ) =>
+            {
+                Ok(())
+            }
+            Err(err) => Err(DbError::Unknown(format!(
+                "{}, update_table(): {:#?}",
+                TABLE_NAME, err
+            ))),
+        }
+    }
+
+    // Looks up users by name without the index, while it is being created
+    async fn scan_by_name(&self, name: &str) -> Result<Vec<User>, DbError> {
+        let mut users = Vec::new();
+        let mut exclusive_start_key = None;
+
+        loop {
+            let output = self
+                .db
+                .client
+                .scan()
+                .table_name(TABLE_NAME)
+                .filter_expression("user_name = :user_name")
+                .expression_attribute_values(":user_name", AttributeValue::S(name.to_string()))
+                .set_exclusive_start_key(exclusive_start_key)
+                .send()
+                .await
+                .map_err(|err| {
+                    DbError::Unknown(format!("{}: scan_by_name {:#?}", TABLE_NAME, err))
+                })?;
+
+            for item in output.items() {
+                users.push(Self::convert_item_to_user(item)?);
+            }
+
+            match output.last_evaluated_key {
+                Some(last_evaluated_key) => exclusive_start_key = Some(last_evaluated_key),
+                None => break,
+            }
+        }
+
+        Ok(users)
+    }
+
+    fn convert_user_to_item(user: &User) -> HashMap<String, AttributeValue> {
+        let mut item = HashMap::new();
+
+        item.insert("id".to_string(), AttributeValue::S(user.id.clone()));
+        item.insert(
+            "user_name".to_string(),
+            AttributeValue::S(user.name.clone()),
+        );
+        item.insert(
+            "user_role".to_string(),
+            AttributeValue::S(user.role.to_string()),
+        );
+        item.insert(
+            "api_key_scope".to_string(),
+            AttributeValue::S(user.scope.to_string()),
+        );
+        item.insert(
+            "api_key".to_string(),
+            AttributeValue::S(user.api_key.clone().unwrap()),
+        );
+        item.insert(
+            "created_at".to_string(),
+            AttributeValue::S(user.created_at.to_rfc3339()),
+        );
+        item.insert(
+            "created_by".to_string(),
+            AttributeValue::S(user.created_by.clone()),
+        );
+
+        item
+    }
+
+    fn convert_item_to_user(item: &HashMap<String, AttributeValue>) -> Result<User, DbError> {
+        let id = get_string_value(item, "id")?;
+        let name = get_string_value(item, "user_name")?;
+        let role = {
+            let role_str = get_string_value(item, "user_role")?;
+            role_str.parse::<UserRole>().map_err(|_| {
+                DbError::Unknown(format!("Invalid role value '{}' in item: {}", role_str, id))
+            })?
+        };
+        // Users created before scopes existed have no scope and keep full access.
+        let scope = match item.get("api_key_scope") {
+            Some(AttributeValue::S(scope_str)) => {
+                scope_str.parse::<ApiKeyScope>().map_err(|_| {
+                    DbError::Unknown(format!(
+                        "Invalid scope value '{}' in item: {}",
+                        scope_str, id
+                    ))
+                })?
+            }
+            _ => ApiKeyScope::ReadWrite,
+        };
+        let api_key = {
+            let api_key = get_string_value(item, "api_key")?;
+            Some(api_key)
+        };
+        let created_at = get_datetime_value(item, "created_at")?;
+        let created_by = get_string_value(item, "created_by")?;
+
+        Ok(User {
+            id,
+            name,
+            role,
+            scope,
+            api_key,
+            created_at,
+            created_by,
+        })
     }
+}
 
-    pub async fn create(&self, user: &User) -> Result<(), DbError> {
+/// Creates the default admin user, retrying while the store is not ready.
+/// Returns the last error once all attempts failed, so the caller decides whether to stop.
+pub async fn create_default_admin(
+    store: &dyn UserStore,
+    max_attempts: u32,
+    retry_interval: Duration,
+) -> Result<(), DbError> {
+    let admin_name = "admin";
+    let mut default_admin_user = User::new(admin_name, &UserRole::Admin, "SYSTEM");
+    default_admin_user.api_key = Some(String::from(DEFAULT_ADMIN_API_KEY));
+
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        println!("Trying to create ADMIN user (attempt {})...", attempt);
+
+        match store.create(&default_admin_user).await {
+            Ok(_) => return Ok(()),
+            Err(err) if attempt >= max_attempts => {
+                return Err(DbError::Unknown(format!(
+                    "Failed to create ADMIN user after {} attempts: {}",
+                    attempt, err
+                )));
+            }
+            Err(_) => sleep(retry_interval).await,
+        }
+    }
+}
+
+#[async_trait]
+impl UserStore for UserRepository {
+    async fn create(&self, user: &User) -> Result<(), DbError> {
         let item = Self::convert_user_to_item(user);
 
         let result = self
@@ -158,7 +466,7 @@ impl UserRepository {
         Ok(())
     }
 
-    pub async fn get_by_api_key(&self, api_key: &str) -> Result<User, DbError> {
+    async fn get_by_api_key(&self, api_key: &str) -> Result<User, DbError> {
         let result = self
             .db
             .client
@@ -189,7 +497,7 @@ impl UserRepository {
         }
     }
 
-    pub async fn get_by_id(&self, id: &str) -> Result<User, DbError> {
+    async fn get_by_id(&self, id: &str) -> Result<User, DbError> {
         let result = self
             .db
             .client
@@ -214,7 +522,45 @@ impl UserRepository {
         }
     }
 
-    pub async fn get_all(&self) -> Result<Vec<User>, DbError> {
+    async fn get_by_name(&self, name: &str) -> Result<Vec<User>, DbError> {
+        if !self.is_user_name_index_active().await? {
+            return self.scan_by_name(name).await;
+        }
+
+        let mut users = Vec::new();
+        let mut exclusive_start_key = None;
+
+        // Follow the pages, so every user with the name is returned
+        loop {
+            let output = self
+                .db
+                .client
+                .query()
+                .table_name(TABLE_NAME)
+                .index_name(USER_NAME_INDEX)
+                .key_condition_expression("user_name = :user_name")
+                .expression_attribute_values(":user_name", AttributeValue::S(name.to_string()))
+                .set_exclusive_start_key(exclusive_start_key)
+                .send()
+                .await
+                .map_err(|err| {
+                    DbError::Unknown(format!("{}: get_by_name {:#?}", TABLE_NAME, err))
+                })?;
+
+            for item in output.items() {
+                users.push(Self::convert_item_to_user(item)?);
+            }
+
+            match output.last_evaluated_key {
+                Some(last_evaluated_key) => exclusive_start_key = Some(last_evaluated_key),
+                None => break,
+            }
+        }
+
+        Ok(users)
+    }
+
+    async fn get_all(&self) -> Result<Vec<User>, DbError> {
         let result = self
             .db
             .client
@@ -245,7 +591,7 @@ impl UserRepository {
         }
     }
 
-    pub async fn delete(&self, user: &User) -> Result<(), DbError> {
+    async fn delete(&self, user: &User) -> Result<(), DbError> {
         let api_key = match &user.api_key {
             Some(key) => key.clone(),
             None => return Err(DbError::Unknown("User api_key is missing".to_string())),
@@ -270,58 +616,88 @@ impl UserRepository {
             Err(err) => Err(DbError::Unknown(format!("{}: {:#?}", TABLE_NAME, err))),
         }
     }
+}
 
-    fn convert_user_to_item(user: &User) -> HashMap<String, AttributeValue> {
-        let mut item = HashMap::new();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
 
-        item.insert("id".to_string(), AttributeValue::S(user.id.clone()));
-        item.insert(
-            "user_name".to_string(),
-            AttributeValue::S(user.name.clone()),
-        );
-        item.insert(
-            "user_role".to_string(),
-            AttributeValue::S(user.role.to_string()),
-        );
-        item.insert(
-            "api_key".to_string(),
-            AttributeValue::S(user.api_key.clone().unwrap()),
-        );
-        item.insert(
-            "created_at".to_string(),
-            AttributeValue::S(user.created_at.to_rfc3339()),
-        );
-        item.insert(
-            "created_by".to_string(),
-            AttributeValue::S(user.created_by.clone()),
-        );
+    /// Fails to create users until `failures` attempts were made
+    #[derive(Debug)]
+    struct UnreadyUserStore {
+        failures: u32,
+        attempts: Mutex<u32>,
+    }
 
-        item
+    impl UnreadyUserStore {
+        fn new(failures: u32) -> Self {
+            UnreadyUserStore {
+                failures,
+                attempts: Mutex::new(0),
+            }
+        }
+
+        fn attempts(&self) -> u32 {
+            *self.attempts.lock().unwrap()
+        }
     }
 
-    fn convert_item_to_user(item: &HashMap<String, AttributeValue>) -> Result<User, DbError> {
-        let id = get_string_value(item, "id")?;
-        let name = get_string_value(item, "user_name")?;
-        let role = {
-            let role_str = get_string_value(item, "user_role")?;
-            role_str.parse::<UserRole>().map_err(|_| {
-                DbError::Unknown(format!("Invalid role value '{}' in item: {}", role_str, id))
-            })?
-        };
-        let api_key = {
-            let api_key = get_string_value(item, "api_key")?;
-            Some(api_key)
-        };
-        let created_at = get_datetime_value(item, "created_at")?;
-        let created_by = get_string_value(item, "created_by")?;
+    #[async_trait]
+    impl UserStore for UnreadyUserStore {
+        async fn create(&self, _user: &User) -> Result<(), DbError> {
+            let mut attempts = self.attempts.lock().unwrap();
+            *attempts += 1;
+            if *attempts <= self.failures {
+                return Err(DbError::Unknown(String::from("table is being created")));
+            }
+            Ok(())
+        }
 
-        Ok(User {
-            id,
-            name,
-            role,
-            api_key,
-            created_at,
-            created_by,
-        })
+        async fn get_by_api_key(&self, _api_key: &str) -> Result<User, DbError> {
+            Err(DbError::NotFound)
+        }
+
+        async fn get_by_id(&self, _id: &str) -> Result<User, DbError> {
+            Err(DbError::NotFound)
+        }
+
+        async fn get_by_name(&self, _name: &str) -> Result<Vec<User>, DbError> {
+            Ok(Vec::new())
+        }
+
+        async fn get_all(&self) -> Result<Vec<User>, DbError> {
+            Ok(Vec::new())
+        }
+
+        async fn delete(&self, _user: &User) -> Result<(), DbError> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn create_default_admin_returns_error_after_all_attempts() {
+        let store = UnreadyUserStore::new(u32::MAX);
+
+        let result = create_default_admin(&store, 3, Duration::ZERO).await;
+
+        match result {
+            Err(DbError::Unknown(msg)) => assert_eq!(
+                msg,
+                "Failed to create ADMIN user after 3 attempts: Unknown error: table is being created"
+            ),
+            other => panic!("Expected an error, got: {:?}", other),
+        }
+        assert_eq!(store.attempts(), 3);
+    }
+
+    #[tokio::test]
+    async fn create_default_admin_retries_until_the_store_is_ready() {
+        let store = UnreadyUserStore::new(2);
+
+        let result = create_default_admin(&store, 3, Duration::ZERO).await;
+
+        assert!(result.is_ok(), "Expected the third attempt to succeed");
+        assert_eq!(store.attempts(), 3);
     }
 }
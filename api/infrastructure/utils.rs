@@ -23,3 +23,10 @@ pub fn get_datetime_value(
         .map_err(|e| DbError::Unknown(format!("Invalid {} format: {}", key, e)))
         .map(|dt| dt.with_timezone(&Utc))
 }
+
+/// A missing string set is empty, as DynamoDB does not store empty sets
+pub fn get_string_set_value(item: &HashMap<String, AttributeValue>, key: &str) -> Vec<String> {
+    item.get(key)
+        .and_then(|av| av.as_ss().ok().cloned())
+        .unwrap_or_default()
+}
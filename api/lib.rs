@@ -1,3 +1,4 @@
+pub mod fairings;
 pub mod handlers;
 pub mod infrastructure;
 pub mod models;
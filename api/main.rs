@@ -6,7 +6,15 @@ use infrastructure::{
 };
 use lambda_web::{is_running_on_lambda, launch_rocket_on_lambda, LambdaError};
 use std::sync::Arc;
-use sitt_api::{handlers, infrastructure, services};
+use sitt_api::{
+    fairings::{
+        compression::ResponseCompression,
+        cors::Cors,
+        shutdown::{shutdown_config_from_env, ShutdownLogger},
+        version_warning::VersionWarning,
+    },
+    handlers, infrastructure, services,
+};
 
 #[rocket::main]
 async fn main() -> Result<(), LambdaError> {
@@ -14,7 +22,11 @@ async fn main() -> Result<(), LambdaError> {
 
     // Infrastructure
     let database = Arc::new(infrastructure::database::Database::new().await);
-    let user_repository = Arc::new(UserRepository::build(database.clone()).await?);
+    // Stop, as the API can not be administrated without the default admin on a new table
+    let user_repository = UserRepository::build(database.clone())
+        .await
+        .inspect_err(|err| eprintln!("Failed to set up the users table: {}", err))?;
+    let user_repository = Arc::new(user_repository);
     let project_repository = Arc::new(ProjectRepository::build(database.clone()).await?);
     let time_track_repository = Arc::new(TimeTrackRepository::build(database.clone()).await?);
 
@@ -35,22 +47,40 @@ async fn main() -> Result<(), LambdaError> {
     project_service
         .set_time_track_service(time_track_service.clone())
         .await;
+    let health_service = Arc::new(services::health_service::HealthService::new(
+        database.clone(),
+        user_repository.clone(),
+    ));
 
-    // Setup Rocket
-    let rocket = rocket::build()
+    // Setup Rocket, letting in-flight requests finish when shutting down during a deploy
+    let figment = rocket::Config::figment().merge(("shutdown", shutdown_config_from_env()));
+    let mut rocket = rocket::custom(figment)
+        .attach(ShutdownLogger)
+        .attach(VersionWarning::default())
         .manage(user_service)
         .manage(project_service)
         .manage(time_track_service)
+        .manage(health_service)
+        .mount("/api/v1", handlers::health_handler::routes())
         .mount("/api/v1", handlers::user_handler::routes())
         .mount("/api/v1", handlers::project_handler::routes())
         .mount("/api/v1", handlers::time_track_handler::routes());
 
+    if let Some(compression) = ResponseCompression::from_env() {
+        rocket = rocket.attach(compression);
+    }
+
+    if let Some(cors) = Cors::from_env() {
+        rocket = rocket.attach(cors);
+    }
+
     if is_running_on_lambda() {
         // Launch on AWS Lambda
         launch_rocket_on_lambda(rocket).await?;
     } else {
         // Launch local server
         let _ = rocket.launch().await?;
+        println!("Shutdown complete");
     }
 
     Ok(())
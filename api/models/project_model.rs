@@ -38,12 +38,14 @@ impl FromStr for ProjectStatus {
     }
 }
 
+// `id`, `created_at` and `created_by` are immutable, updates never change them
 #[derive(Debug, Clone, Serialize)]
 pub struct Project {
     pub id: String,
     pub name: String,
     pub status: ProjectStatus,
     pub total_duration: Duration,
+    pub tags: Vec<String>,
     pub created_at: DateTime<Utc>,
     pub created_by: String,
     pub modified_at: Option<DateTime<Utc>>,
@@ -57,6 +59,7 @@ impl Project {
             name: project_name,
             status: ProjectStatus::Inactive,
             total_duration: Duration::new(0, 0),
+            tags: Vec::new(),
             created_at: Utc::now(),
             created_by: created_by.to_string(),
             modified_at: None,
@@ -44,7 +44,35 @@ impl FromStr for TimeTrackStatus {
     }
 }
 
-#[derive(Debug, Serialize)]
+/// The time between the timestamps to the millisecond, so short time tracks keep their fractions
+/// of a second when they are summed into a total. A stop before the start counts as no time.
+pub fn duration_between(started_at: DateTime<Utc>, stopped_at: DateTime<Utc>) -> Duration {
+    let time_delta = stopped_at - started_at;
+    Duration::from_millis(time_delta.num_milliseconds().max(0) as u64)
+}
+
+/// The most notes a time track can have, as the time track is stored in a single item
+pub const MAX_NOTES: usize = 50;
+
+/// A note appended to a time track while it is worked on, e.g. when starting, midway and when closing it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TimeTrackNote {
+    pub text: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl TimeTrackNote {
+    pub fn new<S: Into<String>>(text: S) -> Self {
+        TimeTrackNote {
+            text: text.into(),
+            created_at: Utc::now(),
+        }
+    }
+}
+
+// `id`, `project_id`, `created_at` and `created_by` are immutable, updates never change them.
+// Notes are only ever appended, so updates never change them either.
+#[derive(Debug, Clone, Serialize)]
 pub struct TimeTrack {
     pub id: String, // Changed from String to Uuid for more efficient handling.
     pub project_id: String,
@@ -52,6 +80,12 @@ pub struct TimeTrack {
     pub started_at: DateTime<Utc>,
     pub stopped_at: Option<DateTime<Utc>>,
     pub total_duration: Duration,
+    // The summary note of the time track, the appended notes are kept apart from it
+    pub comment: Option<String>,
+    pub notes: Vec<TimeTrackNote>,
+    pub last_heartbeat_at: Option<DateTime<Utc>>,
+    // When the time track was logged, which differs from `started_at` for time added afterwards
+    pub created_at: DateTime<Utc>,
     pub created_by: String,
 }
 
@@ -64,9 +98,33 @@ impl TimeTrack {
             started_at: Utc::now(),
             stopped_at: None,
             total_duration: Duration::new(0, 0),
+            comment: None,
+            notes: Vec::new(),
+            last_heartbeat_at: None,
+            created_at: Utc::now(),
             created_by: user.id.clone(),
         }
     }
+
+    /// The time the time track counts towards its project's total. A running time track counts the time
+    /// since it started, while any other status counts only its stored duration, as no time is added to it.
+    pub fn current_duration(&self, now: DateTime<Utc>) -> Duration {
+        match self.status {
+            TimeTrackStatus::InProgress => duration_between(self.started_at, now),
+            TimeTrackStatus::Finished => self.total_duration,
+        }
+    }
+
+    /// An IN_PROGRESS time track is abandoned when its client stopped sending heartbeats.
+    /// Time tracks that never received a heartbeat are not considered stale.
+    pub fn is_heartbeat_stale(&self, now: DateTime<Utc>, max_silence: chrono::Duration) -> bool {
+        match self.last_heartbeat_at {
+            Some(last_heartbeat_at) => {
+                self.status == TimeTrackStatus::InProgress && now - last_heartbeat_at > max_silence
+            }
+            None => false,
+        }
+    }
 }
 
 
@@ -150,4 +208,70 @@ mod tests {
             time_track.total_duration
         );
     }
+
+    #[test]
+    fn test_time_track_is_heartbeat_stale() {
+        let user = User::new("test", &UserRole::User, &Uuid::new_v4().to_string());
+        let mut time_track = TimeTrack::new("proj_12345", &user);
+        let now = Utc::now();
+        let max_silence = chrono::Duration::minutes(5);
+
+        assert!(
+            !time_track.is_heartbeat_stale(now, max_silence),
+            "Expected a time track without heartbeats to not be stale"
+        );
+
+        time_track.last_heartbeat_at = Some(now - chrono::Duration::minutes(10));
+        assert!(
+            time_track.is_heartbeat_stale(now, max_silence),
+            "Expected a silent IN_PROGRESS time track to be stale"
+        );
+
+        time_track.status = TimeTrackStatus::Finished;
+        assert!(
+            !time_track.is_heartbeat_stale(now, max_silence),
+            "Expected a FINISHED time track to not be stale"
+        );
+    }
+
+    #[test]
+    fn test_time_track_current_duration() {
+        let user = User::new("test", &UserRole::User, &Uuid::new_v4().to_string());
+        let mut time_track = TimeTrack::new("proj_12345", &user);
+        let now = Utc::now();
+        time_track.started_at = now - chrono::Duration::hours(2);
+        time_track.total_duration = Duration::from_secs(600);
+
+        assert_eq!(
+            time_track.current_duration(now),
+            Duration::from_secs(2 * 3600),
+            "Expected an IN_PROGRESS time track to count the time since it started"
+        );
+
+        time_track.status = TimeTrackStatus::Finished;
+        assert_eq!(
+            time_track.current_duration(now),
+            Duration::from_secs(600),
+            "Expected a FINISHED time track to count only its stored duration"
+        );
+    }
+
+    #[test]
+    fn test_duration_between_keeps_milliseconds() {
+        let started_at = Utc::now();
+
+        assert_eq!(
+            duration_between(
+                started_at,
+                started_at + chrono::Duration::milliseconds(1500)
+            ),
+            Duration::from_millis(1500),
+            "Expected the fraction of a second to be kept"
+        );
+        assert_eq!(
+            duration_between(started_at, started_at - chrono::Duration::seconds(1)),
+            Duration::ZERO,
+            "Expected a stop before the start to count as no time"
+        );
+    }
 }
@@ -37,11 +37,43 @@ impl FromStr for UserRole {
     }
 }
 
-#[derive(Debug, Serialize)]
+/// What the API key of a user may do. Read-only keys can only read, e.g. to share a dashboard.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Default)]
+pub enum ApiKeyScope {
+    #[serde(rename = "READ_ONLY")]
+    ReadOnly,
+    #[default]
+    #[serde(rename = "READ_WRITE")]
+    ReadWrite,
+}
+
+impl fmt::Display for ApiKeyScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiKeyScope::ReadOnly => write!(f, "READ_ONLY"),
+            ApiKeyScope::ReadWrite => write!(f, "READ_WRITE"),
+        }
+    }
+}
+
+impl FromStr for ApiKeyScope {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "READ_ONLY" => Ok(ApiKeyScope::ReadOnly),
+            "READ_WRITE" => Ok(ApiKeyScope::ReadWrite),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct User {
     pub id: String,
     pub name: String,
     pub role: UserRole,
+    pub scope: ApiKeyScope,
     pub api_key: Option<String>,
     pub created_at: DateTime<Utc>,
     pub created_by: String,
@@ -53,6 +85,7 @@ impl User {
             id: Uuid::new_v4().to_string(),
             name: name.to_string(),
             role: role.clone(),
+            scope: ApiKeyScope::ReadWrite,
             api_key: Some(generate_api_key(API_KEY_LENGTH)),
             created_at: Utc::now(),
             created_by: created_by.to_string(),
@@ -0,0 +1,124 @@
+use std::sync::Arc;
+
+use crate::infrastructure::{
+    database::{DbError, TableStatusStore},
+    project_repository, time_track_repository,
+    user_repository::{self, UserStore, DEFAULT_ADMIN_API_KEY},
+};
+
+/// The status of a table that is ready for requests
+pub const ACTIVE: &str = "ACTIVE";
+/// The status of a table that does not exist
+pub const MISSING: &str = "MISSING";
+/// The status of a table that could not be described
+pub const UNKNOWN: &str = "UNKNOWN";
+
+#[derive(Debug)]
+pub struct HealthDetail {
+    /// The name and status of every table
+    pub tables: Vec<(String, String)>,
+    pub default_admin_present: bool,
+}
+
+impl HealthDetail {
+    pub fn is_healthy(&self) -> bool {
+        self.tables.iter().all(|(_, status)| status == ACTIVE)
+    }
+}
+
+#[derive(Debug)]
+pub struct HealthService {
+    table_status_store: Arc<dyn TableStatusStore>,
+    user_store: Arc<dyn UserStore>,
+}
+
+impl HealthService {
+    pub fn new(
+        table_status_store: Arc<dyn TableStatusStore>,
+        user_store: Arc<dyn UserStore>,
+    ) -> Self {
+        HealthService {
+            table_status_store,
+            user_store,
+        }
+    }
+
+    /// Describes every table and looks for the default admin, which is slow, so only for diagnostics
+    pub async fn detail(&self) -> HealthDetail {
+        let mut tables = Vec::new();
+        for table_name in [
+            user_repository::TABLE_NAME,
+            project_repository::TABLE_NAME,
+            time_track_repository::TABLE_NAME,
+        ] {
+            let status = match self.table_status_store.table_status(table_name).await {
+                Ok(status) => status,
+                Err(DbError::NotFound) => MISSING.to_string(),
+                Err(err) => {
+                    eprintln!("{}", err);
+                    UNKNOWN.to_string()
+                }
+            };
+            tables.push((table_name.to_string(), status));
+        }
+
+        let default_admin_present = self
+            .user_store
+            .get_by_api_key(DEFAULT_ADMIN_API_KEY)
+            .await
+            .is_ok();
+
+        HealthDetail {
+            tables,
+            default_admin_present,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::test_utils::in_memory_services;
+    use async_trait::async_trait;
+    use std::collections::HashMap;
+
+    /// Answers with the configured status per table, and NotFound for the rest
+    #[derive(Debug)]
+    struct FixedTableStatuses(HashMap<&'static str, &'static str>);
+
+    #[async_trait]
+    impl TableStatusStore for FixedTableStatuses {
+        async fn table_status(&self, table_name: &str) -> Result<String, DbError> {
+            self.0
+                .get(table_name)
+                .map(|status| status.to_string())
+                .ok_or(DbError::NotFound)
+        }
+    }
+
+    #[tokio::test]
+    async fn detail_reports_mixed_table_statuses() {
+        let services = in_memory_services().await;
+        let table_statuses = FixedTableStatuses(HashMap::from([
+            (user_repository::TABLE_NAME, ACTIVE),
+            (project_repository::TABLE_NAME, "CREATING"),
+        ]));
+        let health_service = HealthService::new(
+            Arc::new(table_statuses),
+            services.user_service.repository.clone(),
+        );
+
+        let detail = health_service.detail().await;
+
+        assert_eq!(
+            detail.tables,
+            vec![
+                (String::from("users"), String::from(ACTIVE)),
+                (String::from("projects"), String::from("CREATING")),
+                (String::from("time_trackings"), String::from(MISSING)),
+            ]
+        );
+        assert!(!detail.is_healthy());
+        assert!(!detail.default_admin_present);
+    }
+}
@@ -1,3 +1,6 @@
+pub mod health_service;
 pub mod project_service;
 pub mod time_track_service;
 pub mod user_service;
+#[cfg(test)]
+pub(crate) mod test_utils;
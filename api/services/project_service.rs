@@ -2,21 +2,27 @@ use tokio::sync::RwLock;
 
 use super::time_track_service::{TimeTrackError, TimeTrackService};
 use crate::{
-    infrastructure::{database::DbError, project_repository::ProjectRepository},
+    infrastructure::{database::DbError, project_repository::ProjectStore},
     models::{
         project_model::{Project, ProjectStatus},
         user_model::{User, UserRole},
     },
 };
 use std::env;
-use std::{cmp::Ordering, sync::Arc};
+use std::{cmp::Ordering, collections::BTreeMap, sync::Arc, time::Duration};
+
+/// The tag under which the time of projects without tags is summed
+pub const UNTAGGED: &str = "(untagged)";
+
+/// The total time and number of projects of a tag
+pub type TagTotal = (Duration, usize);
 
 #[derive(thiserror::Error, Debug)]
 pub enum ProjectError {
     #[error("Project not found")]
     NotFound,
-    #[error("The user has too many projects")]
-    TooManyProjects,
+    #[error("You have reached your limit of {0} projects")]
+    TooManyProjects(usize),
     #[error("Project exists with same name: {0}")]
     ProjectExistsWithSameName(String),
     #[error("Can not delete project, when time_tracking_service is None")]
@@ -35,6 +41,13 @@ impl From<DbError> for ProjectError {
                 "Conversion error in table '{}' for id '{}'",
                 table, id
             )),
+            DbError::InvalidCursor(cursor) => {
+                ProjectError::Unknown(format!("Invalid cursor: {}", cursor))
+            }
+            DbError::LimitReached(msg) => ProjectError::Unknown(msg),
+            DbError::IndexNotReady(index) => {
+                ProjectError::Unknown(format!("The index {} is still being created", index))
+            }
             DbError::Unknown(msg) => ProjectError::Unknown(msg),
         }
     }
@@ -48,14 +61,14 @@ impl From<TimeTrackError> for ProjectError {
 
 #[derive(Debug)]
 pub struct ProjectService {
-    repository: Arc<ProjectRepository>,
+    repository: Arc<dyn ProjectStore>,
     time_track_service: RwLock<Option<Arc<TimeTrackService>>>,
     max_projects: usize,
 }
 
 impl ProjectService {
     pub fn new(
-        repository: Arc<ProjectRepository>,
+        repository: Arc<dyn ProjectStore>,
         time_track_service: Option<Arc<TimeTrackService>>,
     ) -> Self {
         // Get max number of projects from env
@@ -77,29 +90,90 @@ impl ProjectService {
     }
 
     pub async fn create(&self, user: &User, project_name: String) -> Result<Project, ProjectError> {
+        self.create_with_tags(user, project_name, Vec::new()).await
+    }
+
+    pub async fn create_with_tags(
+        &self,
+        user: &User,
+        project_name: String,
+        tags: Vec<String>,
+    ) -> Result<Project, ProjectError> {
+        let project_name = project_name.trim().to_string();
+
         // Get existing projects for user
         let existing_projects = self.repository.get_all(user).await?;
 
-        // Each user can maximum have 15 projects (except admins)
+        // Each user can maximum have MAX_PROJECTS projects (except admins)
         if user.role != UserRole::Admin && existing_projects.len() >= self.max_projects {
-            return Err(ProjectError::TooManyProjects);
+            return Err(ProjectError::TooManyProjects(self.max_projects));
         }
 
-        // Check if there already is a project with the same name
-        if existing_projects.iter().any(|p| p.name == project_name) {
+        // Check if there already is a project with the same name, ignoring surrounding whitespace
+        if existing_projects
+            .iter()
+            .any(|p| p.name.trim() == project_name)
+        {
             return Err(ProjectError::ProjectExistsWithSameName(project_name));
         }
 
         // Create the new project
-        let project = Project::new(project_name, &user.id);
+        let mut project = Project::new(project_name, &user.id);
+        project.tags = normalize_tags(tags);
         self.repository.create(&project).await?;
 
         Ok(project)
     }
 
-    pub async fn get_all(&self, user: &User) -> Result<Vec<Project>, ProjectError> {
+    /// Creates the project, or returns the existing project with the same name.
+    /// The boolean is true when the project was created.
+    pub async fn get_or_create(
+        &self,
+        user: &User,
+        project_name: String,
+        tags: Vec<String>,
+    ) -> Result<(Project, bool), ProjectError> {
+        // Look for the project first, so an existing project is returned even at the project limit
+        let projects = self.repository.get_all(user).await?;
+        if let Some(existing) = projects
+            .iter()
+            .find(|p| p.name.trim() == project_name.trim())
+        {
+            // Get it again, so the duration of an ACTIVE project is up to date
+            let project = self.get(user, &existing.id).await?;
+            return Ok((project, false));
+        }
+
+        let project = self.create_with_tags(user, project_name, tags).await?;
+        Ok((project, true))
+    }
+
+    /// Gets the projects of the user whose name contains the query, ignoring case
+    pub async fn search(
+        &self,
+        user: &User,
+        tag: Option<&str>,
+        query: &str,
+    ) -> Result<Vec<Project>, ProjectError> {
+        let query = query.trim().to_lowercase();
+        let mut projects = self.get_all(user, tag).await?;
+        projects.retain(|p| p.name.to_lowercase().contains(&query));
+        Ok(projects)
+    }
+
+    /// Gets the projects of the user, only the ones with the tag if one is given
+    pub async fn get_all(
+        &self,
+        user: &User,
+        tag: Option<&str>,
+    ) -> Result<Vec<Project>, ProjectError> {
         let mut projects = self.repository.get_all(user).await?;
 
+        if let Some(tag) = tag {
+            let tag = tag.trim();
+            projects.retain(|p| p.tags.iter().any(|t| t == tag));
+        }
+
         // Sort the projects, so the ACTIVE projects occur first in the list
         projects.sort_by(|a, b| match (&a.status, &b.status) {
             (ProjectStatus::Active, ProjectStatus::Inactive) => Ordering::Less,
@@ -138,6 +212,38 @@ impl ProjectService {
         Ok(projects)
     }
 
+    /// Sums the time of the projects by tag. A project with several tags counts towards each of them,
+    /// so the totals of the tags can add up to more than the time tracked.
+    pub async fn get_total_by_tag(
+        &self,
+        user: &User,
+        include_running: bool,
+    ) -> Result<BTreeMap<String, TagTotal>, ProjectError> {
+        // The stored totals only count finished time, get_all adds the running time of ACTIVE projects
+        let projects = if include_running {
+            self.get_all(user, None).await?
+        } else {
+            self.repository.get_all(user).await?
+        };
+
+        let mut totals: BTreeMap<String, TagTotal> = BTreeMap::new();
+        for project in projects {
+            let tags = if project.tags.is_empty() {
+                vec![UNTAGGED.to_string()]
+            } else {
+                project.tags
+            };
+
+            for tag in tags {
+                let (total, project_count) = totals.entry(tag).or_default();
+                *total += project.total_duration;
+                *project_count += 1;
+            }
+        }
+
+        Ok(totals)
+    }
+
     pub async fn get(&self, user: &User, project_id: &str) -> Result<Project, ProjectError> {
         let mut project = self.repository.get(user, project_id).await?;
 
@@ -162,15 +268,74 @@ impl ProjectService {
         Ok(project)
     }
 
+    /// The project as stored, whose total leaves out the time of a running time track, for
+    /// changing the total without persisting the running time into it
+    pub async fn get_stored(&self, user: &User, project_id: &str) -> Result<Project, ProjectError> {
+        let project = self.repository.get(user, project_id).await?;
+        Ok(project)
+    }
+
     pub async fn update_name(
         &self,
         user: &User,
         project_id: String,
         new_project_name: String,
+    ) -> Result<Project, ProjectError> {
+        self.update_name_and_tags(user, project_id, new_project_name, None)
+            .await
+    }
+
+    /// Renames the project, and replaces its tags if they are given
+    pub async fn update_name_and_tags(
+        &self,
+        user: &User,
+        project_id: String,
+        new_project_name: String,
+        tags: Option<Vec<String>>,
     ) -> Result<Project, ProjectError> {
         let mut project = self.repository.get(user, &project_id).await?;
+        let projects = self.repository.get_all(user).await?;
 
-        project.name = new_project_name;
+        project.name = new_project_name.trim().to_string();
+        ensure_name_available(&projects, &project)?;
+        if let Some(tags) = tags {
+            project.tags = normalize_tags(tags);
+        }
+
+        let updated_project = self.repository.update(user, &mut project).await?;
+        Ok(updated_project)
+    }
+
+    pub async fn update_name_by_name(
+        &self,
+        user: &User,
+        project_name: &str,
+        new_project_name: String,
+    ) -> Result<Project, ProjectError> {
+        self.update_name_and_tags_by_name(user, project_name, new_project_name, None)
+            .await
+    }
+
+    pub async fn update_name_and_tags_by_name(
+        &self,
+        user: &User,
+        project_name: &str,
+        new_project_name: String,
+        tags: Option<Vec<String>>,
+    ) -> Result<Project, ProjectError> {
+        // Resolve the name server-side, so clients do not depend on a fresh name to id cache
+        let projects = self.repository.get_all(user).await?;
+        let mut project = projects
+            .iter()
+            .find(|p| p.name.trim() == project_name.trim())
+            .cloned()
+            .ok_or(ProjectError::NotFound)?;
+
+        project.name = new_project_name.trim().to_string();
+        ensure_name_available(&projects, &project)?;
+        if let Some(tags) = tags {
+            project.tags = normalize_tags(tags);
+        }
 
         let updated_project = self.repository.update(user, &mut project).await?;
         Ok(updated_project)
@@ -227,3 +392,491 @@ impl ProjectService {
         }
     }
 }
+
+// A project may keep its own name, but not take the name of another project
+fn ensure_name_available(projects: &[Project], project: &Project) -> Result<(), ProjectError> {
+    if projects
+        .iter()
+        .any(|p| p.id != project.id && p.name.trim() == project.name)
+    {
+        return Err(ProjectError::ProjectExistsWithSameName(
+            project.name.to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+// Trims the tags and drops empty and repeated ones. The tags are stored as a string set, which
+// has no order, so they are sorted to be the same before and after they are stored.
+fn normalize_tags(tags: Vec<String>) -> Vec<String> {
+    let mut normalized: Vec<String> = tags
+        .iter()
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    normalized.sort();
+    normalized.dedup();
+
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::test_utils::in_memory_services;
+
+    #[tokio::test]
+    async fn create_rejects_duplicate_project_name() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+
+        services
+            .project_service
+            .create(&user, "sitt".to_string())
+            .await
+            .unwrap();
+        let result = services
+            .project_service
+            .create(&user, "sitt".to_string())
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(ProjectError::ProjectExistsWithSameName(name)) if name == "sitt"
+        ));
+    }
+
+    #[tokio::test]
+    async fn create_reports_the_project_limit() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let max_projects = services.project_service.max_projects;
+
+        for i in 0..max_projects {
+            services
+                .project_service
+                .create(&user, format!("project {}", i))
+                .await
+                .unwrap();
+        }
+        let result = services
+            .project_service
+            .create(&user, "one too many".to_string())
+            .await;
+
+        match result {
+            Err(err @ ProjectError::TooManyProjects(_)) => assert_eq!(
+                err.to_string(),
+                format!("You have reached your limit of {} projects", max_projects)
+            ),
+            other => panic!("Expected TooManyProjects, got: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn projects_are_scoped_to_their_creator() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let other_user = User::new("other", &UserRole::User, "SYSTEM");
+
+        let project = services
+            .project_service
+            .create(&user, "sitt".to_string())
+            .await
+            .unwrap();
+
+        let result = services.project_service.get(&other_user, &project.id).await;
+        assert!(matches!(result, Err(ProjectError::NotFound)));
+        assert!(services
+            .project_service
+            .get_all(&other_user, None)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn delete_removes_project_and_its_time_tracks() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+
+        let project = services
+            .project_service
+            .create(&user, "sitt".to_string())
+            .await
+            .unwrap();
+        services
+            .time_track_service
+            .start(&user, &project.id)
+            .await
+            .unwrap();
+        services
+            .time_track_service
+            .stop(&user, &project.id)
+            .await
+            .unwrap();
+
+        services
+            .project_service
+            .delete(&user, &project.id)
+            .await
+            .unwrap();
+
+        let result = services.project_service.get(&user, &project.id).await;
+        assert!(matches!(result, Err(ProjectError::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn update_name_by_name_renames_project() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let project = services
+            .project_service
+            .create(&user, "sitt".to_string())
+            .await
+            .unwrap();
+
+        let renamed = services
+            .project_service
+            .update_name_by_name(&user, "sitt", "sitt-api".to_string())
+            .await
+            .unwrap();
+
+        assert_eq!(renamed.id, project.id, "Expected the same project");
+        assert_eq!(renamed.name, "sitt-api");
+
+        let renamed = services
+            .project_service
+            .update_name_by_name(&user, " sitt-api ", "sitt".to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            renamed.id, project.id,
+            "Expected the name to be trimmed like when it was stored"
+        );
+    }
+
+    #[tokio::test]
+    async fn update_keeps_immutable_fields() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let project = services
+            .project_service
+            .create(&user, "sitt".to_string())
+            .await
+            .unwrap();
+
+        services
+            .project_service
+            .update_name(&user, project.id.clone(), "sitt-api".to_string())
+            .await
+            .unwrap();
+        let updated = services
+            .project_service
+            .get(&user, &project.id)
+            .await
+            .unwrap();
+
+        assert_eq!(updated.name, "sitt-api");
+        assert_eq!(updated.id, project.id);
+        assert_eq!(updated.created_at, project.created_at);
+        assert_eq!(updated.created_by, project.created_by);
+    }
+
+    #[tokio::test]
+    async fn update_name_rejects_the_name_of_another_project() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let project = services
+            .project_service
+            .create(&user, "sitt".to_string())
+            .await
+            .unwrap();
+        services
+            .project_service
+            .create(&user, "Work".to_string())
+            .await
+            .unwrap();
+
+        let by_id = services
+            .project_service
+            .update_name(&user, project.id.clone(), "Work".to_string())
+            .await;
+        let by_name = services
+            .project_service
+            .update_name_by_name(&user, "sitt", " Work ".to_string())
+            .await;
+        let unchanged = services
+            .project_service
+            .update_name(&user, project.id, "sitt".to_string())
+            .await;
+
+        assert!(matches!(
+            by_id,
+            Err(ProjectError::ProjectExistsWithSameName(name)) if name == "Work"
+        ));
+        assert!(matches!(
+            by_name,
+            Err(ProjectError::ProjectExistsWithSameName(name)) if name == "Work"
+        ));
+        assert!(
+            unchanged.is_ok(),
+            "Expected a project to be able to keep its own name"
+        );
+    }
+
+    #[tokio::test]
+    async fn update_name_by_name_returns_not_found_for_unknown_name() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let other_user = User::new("other", &UserRole::User, "SYSTEM");
+        services
+            .project_service
+            .create(&other_user, "sitt".to_string())
+            .await
+            .unwrap();
+
+        // Names are resolved within the owner's projects only
+        let result = services
+            .project_service
+            .update_name_by_name(&user, "sitt", "sitt-api".to_string())
+            .await;
+
+        assert!(matches!(result, Err(ProjectError::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn create_treats_trailing_whitespace_as_duplicate() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+
+        services
+            .project_service
+            .create(&user, "Work".to_string())
+            .await
+            .unwrap();
+        let result = services
+            .project_service
+            .create(&user, "Work ".to_string())
+            .await;
+
+        assert!(
+            matches!(
+                result,
+                Err(ProjectError::ProjectExistsWithSameName(ref name)) if name == "Work"
+            ),
+            "Expected a conflict, got {:?}",
+            result
+        );
+    }
+
+    #[tokio::test]
+    async fn project_names_are_stored_trimmed() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+
+        let project = services
+            .project_service
+            .create(&user, "  Work ".to_string())
+            .await
+            .unwrap();
+        assert_eq!(project.name, "Work");
+
+        let renamed = services
+            .project_service
+            .update_name(&user, project.id, "Side project  ".to_string())
+            .await
+            .unwrap();
+        assert_eq!(renamed.name, "Side project");
+    }
+
+    #[tokio::test]
+    async fn tags_are_persisted_and_replaced_on_update() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let tags = vec![
+            String::from(" client "),
+            String::from("billable"),
+            String::from("client"),
+        ];
+
+        let project = services
+            .project_service
+            .create_with_tags(&user, "sitt".to_string(), tags)
+            .await
+            .unwrap();
+        let project = services
+            .project_service
+            .get(&user, &project.id)
+            .await
+            .unwrap();
+        assert_eq!(
+            project.tags,
+            vec!["billable", "client"],
+            "Expected the tags to be trimmed, deduplicated and sorted"
+        );
+
+        let renamed = services
+            .project_service
+            .update_name_and_tags(&user, project.id.clone(), "renamed".to_string(), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            renamed.tags,
+            vec!["billable", "client"],
+            "Expected a rename without tags to keep them"
+        );
+
+        let retagged = services
+            .project_service
+            .update_name_and_tags_by_name(
+                &user,
+                "renamed",
+                "renamed".to_string(),
+                Some(vec![String::from("internal")]),
+            )
+            .await
+            .unwrap();
+        assert_eq!(retagged.tags, vec!["internal"]);
+    }
+
+    #[tokio::test]
+    async fn get_all_filters_by_tag() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        for (name, tags) in [
+            ("client_a", vec!["client", "billable"]),
+            ("client_b", vec!["client"]),
+            ("internal", vec!["internal"]),
+            ("untagged", vec![]),
+        ] {
+            services
+                .project_service
+                .create_with_tags(
+                    &user,
+                    name.to_string(),
+                    tags.into_iter().map(String::from).collect(),
+                )
+                .await
+                .unwrap();
+        }
+        let names = |projects: Vec<Project>| -> Vec<String> {
+            let mut names: Vec<String> = projects.into_iter().map(|p| p.name).collect();
+            names.sort();
+            names
+        };
+
+        let client_projects = services
+            .project_service
+            .get_all(&user, Some("client"))
+            .await
+            .unwrap();
+        assert_eq!(names(client_projects), vec!["client_a", "client_b"]);
+
+        let billable_projects = services
+            .project_service
+            .get_all(&user, Some("billable"))
+            .await
+            .unwrap();
+        assert_eq!(names(billable_projects), vec!["client_a"]);
+
+        let all_projects = services.project_service.get_all(&user, None).await.unwrap();
+        assert_eq!(all_projects.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn get_total_by_tag_counts_projects_towards_each_of_their_tags() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        for (name, tags, hours) in [
+            ("client_a", vec!["client", "billable"], 2),
+            ("client_b", vec!["client"], 3),
+            ("support", vec!["billable", "internal"], 1),
+            ("untagged", vec![], 4),
+        ] {
+            let mut project = services
+                .project_service
+                .create_with_tags(
+                    &user,
+                    name.to_string(),
+                    tags.into_iter().map(String::from).collect(),
+                )
+                .await
+                .unwrap();
+            project.total_duration = Duration::from_secs(hours * 3600);
+            services
+                .project_service
+                .update(&user, &mut project)
+                .await
+                .unwrap();
+        }
+
+        let totals = services
+            .project_service
+            .get_total_by_tag(&user, true)
+            .await
+            .unwrap();
+
+        let hours = |tag: &str| -> (u64, usize) {
+            let (total, project_count) = totals[tag];
+            (total.as_secs() / 3600, project_count)
+        };
+        assert_eq!(hours("client"), (5, 2));
+        assert_eq!(hours("billable"), (3, 2));
+        assert_eq!(hours("internal"), (1, 1));
+        assert_eq!(hours(UNTAGGED), (4, 1));
+        assert_eq!(totals.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn get_or_create_returns_the_existing_project_at_the_limit() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let (project, created) = services
+            .project_service
+            .get_or_create(&user, "sitt".to_string(), Vec::new())
+            .await
+            .unwrap();
+        assert!(created);
+        for i in 1..15 {
+            services
+                .project_service
+                .create(&user, format!("project {}", i))
+                .await
+                .unwrap();
+        }
+
+        let (existing, created) = services
+            .project_service
+            .get_or_create(&user, " sitt ".to_string(), Vec::new())
+            .await
+            .unwrap();
+
+        assert!(!created, "Expected the existing project to be returned");
+        assert_eq!(existing.id, project.id);
+    }
+
+    #[tokio::test]
+    async fn search_matches_part_of_the_name_ignoring_case() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        for name in ["Sitt API", "sitt cli", "Website"] {
+            services
+                .project_service
+                .create(&user, name.to_string())
+                .await
+                .unwrap();
+        }
+
+        let projects = services
+            .project_service
+            .search(&user, None, " SITT ")
+            .await
+            .unwrap();
+
+        let mut names: Vec<&str> = projects.iter().map(|p| p.name.as_str()).collect();
+        names.sort();
+        assert_eq!(names, vec!["Sitt API", "sitt cli"]);
+    }
+}
@@ -0,0 +1,69 @@
+use super::{
+    project_service::ProjectService, time_track_service::TimeTrackService,
+    user_service::UserService,
+};
+use crate::{
+    infrastructure::in_memory::{InMemoryProjectStore, InMemoryTimeTrackStore, InMemoryUserStore},
+    models::user_model::{User, UserRole},
+};
+use rocket::{local::asynchronous::Client, Route};
+use std::sync::Arc;
+
+#[derive(Clone)]
+pub struct TestServices {
+    pub project_service: Arc<ProjectService>,
+    pub time_track_service: Arc<TimeTrackService>,
+    pub user_service: Arc<UserService>,
+}
+
+// Wires the services together like main.rs does, but backed by the in-memory stores
+pub async fn in_memory_services() -> TestServices {
+    let project_service = Arc::new(ProjectService::new(
+        Arc::new(InMemoryProjectStore::default()),
+        None,
+    ));
+    let user_service = Arc::new(UserService::new(
+        Arc::new(InMemoryUserStore::default()),
+        project_service.clone(),
+    ));
+    let time_track_service = Arc::new(TimeTrackService::new(
+        Arc::new(InMemoryTimeTrackStore::default()),
+        project_service.clone(),
+    ));
+    project_service
+        .set_time_track_service(time_track_service.clone())
+        .await;
+
+    TestServices {
+        project_service,
+        time_track_service,
+        user_service,
+    }
+}
+
+impl TestServices {
+    /// Creates a user with the role, as if created by an ADMIN
+    pub async fn create_user(&self, name: &str, role: &UserRole) -> User {
+        let system = User::new("SYSTEM", &UserRole::Admin, "SYSTEM");
+        self.user_service.create(name, role, &system).await.unwrap()
+    }
+
+    /// A client for the routes, with the services managed like main.rs does
+    pub async fn client(&self, routes: Vec<Route>) -> Client {
+        let rocket = rocket::build()
+            .manage(self.user_service.clone())
+            .manage(self.project_service.clone())
+            .manage(self.time_track_service.clone())
+            .mount("/", routes);
+        Client::tracked(rocket).await.unwrap()
+    }
+}
+
+/// A client for the routes backed by in-memory services, and a USER to call them with.
+/// The services are shared with the client, so tests can set up data through them.
+pub async fn test_client(routes: Vec<Route>) -> (Client, User, TestServices) {
+    let services = in_memory_services().await;
+    let user = services.create_user("test", &UserRole::User).await;
+    let client = services.client(routes).await;
+    (client, user, services)
+}
@@ -2,14 +2,16 @@ use chrono::{DateTime, Utc};
 
 use super::project_service::{ProjectError, ProjectService};
 use crate::{
-    infrastructure::{database::DbError, time_track_repository::TimeTrackRepository},
+    infrastructure::{database::DbError, time_track_repository::TimeTrackStore},
     models::{
         project_model::ProjectStatus,
-        time_track_model::{TimeTrack, TimeTrackStatus},
+        time_track_model::{
+            duration_between, TimeTrack, TimeTrackNote, TimeTrackStatus, MAX_NOTES,
+        },
         user_model::User,
     },
 };
-use std::{sync::Arc, time::Duration};
+use std::{collections::HashMap, env, sync::Arc, time::Duration};
 
 #[derive(thiserror::Error, Debug)]
 pub enum TimeTrackError {
@@ -19,8 +21,18 @@ pub enum TimeTrackError {
     ProjectNotFound,
     #[error("No time tracking is in progress for project '{0}'.")]
     NoInProgressTimeTracking(String),
-    #[error("Time tracking is already in progress on project '{0}'")]
+    #[error("Time tracking is already in progress on project '{0}', stop it first")]
     AlreadyTrackingTime(String),
+    #[error("Invalid cursor: {0}")]
+    InvalidCursor(String),
+    #[error("The time tracking already has the maximum of {} notes", MAX_NOTES)]
+    TooManyNotes,
+    #[error("Recent time trackings can't be listed yet, the database is still being prepared")]
+    IndexNotReady,
+    #[error("The time tracking can not be stopped in the future")]
+    StoppedInFuture,
+    #[error("The time tracking can not be stopped before it was started")]
+    StoppedBeforeStarted,
     #[error("Unknown error: {0}")]
     Unknown(String),
 }
@@ -33,6 +45,9 @@ impl From<DbError> for TimeTrackError {
                 "Conversion error in table '{}' for id '{}'",
                 table, id
             )),
+            DbError::InvalidCursor(cursor) => TimeTrackError::InvalidCursor(cursor),
+            DbError::LimitReached(_) => TimeTrackError::TooManyNotes,
+            DbError::IndexNotReady(_) => TimeTrackError::IndexNotReady,
             DbError::Unknown(msg) => TimeTrackError::Unknown(msg),
         }
     }
@@ -47,17 +62,35 @@ impl From<ProjectError> for TimeTrackError {
     }
 }
 
+/// A page of time tracks with their project names and the cursor to the next page
+pub type RecentPage = (Vec<(TimeTrack, String)>, Option<String>);
+
+/// The status of a project, how long its in-progress time track has been running and
+/// the total duration including it
+pub type LiveTotal = (ProjectStatus, Duration, Duration);
+
+/// The duration of a deleted time track and the total of its project without it
+pub type DeletedTotals = (Duration, Duration);
+
 #[derive(Debug)]
 pub struct TimeTrackService {
-    repository: Arc<TimeTrackRepository>,
+    repository: Arc<dyn TimeTrackStore>,
     project_service: Arc<ProjectService>,
+    verify_totals: bool,
 }
 
 impl TimeTrackService {
-    pub fn new(repository: Arc<TimeTrackRepository>, project_service: Arc<ProjectService>) -> Self {
+    pub fn new(repository: Arc<dyn TimeTrackStore>, project_service: Arc<ProjectService>) -> Self {
+        // Recompute the project total after edits, to catch drift in the incremental accounting
+        let verify_totals = env::var("SITT_VERIFY_TOTALS")
+            .ok()
+            .and_then(|val| val.parse().ok())
+            .unwrap_or(false);
+
         TimeTrackService {
             repository,
             project_service,
+            verify_totals,
         }
     }
 
@@ -68,10 +101,14 @@ impl TimeTrackService {
     ) -> Result<(TimeTrack, String), TimeTrackError> {
         let mut project = self.project_service.get(user, project_id).await?;
 
-        if project.status != ProjectStatus::Inactive {
-            return Err(TimeTrackError::AlreadyTrackingTime(
-                project.name.to_string(),
-            ));
+        // Match every status, so new statuses have to decide how they are started
+        match project.status {
+            ProjectStatus::Inactive => (),
+            ProjectStatus::Active => {
+                return Err(TimeTrackError::AlreadyTrackingTime(
+                    project.name.to_string(),
+                ))
+            }
         }
 
         // Update the project
@@ -89,7 +126,8 @@ impl TimeTrackService {
         user: &User,
         project_id: &str,
     ) -> Result<(TimeTrack, String), TimeTrackError> {
-        let mut project = self.project_service.get(user, project_id).await?;
+        // The stored total, as the running time is added below up to the stop
+        let mut project = self.project_service.get_stored(user, project_id).await?;
 
         if project.status != ProjectStatus::Active {
             return Err(TimeTrackError::NoInProgressTimeTracking(
@@ -113,15 +151,11 @@ impl TimeTrackService {
         let stopped_at = Utc::now();
         time_track.stopped_at = Some(stopped_at);
         time_track.status = TimeTrackStatus::Finished;
-        let duration = {
-            let time_delta = stopped_at - time_track.started_at;
-            Duration::new(time_delta.num_seconds() as u64, 0)
-        };
-        time_track.total_duration = duration;
+        time_track.total_duration = duration_between(time_track.started_at, stopped_at);
         self.repository.update(&time_track).await?;
 
         // Update the project to be INACTIVE
-        // No need to set update_total_duration, because it does that in get
+        project.total_duration += time_track.total_duration;
         project.status = ProjectStatus::Inactive;
 
         self.project_service.update(user, &mut project).await?;
@@ -129,24 +163,46 @@ impl TimeTrackService {
         Ok((time_track, project.name))
     }
 
+    pub async fn heartbeat(&self, user: &User, project_id: &str) -> Result<(), TimeTrackError> {
+        let project = self.project_service.get(user, project_id).await?;
+
+        // Get the IN_PROGRESS time_track for the project
+        let result = self.repository.get_in_progress(user, project_id).await;
+        let mut time_track = match result {
+            Ok(time_track) => time_track,
+            Err(err) => match err {
+                DbError::NotFound => {
+                    return Err(TimeTrackError::NoInProgressTimeTracking(project.name))
+                }
+                _ => return Err(TimeTrackError::Unknown(format!("{:#?}", err))),
+            },
+        };
+
+        time_track.last_heartbeat_at = Some(Utc::now());
+        self.repository.update(&time_track).await?;
+
+        Ok(())
+    }
+
     pub async fn create(
         &self,
         user: &User,
         project_id: String,
         started_at: DateTime<Utc>,
         stopped_at: DateTime<Utc>,
+        comment: Option<String>,
     ) -> Result<(TimeTrack, String), TimeTrackError> {
+        ensure_not_in_future(stopped_at, Utc::now())?;
+        ensure_chronological(started_at, stopped_at)?;
+
         let mut project = self.project_service.get(user, &project_id).await?;
 
         let mut time_track = TimeTrack::new(&project_id, user);
         time_track.started_at = started_at;
         time_track.stopped_at = Some(stopped_at);
+        time_track.comment = comment;
         time_track.status = TimeTrackStatus::Finished;
-        time_track.total_duration = {
-            // Calculate the duration
-            let time_delta = stopped_at - started_at;
-            Duration::new(time_delta.num_seconds() as u64, 0)
-        };
+        time_track.total_duration = duration_between(started_at, stopped_at);
         self.repository.create(&time_track).await?;
 
         project.total_duration += time_track.total_duration;
@@ -155,30 +211,93 @@ impl TimeTrackService {
         Ok((time_track, project.name))
     }
 
+    /// Gets the time tracks of the project. Without `include_running`, the IN_PROGRESS time track
+    /// counts as no time, so the result does not change while it runs, e.g. for exports.
     pub async fn get_all(
         &self,
         user: &User,
         project_id: &str,
+        include_running: bool,
     ) -> Result<(Vec<TimeTrack>, String), TimeTrackError> {
         let project = self.project_service.get(user, project_id).await?;
         let mut time_track_items = self.repository.get_all(project_id, user).await?;
 
-        if !time_track_items.is_empty() {
-            // Sort the items by started_at in descending order (newest first)
-            time_track_items.sort_by(|a, b| a.started_at.cmp(&b.started_at));
-        }
+        // Oldest first, ties by id, so equal start times, e.g. from imports, keep the same order
+        time_track_items.sort_by(|a, b| {
+            a.started_at
+                .cmp(&b.started_at)
+                .then_with(|| a.id.cmp(&b.id))
+        });
 
         let active_time_track = time_track_items.iter_mut().find(|t| t.status == TimeTrackStatus::InProgress);
         if let Some(time_track) = active_time_track {
-            time_track.total_duration = {
-                let time_delta = Utc::now() - time_track.started_at;
-                Duration::new(time_delta.num_seconds() as u64, 0 )
-            }
+            time_track.total_duration = if include_running {
+                time_track.current_duration(Utc::now())
+            } else {
+                Duration::ZERO
+            };
         }
 
         Ok((time_track_items, project.name))
     }
 
+    pub async fn get_recent(
+        &self,
+        user: &User,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> Result<RecentPage, TimeTrackError> {
+        // One query on the created_by index, newest first, instead of one per project
+        let (time_tracks, next_cursor) = self
+            .repository
+            .get_all_for_user(user, limit, cursor)
+            .await?;
+
+        let project_names: HashMap<String, String> = self
+            .project_service
+            .get_all(user, None)
+            .await?
+            .into_iter()
+            .map(|project| (project.id, project.name))
+            .collect();
+
+        // Calculate the time the IN_PROGRESS time tracks have been running
+        let now = Utc::now();
+        let time_track_items = time_tracks
+            .into_iter()
+            .map(|mut time_track| {
+                time_track.total_duration = time_track.current_duration(now);
+                let project_name = project_names
+                    .get(&time_track.project_id)
+                    .cloned()
+                    .unwrap_or_default();
+                (time_track, project_name)
+            })
+            .collect();
+
+        Ok((time_track_items, next_cursor))
+    }
+
+    pub async fn get_live_total(
+        &self,
+        user: &User,
+        project_id: &str,
+    ) -> Result<LiveTotal, TimeTrackError> {
+        // The total of an ACTIVE project already includes the running time track
+        let project = self.project_service.get(user, project_id).await?;
+
+        let elapsed = match project.status {
+            ProjectStatus::Active => {
+                self.get_in_progress(user, project_id, &project.name)
+                    .await?
+                    .total_duration
+            }
+            ProjectStatus::Inactive => Duration::new(0, 0),
+        };
+
+        Ok((project.status, elapsed, project.total_duration))
+    }
+
     pub async fn get_in_progress(
         &self,
         user: &User,
@@ -200,10 +319,7 @@ impl TimeTrackService {
         };
 
         // Calculate the time it has been running
-        time_track.total_duration = {
-            let time_delta = Utc::now() - time_track.started_at;
-            Duration::new(time_delta.num_seconds() as u64, 0)
-        };
+        time_track.total_duration = time_track.current_duration(Utc::now());
 
         Ok(time_track)
     }
@@ -215,7 +331,11 @@ impl TimeTrackService {
         time_track_id: String,
         new_started_at: DateTime<Utc>,
         new_stopped_at: DateTime<Utc>,
+        new_comment: Option<String>,
     ) -> Result<(TimeTrack, String), TimeTrackError> {
+        ensure_not_in_future(new_stopped_at, Utc::now())?;
+        ensure_chronological(new_started_at, new_stopped_at)?;
+
         let mut project = self.project_service.get(user, &project_id).await?;
 
         let mut time_track = self
@@ -229,28 +349,79 @@ impl TimeTrackService {
         // Update the time track properties
         time_track.started_at = new_started_at;
         time_track.stopped_at = Some(new_stopped_at);
-        time_track.total_duration = {
-            // Recalculate the duration
-            let time_delta = new_stopped_at - new_started_at;
-            Duration::new(time_delta.num_seconds() as u64, 0)
-        };
+        time_track.comment = new_comment;
+        // Recalculate the duration
+        time_track.total_duration = duration_between(new_started_at, new_stopped_at);
         self.repository.update(&time_track).await?;
 
         // Add the new time track duration to the project total duration
         project.total_duration += time_track.total_duration;
+
+        if self.verify_totals {
+            self.reconcile_total(user, &mut project.total_duration, &project_id)
+                .await?;
+        }
+
         self.project_service.update(user, &mut project).await?;
 
         Ok((time_track, project.name))
     }
 
+    /// Appends a note to the time track, leaving its summary comment untouched
+    pub async fn append_note(
+        &self,
+        user: &User,
+        project_id: &str,
+        time_track_id: &str,
+        text: &str,
+    ) -> Result<(TimeTrack, String), TimeTrackError> {
+        let project = self.project_service.get(user, project_id).await?;
+
+        let note = TimeTrackNote::new(text.trim());
+        let time_track = self
+            .repository
+            .append_note(user, project_id, time_track_id, &note)
+            .await?;
+
+        Ok((time_track, project.name))
+    }
+
+    /// Recomputes the total from the finished time tracks of the project and corrects it,
+    /// if it has drifted from the incrementally maintained total
+    async fn reconcile_total(
+        &self,
+        user: &User,
+        total_duration: &mut Duration,
+        project_id: &str,
+    ) -> Result<(), TimeTrackError> {
+        let time_tracks = self.repository.get_all(project_id, user).await?;
+        let recomputed: Duration = time_tracks
+            .iter()
+            .filter(|t| t.status == TimeTrackStatus::Finished)
+            .map(|t| t.total_duration)
+            .sum();
+
+        if *total_duration != recomputed {
+            eprintln!(
+                "Corrected total duration of project '{}' from {}s to {}s",
+                project_id,
+                total_duration.as_secs(),
+                recomputed.as_secs()
+            );
+            *total_duration = recomputed;
+        }
+
+        Ok(())
+    }
+
     pub async fn delete(
         &self,
         user: &User,
         project_id: String,
         time_track_id: String,
-    ) -> Result<(), TimeTrackError> {
-        // Get the project
-        let mut project = self.project_service.get(user, &project_id).await?;
+    ) -> Result<DeletedTotals, TimeTrackError> {
+        // Get the stored project, as the live total of an ACTIVE project would be saved with the running time
+        let mut project = self.project_service.get_stored(user, &project_id).await?;
 
         // Try to delete the time_track and have it returned
         let time_track = self
@@ -264,7 +435,7 @@ impl TimeTrackService {
         // Update the project with the reduced duration
         self.project_service.update(user, &mut project).await?;
 
-        Ok(())
+        Ok((time_track.total_duration, project.total_duration))
     }
 
     pub async fn delete_for_project(
@@ -272,7 +443,7 @@ impl TimeTrackService {
         user: &User,
         project_id: &str,
     ) -> Result<(), TimeTrackError> {
-        let time_track_items = self.get_all(user, project_id).await?;
+        let time_track_items = self.get_all(user, project_id, true).await?;
 
         // If there are no time track items, return OK
         if time_track_items.0.is_empty() {
@@ -285,3 +456,1090 @@ impl TimeTrackService {
         Ok(())
     }
 }
+
+/// Rejects a stop time in the future, allowing for a minute of clock skew between client and server
+fn ensure_not_in_future(
+    stopped_at: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Result<(), TimeTrackError> {
+    if stopped_at > now + chrono::Duration::minutes(1) {
+        return Err(TimeTrackError::StoppedInFuture);
+    }
+
+    Ok(())
+}
+
+/// Rejects a stop before the start. Equal timestamps are a valid zero duration entry.
+fn ensure_chronological(
+    started_at: DateTime<Utc>,
+    stopped_at: DateTime<Utc>,
+) -> Result<(), TimeTrackError> {
+    if started_at > stopped_at {
+        return Err(TimeTrackError::StoppedBeforeStarted);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::{project_model::Project, user_model::UserRole},
+        services::test_utils::{in_memory_services, TestServices},
+    };
+
+    // Logs `count` hours of time, an hour apart and alternating between two projects, newest first
+    async fn seed_recent(services: &TestServices, user: &User, count: i64) -> Vec<String> {
+        let mut projects = Vec::new();
+        for name in ["Project 0", "Project 1"] {
+            let project = services
+                .project_service
+                .create(user, name.to_string())
+                .await
+                .unwrap();
+            projects.push(project.id);
+        }
+
+        let now = Utc::now();
+        let mut time_track_ids = Vec::new();
+        for i in 0..count {
+            let started_at = now - chrono::Duration::hours(i + 1);
+            let (time_track, _) = services
+                .time_track_service
+                .create(
+                    user,
+                    projects[(i % 2) as usize].clone(),
+                    started_at,
+                    started_at + chrono::Duration::minutes(30),
+                    None,
+                )
+                .await
+                .unwrap();
+            time_track_ids.push(time_track.id);
+        }
+        time_track_ids
+    }
+
+    #[tokio::test]
+    async fn get_recent_returns_newest_first_across_projects() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let time_track_ids = seed_recent(&services, &user, 5).await;
+
+        let (page, next_cursor) = services
+            .time_track_service
+            .get_recent(&user, 3, None)
+            .await
+            .unwrap();
+
+        let ids: Vec<&str> = page.iter().map(|(t, _)| t.id.as_str()).collect();
+        assert_eq!(ids, time_track_ids[..3]);
+        let names: Vec<&str> = page.iter().map(|(_, name)| name.as_str()).collect();
+        assert_eq!(names, vec!["Project 0", "Project 1", "Project 0"]);
+        assert!(next_cursor.is_some(), "Expected a cursor to the next page");
+    }
+
+    #[tokio::test]
+    async fn get_recent_continues_from_cursor() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let time_track_ids = seed_recent(&services, &user, 5).await;
+        let service = &services.time_track_service;
+
+        let mut seen_ids = Vec::new();
+        let mut cursor = None;
+        loop {
+            let (page, next_cursor) = service
+                .get_recent(&user, 2, cursor.as_deref())
+                .await
+                .unwrap();
+            seen_ids.extend(page.into_iter().map(|(t, _)| t.id));
+            cursor = next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+
+        assert_eq!(
+            seen_ids, time_track_ids,
+            "Expected every item exactly once across the pages"
+        );
+    }
+
+    #[test]
+    fn ensure_not_in_future_rejects_future_stop() {
+        let now = Utc::now();
+
+        assert!(
+            matches!(
+                ensure_not_in_future(now + chrono::Duration::hours(1), now),
+                Err(TimeTrackError::StoppedInFuture)
+            ),
+            "Expected a stop an hour in the future to be rejected"
+        );
+        assert!(
+            ensure_not_in_future(now - chrono::Duration::hours(1), now).is_ok(),
+            "Expected a stop in the past to be accepted"
+        );
+        assert!(
+            ensure_not_in_future(now + chrono::Duration::seconds(30), now).is_ok(),
+            "Expected a stop within the clock skew allowance to be accepted"
+        );
+    }
+
+    #[tokio::test]
+    async fn get_recent_rejects_invalid_cursor() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        seed_recent(&services, &user, 2).await;
+
+        let result = services
+            .time_track_service
+            .get_recent(&user, 2, Some("not-a-cursor"))
+            .await;
+
+        assert!(
+            matches!(result, Err(TimeTrackError::InvalidCursor(_))),
+            "Expected InvalidCursor, got {:?}",
+            result.map(|(page, _)| page.len())
+        );
+    }
+
+    #[tokio::test]
+    async fn start_and_stop_toggle_project_status() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let project = services
+            .project_service
+            .create(&user, "sitt".to_string())
+            .await
+            .unwrap();
+
+        services
+            .time_track_service
+            .start(&user, &project.id)
+            .await
+            .unwrap();
+        let active = services
+            .project_service
+            .get(&user, &project.id)
+            .await
+            .unwrap();
+        assert_eq!(
+            active.status,
+            ProjectStatus::Active,
+            "Expected an ACTIVE project"
+        );
+
+        let result = services.time_track_service.start(&user, &project.id).await;
+        assert!(
+            matches!(result, Err(TimeTrackError::AlreadyTrackingTime(_))),
+            "Expected a second start to be rejected"
+        );
+
+        let (time_track, _) = services
+            .time_track_service
+            .stop(&user, &project.id)
+            .await
+            .unwrap();
+        assert_eq!(time_track.status, TimeTrackStatus::Finished);
+        let inactive = services
+            .project_service
+            .get(&user, &project.id)
+            .await
+            .unwrap();
+        assert_eq!(
+            inactive.status,
+            ProjectStatus::Inactive,
+            "Expected an INACTIVE project"
+        );
+    }
+
+    #[tokio::test]
+    async fn live_total_includes_the_running_time_track() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let project = services
+            .project_service
+            .create(&user, "sitt".to_string())
+            .await
+            .unwrap();
+        let stopped_at = Utc::now() - chrono::Duration::hours(2);
+        services
+            .time_track_service
+            .create(
+                &user,
+                project.id.clone(),
+                stopped_at - chrono::Duration::hours(1),
+                stopped_at,
+                None,
+            )
+            .await
+            .unwrap();
+
+        let (status, elapsed, total) = services
+            .time_track_service
+            .get_live_total(&user, &project.id)
+            .await
+            .unwrap();
+        assert_eq!(status, ProjectStatus::Inactive);
+        assert_eq!(elapsed.as_secs(), 0, "Expected nothing running");
+        assert_eq!(total.as_secs(), 3600);
+
+        services
+            .time_track_service
+            .start(&user, &project.id)
+            .await
+            .unwrap();
+        // Started an hour ago
+        let repository = &services.time_track_service.repository;
+        let mut running = repository
+            .get_in_progress(&user, &project.id)
+            .await
+            .unwrap();
+        running.started_at = Utc::now() - chrono::Duration::hours(1);
+        repository.update(&running).await.unwrap();
+
+        let (status, elapsed, total) = services
+            .time_track_service
+            .get_live_total(&user, &project.id)
+            .await
+            .unwrap();
+        assert_eq!(status, ProjectStatus::Active);
+        assert_eq!(elapsed.as_secs() / 60, 60);
+        assert_eq!(
+            total.as_secs() / 60,
+            120,
+            "Expected the running time to be counted once"
+        );
+
+        let project = services
+            .project_service
+            .get(&user, &project.id)
+            .await
+            .unwrap();
+        assert_eq!(project.total_duration.as_secs() / 60, 120);
+    }
+
+    #[tokio::test]
+    async fn live_total_of_an_active_project_counts_the_running_time_once() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let project = services
+            .project_service
+            .create(&user, "sitt".to_string())
+            .await
+            .unwrap();
+        services
+            .time_track_service
+            .start(&user, &project.id)
+            .await
+            .unwrap();
+        // Started half an hour ago, with nothing stored on the project yet
+        let repository = &services.time_track_service.repository;
+        let mut running = repository
+            .get_in_progress(&user, &project.id)
+            .await
+            .unwrap();
+        running.started_at = Utc::now() - chrono::Duration::minutes(30);
+        repository.update(&running).await.unwrap();
+
+        let (status, elapsed, total) = services
+            .time_track_service
+            .get_live_total(&user, &project.id)
+            .await
+            .unwrap();
+        assert_eq!(status, ProjectStatus::Active);
+        assert_eq!(elapsed.as_secs() / 60, 30);
+        assert_eq!(
+            total.as_secs() / 60,
+            30,
+            "Expected the total of an ACTIVE project to equal the running time"
+        );
+    }
+
+    #[tokio::test]
+    async fn delete_keeps_the_running_time_out_of_the_stored_total() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let project = services
+            .project_service
+            .create(&user, "sitt".to_string())
+            .await
+            .unwrap();
+        let stopped_at = Utc::now() - chrono::Duration::hours(2);
+        let mut time_track_ids = Vec::new();
+        for hours in [1, 2] {
+            let (time_track, _) = services
+                .time_track_service
+                .create(
+                    &user,
+                    project.id.clone(),
+                    stopped_at - chrono::Duration::hours(hours),
+                    stopped_at,
+                    None,
+                )
+                .await
+                .unwrap();
+            time_track_ids.push(time_track.id);
+        }
+        services
+            .time_track_service
+            .start(&user, &project.id)
+            .await
+            .unwrap();
+        // Running for half an hour
+        let repository = &services.time_track_service.repository;
+        let mut running = repository
+            .get_in_progress(&user, &project.id)
+            .await
+            .unwrap();
+        running.started_at = Utc::now() - chrono::Duration::minutes(30);
+        repository.update(&running).await.unwrap();
+
+        let (deleted, total) = services
+            .time_track_service
+            .delete(&user, project.id.clone(), time_track_ids[0].clone())
+            .await
+            .unwrap();
+
+        assert_eq!(deleted.as_secs(), 3600);
+        assert_eq!(
+            total.as_secs(),
+            2 * 3600,
+            "Expected the stored total without the running time"
+        );
+        let stored = services
+            .project_service
+            .get_stored(&user, &project.id)
+            .await
+            .unwrap();
+        assert_eq!(stored.total_duration.as_secs(), 2 * 3600);
+    }
+
+    #[tokio::test]
+    async fn get_all_orders_equal_start_times_by_id() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let project = services
+            .project_service
+            .create(&user, "sitt".to_string())
+            .await
+            .unwrap();
+        let started_at = Utc::now() - chrono::Duration::hours(2);
+        for _ in 0..5 {
+            services
+                .time_track_service
+                .create(
+                    &user,
+                    project.id.clone(),
+                    started_at,
+                    started_at + chrono::Duration::minutes(30),
+                    None,
+                )
+                .await
+                .unwrap();
+        }
+        let earlier = services
+            .time_track_service
+            .create(
+                &user,
+                project.id.clone(),
+                started_at - chrono::Duration::hours(1),
+                started_at - chrono::Duration::minutes(30),
+                None,
+            )
+            .await
+            .unwrap();
+
+        let (time_tracks, _) = services
+            .time_track_service
+            .get_all(&user, &project.id, true)
+            .await
+            .unwrap();
+
+        assert_eq!(time_tracks[0].id, earlier.0.id, "Expected the oldest first");
+        let tied_ids: Vec<&str> = time_tracks[1..].iter().map(|t| t.id.as_str()).collect();
+        let mut sorted_ids = tied_ids.clone();
+        sorted_ids.sort();
+        assert_eq!(tied_ids, sorted_ids);
+
+        let (again, _) = services
+            .time_track_service
+            .get_all(&user, &project.id, true)
+            .await
+            .unwrap();
+        let ids = |time_tracks: &[TimeTrack]| -> Vec<String> {
+            time_tracks.iter().map(|t| t.id.clone()).collect()
+        };
+        assert_eq!(ids(&time_tracks), ids(&again));
+    }
+
+    #[tokio::test]
+    async fn get_all_counts_the_running_time_track_only_when_included() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let project = services
+            .project_service
+            .create_with_tags(&user, "sitt".to_string(), vec![String::from("client")])
+            .await
+            .unwrap();
+        let stopped_at = Utc::now() - chrono::Duration::hours(2);
+        services
+            .time_track_service
+            .create(
+                &user,
+                project.id.clone(),
+                stopped_at - chrono::Duration::hours(1),
+                stopped_at,
+                None,
+            )
+            .await
+            .unwrap();
+        services
+            .time_track_service
+            .start(&user, &project.id)
+            .await
+            .unwrap();
+        // Started half an hour ago
+        let repository = &services.time_track_service.repository;
+        let mut running = repository
+            .get_in_progress(&user, &project.id)
+            .await
+            .unwrap();
+        running.started_at = Utc::now() - chrono::Duration::minutes(30);
+        repository.update(&running).await.unwrap();
+
+        for (include_running, expected_minutes) in [(true, 90), (false, 60)] {
+            let (time_tracks, _) = services
+                .time_track_service
+                .get_all(&user, &project.id, include_running)
+                .await
+                .unwrap();
+            assert_eq!(
+                time_tracks.len(),
+                2,
+                "Expected the running time track to be listed"
+            );
+            let total: Duration = time_tracks.iter().map(|t| t.total_duration).sum();
+            assert_eq!(total.as_secs() / 60, expected_minutes);
+
+            let totals = services
+                .project_service
+                .get_total_by_tag(&user, include_running)
+                .await
+                .unwrap();
+            assert_eq!(totals["client"].0.as_secs() / 60, expected_minutes);
+        }
+    }
+
+    #[tokio::test]
+    async fn update_keeps_the_owner_of_the_time_track() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let project = services
+            .project_service
+            .create(&user, "sitt".to_string())
+            .await
+            .unwrap();
+        let stopped_at = Utc::now() - chrono::Duration::hours(1);
+        let (time_track, _) = services
+            .time_track_service
+            .create(
+                &user,
+                project.id.clone(),
+                stopped_at - chrono::Duration::hours(1),
+                stopped_at,
+                None,
+            )
+            .await
+            .unwrap();
+
+        services
+            .time_track_service
+            .update(
+                &user,
+                project.id.clone(),
+                time_track.id.clone(),
+                stopped_at - chrono::Duration::minutes(30),
+                stopped_at,
+                None,
+            )
+            .await
+            .unwrap();
+        let (time_tracks, _) = services
+            .time_track_service
+            .get_all(&user, &project.id, true)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            time_tracks.len(),
+            1,
+            "Expected the time track to be updated in place"
+        );
+        assert_eq!(time_tracks[0].id, time_track.id);
+        assert_eq!(time_tracks[0].project_id, project.id);
+        assert_eq!(time_tracks[0].created_by, user.id);
+    }
+
+    #[tokio::test]
+    async fn update_reconciles_a_drifted_project_total() {
+        let services = in_memory_services().await;
+        let time_track_service = TimeTrackService {
+            repository: services.time_track_service.repository.clone(),
+            project_service: services.project_service.clone(),
+            verify_totals: true,
+        };
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let project = services
+            .project_service
+            .create(&user, "sitt".to_string())
+            .await
+            .unwrap();
+        let stopped_at = Utc::now() - chrono::Duration::hours(1);
+
+        let (time_track, _) = time_track_service
+            .create(
+                &user,
+                project.id.clone(),
+                stopped_at - chrono::Duration::hours(2),
+                stopped_at,
+                None,
+            )
+            .await
+            .unwrap();
+
+        // Drift the total, e.g. from an earlier write that only partially succeeded
+        let mut drifted = services
+            .project_service
+            .get(&user, &project.id)
+            .await
+            .unwrap();
+        drifted.total_duration += Duration::from_secs(3600);
+        services
+            .project_service
+            .update(&user, &mut drifted)
+            .await
+            .unwrap();
+
+        time_track_service
+            .update(
+                &user,
+                project.id.clone(),
+                time_track.id,
+                stopped_at - chrono::Duration::minutes(30),
+                stopped_at,
+                None,
+            )
+            .await
+            .unwrap();
+
+        let updated = services
+            .project_service
+            .get(&user, &project.id)
+            .await
+            .unwrap();
+        assert_eq!(
+            updated.total_duration.as_secs(),
+            30 * 60,
+            "Expected the drifted total to be reconciled to 30 minutes"
+        );
+    }
+
+    #[tokio::test]
+    async fn create_update_and_delete_adjust_project_total() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let project = services
+            .project_service
+            .create(&user, "sitt".to_string())
+            .await
+            .unwrap();
+        let stopped_at = Utc::now() - chrono::Duration::hours(1);
+
+        let (time_track, _) = services
+            .time_track_service
+            .create(
+                &user,
+                project.id.clone(),
+                stopped_at - chrono::Duration::hours(2),
+                stopped_at,
+                None,
+            )
+            .await
+            .unwrap();
+        let project_total = |project: Project| project.total_duration.as_secs();
+        let created = services
+            .project_service
+            .get(&user, &project.id)
+            .await
+            .unwrap();
+        assert_eq!(
+            project_total(created),
+            2 * 3600,
+            "Expected 2 hours after create"
+        );
+
+        services
+            .time_track_service
+            .update(
+                &user,
+                project.id.clone(),
+                time_track.id.clone(),
+                stopped_at - chrono::Duration::minutes(30),
+                stopped_at,
+                None,
+            )
+            .await
+            .unwrap();
+        let updated = services
+            .project_service
+            .get(&user, &project.id)
+            .await
+            .unwrap();
+        assert_eq!(
+            project_total(updated),
+            30 * 60,
+            "Expected 30 minutes after update"
+        );
+
+        let (deleted_duration, total_duration) = services
+            .time_track_service
+            .delete(&user, project.id.clone(), time_track.id)
+            .await
+            .unwrap();
+        assert_eq!(deleted_duration.as_secs(), 30 * 60);
+        assert_eq!(total_duration.as_secs(), 0);
+        let deleted = services
+            .project_service
+            .get(&user, &project.id)
+            .await
+            .unwrap();
+        assert_eq!(
+            project_total(deleted),
+            0,
+            "Expected nothing left after delete"
+        );
+    }
+
+    #[tokio::test]
+    async fn ownership_survives_user_name_change() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let project = services
+            .project_service
+            .create(&user, "sitt".to_string())
+            .await
+            .unwrap();
+        services
+            .time_track_service
+            .start(&user, &project.id)
+            .await
+            .unwrap();
+
+        // Ownership is keyed on the immutable id, so a rename must not orphan anything
+        let mut renamed_user = user.clone();
+        renamed_user.name = "renamed".to_string();
+
+        let (time_tracks, _) = services
+            .time_track_service
+            .get_all(&renamed_user, &project.id, true)
+            .await
+            .unwrap();
+        assert_eq!(
+            time_tracks.len(),
+            1,
+            "Expected the time track to still be owned"
+        );
+        assert_eq!(time_tracks[0].created_by, user.id);
+
+        let result = services
+            .time_track_service
+            .stop(&renamed_user, &project.id)
+            .await;
+        assert!(
+            result.is_ok(),
+            "Expected the renamed user to stop the time track"
+        );
+    }
+
+    #[tokio::test]
+    async fn start_on_active_project_keeps_single_time_track() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let project = services
+            .project_service
+            .create(&user, "sitt".to_string())
+            .await
+            .unwrap();
+        services
+            .time_track_service
+            .start(&user, &project.id)
+            .await
+            .unwrap();
+
+        let err = services
+            .time_track_service
+            .start(&user, &project.id)
+            .await
+            .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Time tracking is already in progress on project 'sitt', stop it first"
+        );
+        let (time_tracks, _) = services
+            .time_track_service
+            .get_all(&user, &project.id, true)
+            .await
+            .unwrap();
+        assert_eq!(time_tracks.len(), 1, "Expected no second time track");
+    }
+
+    #[tokio::test]
+    async fn heartbeat_updates_in_progress_time_track() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let project = services
+            .project_service
+            .create(&user, "sitt".to_string())
+            .await
+            .unwrap();
+
+        let result = services
+            .time_track_service
+            .heartbeat(&user, &project.id)
+            .await;
+        assert!(
+            matches!(result, Err(TimeTrackError::NoInProgressTimeTracking(_))),
+            "Expected no heartbeat without an IN_PROGRESS time track"
+        );
+
+        services
+            .time_track_service
+            .start(&user, &project.id)
+            .await
+            .unwrap();
+        services
+            .time_track_service
+            .heartbeat(&user, &project.id)
+            .await
+            .unwrap();
+
+        let time_track = services
+            .time_track_service
+            .get_in_progress(&user, &project.id, &project.name)
+            .await
+            .unwrap();
+        let last_heartbeat_at = time_track.last_heartbeat_at.expect("Expected a heartbeat");
+        assert!(!time_track.is_heartbeat_stale(last_heartbeat_at, chrono::Duration::minutes(5)));
+        assert!(time_track.is_heartbeat_stale(
+            last_heartbeat_at + chrono::Duration::minutes(6),
+            chrono::Duration::minutes(5)
+        ));
+    }
+
+    #[tokio::test]
+    async fn create_and_update_accept_zero_duration() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let project = services
+            .project_service
+            .create(&user, "sitt".to_string())
+            .await
+            .unwrap();
+        let at = Utc::now() - chrono::Duration::hours(1);
+
+        let (time_track, _) = services
+            .time_track_service
+            .create(&user, project.id.clone(), at, at, None)
+            .await
+            .unwrap();
+        assert_eq!(time_track.total_duration, Duration::ZERO);
+
+        let (time_track, _) = services
+            .time_track_service
+            .update(&user, project.id.clone(), time_track.id, at, at, None)
+            .await
+            .unwrap();
+        assert_eq!(time_track.total_duration, Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn create_and_update_reject_reversed_timestamps() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let project = services
+            .project_service
+            .create(&user, "sitt".to_string())
+            .await
+            .unwrap();
+        let stopped_at = Utc::now() - chrono::Duration::hours(1);
+        let started_at = stopped_at + chrono::Duration::seconds(1);
+
+        let result = services
+            .time_track_service
+            .create(&user, project.id.clone(), started_at, stopped_at, None)
+            .await;
+        assert!(
+            matches!(result, Err(TimeTrackError::StoppedBeforeStarted)),
+            "Expected create to reject a stop before the start"
+        );
+
+        let (time_track, _) = services
+            .time_track_service
+            .create(&user, project.id.clone(), stopped_at, stopped_at, None)
+            .await
+            .unwrap();
+        let result = services
+            .time_track_service
+            .update(
+                &user,
+                project.id,
+                time_track.id,
+                started_at,
+                stopped_at,
+                None,
+            )
+            .await;
+        assert!(
+            matches!(result, Err(TimeTrackError::StoppedBeforeStarted)),
+            "Expected update to reject a stop before the start"
+        );
+    }
+
+    #[tokio::test]
+    async fn create_backdated_time_track_records_when_it_was_logged() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let project = services
+            .project_service
+            .create(&user, "sitt".to_string())
+            .await
+            .unwrap();
+        let started_at = Utc::now() - chrono::Duration::days(1);
+        let stopped_at = started_at + chrono::Duration::hours(2);
+
+        let before = Utc::now();
+        let (time_track, _) = services
+            .time_track_service
+            .create(&user, project.id.clone(), started_at, stopped_at, None)
+            .await
+            .unwrap();
+
+        assert_eq!(time_track.started_at, started_at);
+        assert!(
+            time_track.created_at >= before,
+            "Expected created_at to be when it was logged, got: {}",
+            time_track.created_at
+        );
+        assert_ne!(time_track.created_at, time_track.started_at);
+
+        let (time_tracks, _) = services
+            .time_track_service
+            .get_all(&user, &project.id, true)
+            .await
+            .unwrap();
+        assert_eq!(
+            time_tracks[0].created_at, time_track.created_at,
+            "Expected created_at to be persisted"
+        );
+    }
+
+    #[tokio::test]
+    async fn stop_adds_exactly_the_stopped_time_to_the_project_total() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let project = services
+            .project_service
+            .create(&user, "sitt".to_string())
+            .await
+            .unwrap();
+
+        for _ in 0..3 {
+            services
+                .time_track_service
+                .start(&user, &project.id)
+                .await
+                .unwrap();
+            // Started a bit over an hour ago, so the time is measured in milliseconds
+            let repository = &services.time_track_service.repository;
+            let mut running = repository
+                .get_in_progress(&user, &project.id)
+                .await
+                .unwrap();
+            running.started_at = Utc::now() - chrono::Duration::milliseconds(3_600_123);
+            repository.update(&running).await.unwrap();
+            services
+                .time_track_service
+                .stop(&user, &project.id)
+                .await
+                .unwrap();
+        }
+
+        let stored = services
+            .project_service
+            .get_stored(&user, &project.id)
+            .await
+            .unwrap();
+        let time_tracks = services
+            .time_track_service
+            .repository
+            .get_all(&project.id, &user)
+            .await
+            .unwrap();
+        let sum: Duration = time_tracks.iter().map(|t| t.total_duration).sum();
+        assert_eq!(
+            stored.total_duration, sum,
+            "Expected the stored total to be the sum of the time tracks"
+        );
+    }
+
+    #[tokio::test]
+    async fn stop_immediately_after_start_finds_time_track() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let project = services
+            .project_service
+            .create(&user, "sitt".to_string())
+            .await
+            .unwrap();
+
+        // The repositories read the project and IN_PROGRESS time track with consistent reads,
+        // so a stop right after a start must never miss the time track
+        for _ in 0..3 {
+            let (started, _) = services
+                .time_track_service
+                .start(&user, &project.id)
+                .await
+                .unwrap();
+            let (stopped, _) = services
+                .time_track_service
+                .stop(&user, &project.id)
+                .await
+                .unwrap();
+
+            assert_eq!(stopped.id, started.id, "Expected the started time track");
+            assert_eq!(stopped.status, TimeTrackStatus::Finished);
+        }
+    }
+
+    #[tokio::test]
+    async fn append_note_keeps_the_notes_in_order() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let project = services
+            .project_service
+            .create(&user, "sitt".to_string())
+            .await
+            .unwrap();
+        let (time_track, _) = services
+            .time_track_service
+            .start(&user, &project.id)
+            .await
+            .unwrap();
+
+        for text in ["Started on the login bug", "  Found the cause  "] {
+            services
+                .time_track_service
+                .append_note(&user, &project.id, &time_track.id, text)
+                .await
+                .unwrap();
+        }
+        let (time_tracks, _) = services
+            .time_track_service
+            .get_all(&user, &project.id, true)
+            .await
+            .unwrap();
+
+        let notes: Vec<&str> = time_tracks[0]
+            .notes
+            .iter()
+            .map(|note| note.text.as_str())
+            .collect();
+        assert_eq!(notes, ["Started on the login bug", "Found the cause"]);
+        assert!(
+            time_tracks[0].comment.is_none(),
+            "Expected the comment to be left untouched"
+        );
+    }
+
+    #[tokio::test]
+    async fn append_note_rejects_time_tracks_of_other_users() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let other_user = User::new("other", &UserRole::User, "SYSTEM");
+        let project = services
+            .project_service
+            .create(&user, "sitt".to_string())
+            .await
+            .unwrap();
+        let (time_track, _) = services
+            .time_track_service
+            .start(&user, &project.id)
+            .await
+            .unwrap();
+
+        let result = services
+            .time_track_service
+            .append_note(&user, &project.id, "unknown", "A note")
+            .await;
+        assert!(matches!(result, Err(TimeTrackError::NotFound)));
+
+        let result = services
+            .time_track_service
+            .append_note(&other_user, &project.id, &time_track.id, "A note")
+            .await;
+        assert!(
+            matches!(result, Err(TimeTrackError::ProjectNotFound)),
+            "Expected the project of another user to be hidden, got {:?}",
+            result
+        );
+    }
+
+    #[tokio::test]
+    async fn short_time_tracks_sum_to_the_millisecond() {
+        let services = in_memory_services().await;
+        let user = User::new("test", &UserRole::User, "SYSTEM");
+        let project = services
+            .project_service
+            .create(&user, "sitt".to_string())
+            .await
+            .unwrap();
+        let first_started_at = Utc::now() - chrono::Duration::hours(1);
+
+        // Ten brief sessions of 1.5 seconds each
+        for i in 0..10 {
+            let started_at = first_started_at + chrono::Duration::minutes(i);
+            services
+                .time_track_service
+                .create(
+                    &user,
+                    project.id.clone(),
+                    started_at,
+                    started_at + chrono::Duration::milliseconds(1500),
+                    None,
+                )
+                .await
+                .unwrap();
+        }
+        let project = services
+            .project_service
+            .get(&user, &project.id)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            project.total_duration,
+            Duration::from_millis(15_000),
+            "Expected the milliseconds of every session to be counted"
+        );
+        assert_ne!(
+            project.total_duration,
+            Duration::from_secs(10),
+            "Expected more than the sum of the whole seconds"
+        );
+    }
+}
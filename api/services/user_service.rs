@@ -1,11 +1,11 @@
-use std::sync::Arc;
+use std::{env, sync::Arc};
 
 use crate::{
     infrastructure::{
         database::DbError,
-        user_repository::UserRepository,
+        user_repository::{UserStore, DEFAULT_ADMIN_API_KEY},
     },
-    models::user_model::{User, UserRole},
+    models::user_model::{ApiKeyScope, User, UserRole},
 };
 
 use super::project_service::{ProjectError, ProjectService};
@@ -16,6 +16,14 @@ pub enum UserError {
     NotFound,
     #[error("User does have the required permissions to perform this action")]
     Forbidden,
+    #[error("Creating ADMIN users is disabled on this server, ask an operator to provision them")]
+    AdminCreationDisabled,
+    #[error("A user named {0} already exists")]
+    NameTaken(String),
+    #[error("Only the default admin can be claimed")]
+    NotDefaultAdmin,
+    #[error("ADMIN users can't have a read-only API key")]
+    ReadOnlyAdmin,
     #[error("Unknown error: {0}")]
     Unknown(String),
     #[error("Project error")]
@@ -38,17 +46,34 @@ impl From<ProjectError> for UserError {
     }
 }
 
+/// The personal admin that replaced the default admin, and whether the default admin was deleted
+pub type ClaimedAdmin = (User, bool);
+
 #[derive(Debug)]
 pub struct UserService {
-    pub repository: Arc<UserRepository>,
+    pub repository: Arc<dyn UserStore>,
     pub project_service: Arc<ProjectService>,
+    allow_admin_creation: bool,
+    unique_user_names: bool,
 }
 
 impl UserService {
-    pub fn new(repository: Arc<UserRepository>, project_service: Arc<ProjectService>) -> Self {
+    pub fn new(repository: Arc<dyn UserStore>, project_service: Arc<ProjectService>) -> Self {
+        // Operators can lock down privilege escalation by provisioning admins out-of-band
+        let allow_admin_creation =
+            parse_allow_admin_creation(env::var("ALLOW_ADMIN_CREATION").ok());
+        // Instances that use names as stable handles can require them to be unique. This is best
+        // effort, see create_with_scope().
+        let unique_user_names = env::var("UNIQUE_USER_NAMES")
+            .ok()
+            .and_then(|val| val.parse().ok())
+            .unwrap_or(false);
+
         UserService {
             repository,
             project_service,
+            allow_admin_creation,
+            unique_user_names,
         }
     }
 
@@ -58,7 +83,37 @@ impl UserService {
         role: &UserRole,
         created_by: &User,
     ) -> Result<User, UserError> {
-        let user = User::new(name, role, &created_by.id);
+        self.create_with_scope(name, role, &ApiKeyScope::ReadWrite, created_by)
+            .await
+    }
+
+    /// Creates a user whose API key is limited to the scope, e.g. read-only for sharing dashboards
+    pub async fn create_with_scope(
+        &self,
+        name: &str,
+        role: &UserRole,
+        scope: &ApiKeyScope,
+        created_by: &User,
+    ) -> Result<User, UserError> {
+        if *role == UserRole::Admin && !self.allow_admin_creation {
+            return Err(UserError::AdminCreationDisabled);
+        }
+
+        // A read-only admin could still read every user's API key, which grants full access
+        if *role == UserRole::Admin && *scope == ApiKeyScope::ReadOnly {
+            return Err(UserError::ReadOnlyAdmin);
+        }
+
+        // Best effort only: the name is checked before the write and DynamoDB can't make an
+        // attribute other than the key unique, so concurrent creates of the same name can both
+        // succeed. The user_name index is also eventually consistent, so a user created moments
+        // ago may not be found yet.
+        if self.unique_user_names && !self.repository.get_by_name(name).await?.is_empty() {
+            return Err(UserError::NameTaken(name.to_string()));
+        }
+
+        let mut user = User::new(name, role, &created_by.id);
+        user.scope = scope.clone();
         self.repository.create(&user).await?;
 
         Ok(user)
@@ -79,6 +134,19 @@ impl UserService {
         Ok(user)
     }
 
+    /// Gets all users with the name, as names are not unique
+    pub async fn get_by_name(&self, name: &str) -> Result<Vec<User>, UserError> {
+        let mut users = self.repository.get_by_name(name).await?;
+        if users.is_empty() {
+            return Err(UserError::NotFound);
+        }
+
+        // Do not return the API KEY when looking up users
+        users.iter_mut().for_each(|user| user.api_key = None);
+
+        Ok(users)
+    }
+
     pub async fn get_all(&self) -> Result<Vec<User>, UserError> {
         let mut users = self.repository.get_all().await?;
 
@@ -93,7 +161,7 @@ impl UserService {
         let user = self.get_by_id(user_id, true).await?;
 
         // Delete all projects by user
-        let projects = self.project_service.get_all(&user).await?;
+        let projects = self.project_service.get_all(&user, None).await?;
         for project in projects {
             self.project_service.delete(&user, &project.id).await?;
         }
@@ -103,4 +171,318 @@ impl UserService {
 
         Ok(())
     }
+
+    /// Replaces the default admin with a personal admin. The personal admin is created before the
+    /// default admin is deleted, so the server is never left without an admin.
+    pub async fn claim_default_admin(
+        &self,
+        default_admin: &User,
+        name: &str,
+    ) -> Result<ClaimedAdmin, UserError> {
+        if default_admin.api_key.as_deref() != Some(DEFAULT_ADMIN_API_KEY) {
+            return Err(UserError::NotDefaultAdmin);
+        }
+
+        let admin = self.create(name, &UserRole::Admin, default_admin).await?;
+
+        // The key of the new admin is only returned here, so it must not be lost to a failed delete
+        let default_admin_deleted = match self.delete(&default_admin.id).await {
+            Ok(_) => true,
+            Err(err) => {
+                eprintln!("Failed to delete the default admin: {}", err);
+                false
+            }
+        };
+
+        Ok((admin, default_admin_deleted))
+    }
+}
+
+// Admin creation is allowed when unset, but a set value that isn't `true` disables it,
+// so a typo like ALLOW_ADMIN_CREATION=no fails closed
+fn parse_allow_admin_creation(value: Option<String>) -> bool {
+    match value {
+        Some(value) => value.trim().parse().unwrap_or(false),
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        infrastructure::user_repository::create_default_admin,
+        services::test_utils::{in_memory_services, TestServices},
+    };
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn delete_removes_user_and_their_projects() {
+        let services = in_memory_services().await;
+        let admin = User::new("admin", &UserRole::Admin, "SYSTEM");
+        let user = services
+            .user_service
+            .create("test", &UserRole::User, &admin)
+            .await
+            .unwrap();
+        services
+            .project_service
+            .create(&user, "sitt".to_string())
+            .await
+            .unwrap();
+
+        services.user_service.delete(&user.id).await.unwrap();
+
+        let result = services.user_service.get_by_id(&user.id, false).await;
+        assert!(
+            matches!(result, Err(UserError::NotFound)),
+            "Expected the user to be deleted"
+        );
+        assert!(
+            services
+                .project_service
+                .get_all(&user, None)
+                .await
+                .unwrap()
+                .is_empty(),
+            "Expected the user's projects to be deleted"
+        );
+    }
+
+    #[tokio::test]
+    async fn get_all_hides_api_keys() {
+        let services = in_memory_services().await;
+        let admin = User::new("admin", &UserRole::Admin, "SYSTEM");
+        services
+            .user_service
+            .create("test", &UserRole::User, &admin)
+            .await
+            .unwrap();
+
+        let users = services.user_service.get_all().await.unwrap();
+
+        assert!(
+            users.iter().all(|user| user.api_key.is_none()),
+            "Expected no API keys when listing users"
+        );
+    }
+
+    fn user_service(
+        services: &TestServices,
+        allow_admin_creation: bool,
+        unique_user_names: bool,
+    ) -> UserService {
+        UserService {
+            repository: services.user_service.repository.clone(),
+            project_service: services.project_service.clone(),
+            allow_admin_creation,
+            unique_user_names,
+        }
+    }
+
+    #[tokio::test]
+    async fn create_admin_is_allowed_by_default() {
+        let services = in_memory_services().await;
+        let admin = User::new("admin", &UserRole::Admin, "SYSTEM");
+        let user_service = user_service(&services, true, false);
+
+        let user = user_service
+            .create("new_admin", &UserRole::Admin, &admin)
+            .await
+            .unwrap();
+
+        assert_eq!(user.role, UserRole::Admin);
+    }
+
+    #[tokio::test]
+    async fn create_admin_is_rejected_when_disabled() {
+        let services = in_memory_services().await;
+        let admin = User::new("admin", &UserRole::Admin, "SYSTEM");
+        let user_service = user_service(&services, false, false);
+
+        let result = user_service
+            .create("new_admin", &UserRole::Admin, &admin)
+            .await;
+        assert!(
+            matches!(result, Err(UserError::AdminCreationDisabled)),
+            "Expected the ADMIN creation to be rejected, got: {:?}",
+            result
+        );
+
+        let user = user_service
+            .create("new_user", &UserRole::User, &admin)
+            .await
+            .unwrap();
+        assert_eq!(
+            user.role,
+            UserRole::User,
+            "Expected USERs to still be created"
+        );
+
+        let names: Vec<String> = user_service
+            .get_all()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|user| user.name)
+            .collect();
+        assert_eq!(names, vec!["new_user"], "Expected no ADMIN to be created");
+    }
+
+    #[tokio::test]
+    async fn duplicate_names_are_allowed_by_default() {
+        let services = in_memory_services().await;
+        let admin = User::new("admin", &UserRole::Admin, "SYSTEM");
+        let user_service = user_service(&services, true, false);
+
+        for _ in 0..2 {
+            user_service
+                .create("bob", &UserRole::User, &admin)
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(user_service.get_by_name("bob").await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn duplicate_names_are_rejected_when_unique() {
+        let services = in_memory_services().await;
+        let admin = User::new("admin", &UserRole::Admin, "SYSTEM");
+        let user_service = user_service(&services, true, true);
+
+        user_service
+            .create("bob", &UserRole::User, &admin)
+            .await
+            .unwrap();
+        let result = user_service.create("bob", &UserRole::Admin, &admin).await;
+
+        assert!(
+            matches!(result, Err(UserError::NameTaken(ref name)) if name == "bob"),
+            "Expected the duplicate name to be rejected, got: {:?}",
+            result
+        );
+        assert_eq!(
+            user_service.get_by_name("bob").await.unwrap().len(),
+            1,
+            "Expected no second bob to be created"
+        );
+    }
+
+    async fn default_admin(services: &TestServices) -> User {
+        let repository = services.user_service.repository.clone();
+        create_default_admin(repository.as_ref(), 1, Duration::ZERO)
+            .await
+            .unwrap();
+        repository
+            .get_by_api_key(DEFAULT_ADMIN_API_KEY)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn claim_default_admin_creates_the_admin_before_deleting_the_default() {
+        let services = in_memory_services().await;
+        let default_admin = default_admin(&services).await;
+
+        let (admin, default_admin_deleted) = services
+            .user_service
+            .claim_default_admin(&default_admin, "alice")
+            .await
+            .unwrap();
+
+        assert!(
+            default_admin_deleted,
+            "Expected the default admin to be deleted"
+        );
+        assert_eq!(admin.role, UserRole::Admin);
+        assert!(admin.api_key.is_some(), "Expected the key of the new admin");
+        let stored = services.user_service.get_by_id(&admin.id, false).await;
+        assert!(stored.is_ok(), "Expected the new admin to be stored");
+        let result = services
+            .user_service
+            .get_by_api_key(DEFAULT_ADMIN_API_KEY)
+            .await;
+        assert!(
+            matches!(result, Err(UserError::NotFound)),
+            "Expected the default admin to be deleted, got: {:?}",
+            result
+        );
+    }
+
+    #[tokio::test]
+    async fn claim_default_admin_keeps_the_default_when_the_create_fails() {
+        let services = in_memory_services().await;
+        let default_admin = default_admin(&services).await;
+        let user_service = user_service(&services, false, false);
+
+        let result = user_service
+            .claim_default_admin(&default_admin, "alice")
+            .await;
+
+        assert!(
+            matches!(result, Err(UserError::AdminCreationDisabled)),
+            "Expected the ADMIN creation to be rejected, got: {:?}",
+            result
+        );
+        assert!(
+            user_service
+                .get_by_api_key(DEFAULT_ADMIN_API_KEY)
+                .await
+                .is_ok(),
+            "Expected the default admin to be kept, so an admin is left"
+        );
+    }
+
+    #[tokio::test]
+    async fn claim_default_admin_rejects_other_admins() {
+        let services = in_memory_services().await;
+        default_admin(&services).await;
+        let admin = services
+            .user_service
+            .create(
+                "bob",
+                &UserRole::Admin,
+                &User::new("admin", &UserRole::Admin, "SYSTEM"),
+            )
+            .await
+            .unwrap();
+
+        let result = services
+            .user_service
+            .claim_default_admin(&admin, "alice")
+            .await;
+
+        assert!(
+            matches!(result, Err(UserError::NotDefaultAdmin)),
+            "Expected only the default admin to be claimable, got: {:?}",
+            result
+        );
+        assert!(
+            services
+                .user_service
+                .get_by_api_key(DEFAULT_ADMIN_API_KEY)
+                .await
+                .is_ok(),
+            "Expected the default admin to be kept"
+        );
+        assert!(
+            services.user_service.get_by_name("alice").await.is_err(),
+            "Expected no admin to be created"
+        );
+    }
+
+    #[test]
+    fn allow_admin_creation_fails_closed() {
+        assert!(parse_allow_admin_creation(None));
+        assert!(parse_allow_admin_creation(Some("true".to_string())));
+        assert!(!parse_allow_admin_creation(Some("false".to_string())));
+        for value in ["no", "0", "False", ""] {
+            assert!(
+                !parse_allow_admin_creation(Some(value.to_string())),
+                "Expected {:?} to disable admin creation",
+                value
+            );
+        }
+    }
 }
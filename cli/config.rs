@@ -1,14 +1,18 @@
 use colored::{Color, Colorize};
 use etcetera::BaseStrategy;
-use inquire::{validator::Validation, Text};
+use inquire::{validator::Validation, Confirm, Text};
 use serde::{Deserialize, Serialize};
-use std::{fs, io, process::exit};
+use std::{fs, io, path::PathBuf, process::exit, time::Duration};
 use thiserror::Error;
 use url::Url;
 
-use crate::sitt_client::{self, ClientError};
+use crate::{
+    sitt_client::{self, ClientError},
+    utils::ExitCode,
+};
 
 const CONFIG_FILE: &str = "sitt.toml";
+const DEFAULT_STARTUP_RETRY_BUDGET: Duration = Duration::from_secs(30);
 
 #[derive(Error, Debug)]
 pub enum ConfigError {
@@ -18,27 +22,37 @@ pub enum ConfigError {
     InvalidConfig(String),
 }
 
+impl ExitCode for ConfigError {}
+
 #[derive(Serialize, Deserialize)]
 pub struct Config {
     api_key: String,
     sitt_url: String,
+    // How many projects and logged times list commands show, unless --limit is given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    projects_per_page: Option<usize>,
+    // How many seconds reads wait in total for an API that is starting up, 30 when not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    startup_retry_secs: Option<u64>,
+    // Set from the --offline flag, never stored in the configuration file
+    #[serde(skip)]
+    offline: bool,
 }
 
 impl Config {
     fn new(api_key: String, sitt_url: String) -> Self {
-        Self { api_key, sitt_url }
+        Self {
+            api_key,
+            sitt_url,
+            projects_per_page: None,
+            startup_retry_secs: None,
+            offline: false,
+        }
     }
 
     pub fn load() -> Result<Self, ConfigError> {
-        let config_path = etcetera::choose_base_strategy()
-            .unwrap_or_else(|err| {
-                eprintln!("Error: {}", err);
-                exit(1);
-            })
-            .config_dir()
-            .join(CONFIG_FILE);
-
-        let config_content = fs::read_to_string(config_path).map_err(ConfigError::MissingFile)?;
+        let config_content =
+            fs::read_to_string(Self::config_path()).map_err(ConfigError::MissingFile)?;
 
         let config: Config = toml::from_str(&config_content)
             .map_err(|err| ConfigError::InvalidConfig(err.to_string()))?;
@@ -46,47 +60,142 @@ impl Config {
         Ok(config)
     }
 
+    pub fn export(&self, mask_api_key: bool) -> Result<String, ConfigError> {
+        let mut config = Config::new(self.api_key.clone(), self.sitt_url.clone());
+        config.projects_per_page = self.projects_per_page;
+        config.startup_retry_secs = self.startup_retry_secs;
+        if mask_api_key {
+            config.api_key = "*".repeat(config.api_key.chars().count());
+        }
+
+        toml::to_string(&config).map_err(|err| ConfigError::InvalidConfig(err.to_string()))
+    }
+
+    pub fn import(path: &PathBuf) -> Result<Self, ConfigError> {
+        let config_content = fs::read_to_string(path).map_err(ConfigError::MissingFile)?;
+        let config = Self::parse(&config_content)?;
+
+        config.save();
+
+        Ok(config)
+    }
+
+    pub fn parse(config_content: &str) -> Result<Self, ConfigError> {
+        let config: Config = toml::from_str(config_content)
+            .map_err(|err| ConfigError::InvalidConfig(err.to_string()))?;
+
+        if !is_valid_url(&config.sitt_url) {
+            return Err(ConfigError::InvalidConfig(format!(
+                "'{}' is not a valid URL",
+                config.sitt_url
+            )));
+        }
+        if is_masked_api_key(&config.api_key) {
+            return Err(ConfigError::InvalidConfig(String::from(
+                "The API key is masked, so a masked export can't be imported",
+            )));
+        }
+        if !is_valid_api_key(&config.api_key) {
+            return Err(ConfigError::InvalidConfig(String::from(
+                "The API key must be 32 characters long",
+            )));
+        }
+        if config.projects_per_page == Some(0) {
+            return Err(ConfigError::InvalidConfig(String::from(
+                "The projects per page must be at least 1",
+            )));
+        }
+
+        Ok(config)
+    }
+
     pub fn setup() -> Self {
         println!("Setup configuration:");
 
         let api_key_validator = |input: &str| {
-            if input.chars().count() != 32 {
+            if !is_valid_api_key(input) {
                 Ok(Validation::Invalid("Invalid API key, try again".into()))
             } else {
                 Ok(Validation::Valid)
             }
         };
 
-        let url_validator = |input: &str| match Url::parse(input) {
-            Ok(s) => {
-                println!("{}", s);
+        let url_validator = |input: &str| {
+            if !is_valid_url(input) {
+                Ok(Validation::Invalid("It must be a valid URL".into()))
+            } else {
                 Ok(Validation::Valid)
             }
-            Err(_) => Ok(Validation::Invalid("It must be a valid URL".into())),
         };
 
-        let sitt_url = Text::new(&format!("{} URL:", "sitt".color(Color::Yellow)))
-            .with_help_message(&format!(
-                "The URL where the {} API is hosted",
-                "sitt".color(Color::Yellow)
-            ))
-            .with_validator(url_validator)
+        let save_anyway = || {
+            Confirm::new("Couldn't reach this URL — save anyway?")
+                .with_default(false)
+                .with_help_message("Choose yes to set up sitt while the API is offline")
+                .prompt()
+                .unwrap_or_else(|err| {
+                    eprintln!("Error: {}", err);
+                    exit(1);
+                })
+        };
+
+        // Ask again until the URL is reachable, so a typo is caught before it is saved
+        let (sitt_url, reachable) = loop {
+            let sitt_url = Text::new(&format!("{} URL:", "sitt".color(Color::Yellow)))
+                .with_help_message(&format!(
+                    "The URL where the {} API is hosted",
+                    "sitt".color(Color::Yellow)
+                ))
+                .with_validator(url_validator)
+                .prompt()
+                .unwrap_or_else(|err| {
+                    eprintln!("Error: {}", err);
+                    exit(1);
+                });
+
+            match check_url(&sitt_url, sitt_client::probe_url, save_anyway) {
+                UrlCheck::Reachable => break (sitt_url, true),
+                UrlCheck::SaveAnyway => break (sitt_url, false),
+                UrlCheck::Retry => continue,
+            }
+        };
+
+        let api_key = Text::new(&format!("{} API key:", "sitt".color(Color::Yellow)))
+            .with_validator(api_key_validator)
             .prompt()
             .unwrap_or_else(|err| {
                 eprintln!("Error: {}", err);
                 exit(1);
             });
 
-        let api_key = Text::new(&format!("{} API key:", "sitt".color(Color::Yellow)))
-            .with_validator(api_key_validator)
+        let projects_per_page_validator = |input: &str| {
+            if input.trim().is_empty() || parse_projects_per_page(input).is_some() {
+                Ok(Validation::Valid)
+            } else {
+                Ok(Validation::Invalid(
+                    "It must be a number of at least 1".into(),
+                ))
+            }
+        };
+
+        let projects_per_page = Text::new("Projects per page:")
+            .with_help_message(
+                "How many projects and logged times to list. Leave empty to list all",
+            )
+            .with_validator(projects_per_page_validator)
             .prompt()
             .unwrap_or_else(|err| {
                 eprintln!("Error: {}", err);
                 exit(1);
             });
 
-        let config = Config::new(api_key, sitt_url);
-        let result = sitt_client::validate_user_config(&config);
+        let mut config = Config::new(api_key, sitt_url);
+        config.projects_per_page = parse_projects_per_page(&projects_per_page);
+        // The API key can only be checked against a reachable API
+        let result = match reachable {
+            true => sitt_client::validate_user_config(&config),
+            false => Ok(()),
+        };
         match result {
             Ok(_) => {}
             Err(ClientError::Unauthorized) => {
@@ -103,38 +212,249 @@ impl Config {
             }
         }
 
-        let toml = toml::to_string(&config).unwrap_or_else(|err| {
+        let config_path = config.save();
+
+        println!("\nConfiguration was successful ✅");
+        if !reachable {
+            println!("The API key was not checked, as the API could not be reached");
+        }
+        println!(
+            "Configuration saved at: {}",
+            &config_path.to_string_lossy().to_string()
+        );
+
+        config
+    }
+
+    pub fn save(&self) -> PathBuf {
+        let toml = toml::to_string(self).unwrap_or_else(|err| {
             eprintln!("Error: {}", err);
             exit(1);
         });
 
-        // Get configuration path fiting the OS
-        let config_path = etcetera::choose_base_strategy()
-            .unwrap_or_else(|err| {
-                eprintln!("Error: {}", err);
-                exit(1);
-            })
-            .config_dir()
-            .join(CONFIG_FILE);
-
+        let config_path = Self::config_path();
         fs::write(&config_path, toml).unwrap_or_else(|err| {
             eprintln!("Error: {}", err);
             exit(1);
         });
 
-        println!("\nConfiguration was successful ✅");
-        println!(
-            "Configuration saved at: {}",
-            &config_path.to_string_lossy().to_string()
-        );
+        config_path
+    }
 
-        config
+    // Get configuration path fiting the OS
+    fn config_path() -> PathBuf {
+        etcetera::choose_base_strategy()
+            .unwrap_or_else(|err| {
+                eprintln!("Error: {}", err);
+                exit(1);
+            })
+            .config_dir()
+            .join(CONFIG_FILE)
     }
 
     pub fn get_api_key(&self) -> &str {
         &self.api_key
     }
+    pub fn set_api_key(&mut self, api_key: String) {
+        self.api_key = api_key;
+    }
     pub fn get_url(&self) -> &str {
         &self.sitt_url
     }
+    /// The number of items a list command shows, where the --limit flag wins over the configuration
+    pub fn list_limit(&self, limit_flag: Option<usize>) -> Option<usize> {
+        limit_flag.or(self.projects_per_page)
+    }
+    /// How long reads wait in total for an API that answers 503 while it is starting up
+    pub fn startup_retry_budget(&self) -> Duration {
+        self.startup_retry_secs
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_STARTUP_RETRY_BUDGET)
+    }
+    pub fn set_offline(&mut self, offline: bool) {
+        self.offline = offline;
+    }
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+}
+
+// `sitt config export --mask` replaces every character of the API key with `*`
+fn is_masked_api_key(input: &str) -> bool {
+    !input.is_empty() && input.chars().all(|c| c == '*')
+}
+
+fn is_valid_api_key(input: &str) -> bool {
+    input.chars().count() == 32
+}
+
+enum UrlCheck {
+    Reachable,
+    SaveAnyway,
+    Retry,
+}
+
+/// Probes the URL and asks whether to save it anyway when it can not be reached
+fn check_url(
+    sitt_url: &str,
+    probe: impl Fn(&str) -> Result<(), ClientError>,
+    save_anyway: impl Fn() -> bool,
+) -> UrlCheck {
+    match probe(sitt_url) {
+        Ok(_) => UrlCheck::Reachable,
+        Err(_) if save_anyway() => UrlCheck::SaveAnyway,
+        Err(_) => UrlCheck::Retry,
+    }
+}
+
+fn is_valid_url(input: &str) -> bool {
+    Url::parse(input).is_ok()
+}
+
+fn parse_projects_per_page(input: &str) -> Option<usize> {
+    input.trim().parse().ok().filter(|&n| n > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const API_KEY: &str = "abcdefghijklmnopqrstuvwxyz123456";
+    const URL: &str = "https://sitt.example.com";
+
+    #[test]
+    fn export_import_round_trip() {
+        let config = Config::new(API_KEY.to_string(), URL.to_string());
+
+        let exported = config.export(false).unwrap();
+        let imported = Config::parse(&exported).unwrap();
+
+        assert_eq!(imported.get_url(), URL);
+        assert_eq!(imported.get_api_key(), API_KEY);
+    }
+
+    #[test]
+    fn export_masks_api_key() {
+        let config = Config::new(API_KEY.to_string(), URL.to_string());
+
+        let exported = config.export(true).unwrap();
+
+        assert!(
+            !exported.contains(API_KEY),
+            "Expected the API key to be masked, got: {}",
+            exported
+        );
+        assert!(exported.contains(URL), "Expected the URL to be exported");
+    }
+
+    #[test]
+    fn import_rejects_masked_export() {
+        let config = Config::new(API_KEY.to_string(), URL.to_string());
+
+        let exported = config.export(true).unwrap();
+
+        match Config::parse(&exported) {
+            Err(ConfigError::InvalidConfig(message)) => assert!(
+                message.contains("masked"),
+                "Expected the masked API key to be named, got: {}",
+                message
+            ),
+            Err(err) => panic!("Expected an invalid config error, got: {}", err),
+            Ok(_) => panic!("Expected a masked export to be rejected"),
+        }
+    }
+
+    #[test]
+    fn import_rejects_invalid_config() {
+        let invalid_key = format!("api_key = \"too-short\"\nsitt_url = \"{}\"", URL);
+        let invalid_url = format!("api_key = \"{}\"\nsitt_url = \"not a url\"", API_KEY);
+
+        assert!(
+            matches!(
+                Config::parse(&invalid_key),
+                Err(ConfigError::InvalidConfig(_))
+            ),
+            "Expected an invalid API key to be rejected"
+        );
+        assert!(
+            matches!(
+                Config::parse(&invalid_url),
+                Err(ConfigError::InvalidConfig(_))
+            ),
+            "Expected an invalid URL to be rejected"
+        );
+    }
+
+    #[test]
+    fn list_limit_defaults_to_the_configured_projects_per_page() {
+        let content = format!(
+            "api_key = \"{}\"\nsitt_url = \"{}\"\nprojects_per_page = 20",
+            API_KEY, URL
+        );
+        let config = Config::parse(&content).unwrap();
+
+        assert_eq!(config.list_limit(None), Some(20));
+        assert_eq!(
+            config.list_limit(Some(5)),
+            Some(5),
+            "Expected the --limit flag to win"
+        );
+    }
+
+    #[test]
+    fn list_limit_without_projects_per_page_lists_all() {
+        let content = format!("api_key = \"{}\"\nsitt_url = \"{}\"", API_KEY, URL);
+        let config = Config::parse(&content).unwrap();
+
+        assert_eq!(config.list_limit(None), None);
+        assert!(
+            !config.export(false).unwrap().contains("projects_per_page"),
+            "Expected no projects_per_page to be written when it is not set"
+        );
+    }
+
+    #[test]
+    fn startup_retry_budget_is_configurable() {
+        let content = format!("api_key = \"{}\"\nsitt_url = \"{}\"", API_KEY, URL);
+        let config = Config::parse(&content).unwrap();
+        assert_eq!(config.startup_retry_budget(), Duration::from_secs(30));
+
+        let content = format!(
+            "api_key = \"{}\"\nsitt_url = \"{}\"\nstartup_retry_secs = 0",
+            API_KEY, URL
+        );
+        let config = Config::parse(&content).unwrap();
+        assert_eq!(
+            config.startup_retry_budget(),
+            Duration::ZERO,
+            "Expected 0 to turn off the retries"
+        );
+        assert!(config
+            .export(false)
+            .unwrap()
+            .contains("startup_retry_secs = 0"));
+    }
+
+    fn unreachable(_: &str) -> Result<(), ClientError> {
+        Err(ClientError::Unreachable(String::from("connection refused")))
+    }
+
+    #[test]
+    fn check_url_saves_a_reachable_url_without_asking() {
+        let check = check_url(URL, |_| Ok(()), || panic!("Expected no question"));
+
+        assert!(matches!(check, UrlCheck::Reachable));
+    }
+
+    #[test]
+    fn check_url_asks_before_saving_an_unreachable_url() {
+        assert!(matches!(
+            check_url(URL, unreachable, || true),
+            UrlCheck::SaveAnyway
+        ));
+        assert!(matches!(
+            check_url(URL, unreachable, || false),
+            UrlCheck::Retry
+        ));
+    }
 }
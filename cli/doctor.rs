@@ -0,0 +1,428 @@
+use std::{fs, path::Path, process::exit};
+
+use colored::{Color, Colorize};
+use etcetera::BaseStrategy;
+
+use crate::{
+    config::{Config, ConfigError},
+    sitt_client::{self, ClientError},
+};
+
+const PROBE_FILE: &str = ".sitt-doctor";
+
+/// The API calls the doctor needs, so the checks can be tested without a server
+pub trait DoctorClient {
+    fn ping(&self, config: &Config) -> Result<(), ClientError>;
+    /// Returns the warnings of the API, e.g. that sitt is older than the API
+    fn authenticate(&self, config: &Config) -> Result<Vec<String>, ClientError>;
+}
+
+struct SittClient;
+
+impl DoctorClient for SittClient {
+    fn ping(&self, config: &Config) -> Result<(), ClientError> {
+        sitt_client::ping(config)
+    }
+
+    fn authenticate(&self, config: &Config) -> Result<Vec<String>, ClientError> {
+        sitt_client::validate_user_config_with_warnings(config)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum CheckStatus {
+    Pass,
+    Fail,
+    Skip,
+}
+
+#[derive(Debug)]
+struct Check {
+    name: &'static str,
+    status: CheckStatus,
+    detail: String,
+    hint: Option<&'static str>,
+    critical: bool,
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: String) -> Self {
+        Check {
+            name,
+            status: CheckStatus::Pass,
+            detail,
+            hint: None,
+            critical: false,
+        }
+    }
+
+    fn fail(name: &'static str, detail: String, hint: &'static str, critical: bool) -> Self {
+        Check {
+            name,
+            status: CheckStatus::Fail,
+            detail,
+            hint: Some(hint),
+            critical,
+        }
+    }
+
+    fn skip(name: &'static str, detail: &str) -> Self {
+        Check {
+            name,
+            status: CheckStatus::Skip,
+            detail: detail.to_string(),
+            hint: None,
+            critical: false,
+        }
+    }
+
+    fn render(&self) -> String {
+        let symbol = match self.status {
+            CheckStatus::Pass => "✅",
+            CheckStatus::Fail => "❌",
+            CheckStatus::Skip => "➖",
+        };
+
+        let mut line = format!("{} {}: {}", symbol, self.name, self.detail);
+        if let Some(hint) = self.hint {
+            line.push_str(&format!("\n   Hint: {}", hint));
+        }
+
+        line
+    }
+}
+
+pub fn run() {
+    let cache_dir = etcetera::choose_base_strategy()
+        .unwrap_or_else(|err| {
+            eprintln!("Error: {}", err);
+            exit(1);
+        })
+        .cache_dir();
+
+    let checks = run_checks(Config::load(), &SittClient, &cache_dir);
+
+    println!("🩺 Checking your {} setup:\n", "sitt".color(Color::Yellow));
+    checks
+        .iter()
+        .for_each(|check| println!("{}", check.render()));
+
+    if checks
+        .iter()
+        .any(|check| check.critical && check.status == CheckStatus::Fail)
+    {
+        exit(1);
+    }
+}
+
+fn run_checks(
+    config: Result<Config, ConfigError>,
+    client: &impl DoctorClient,
+    cache_dir: &Path,
+) -> Vec<Check> {
+    let mut checks = Vec::new();
+    let mut version = None;
+
+    match config {
+        Ok(config) => {
+            checks.push(Check::pass(
+                "Configuration",
+                String::from("The configuration file exists and is valid"),
+            ));
+
+            let reachable = check_reachable(&config, client);
+            let is_reachable = reachable.status == CheckStatus::Pass;
+            checks.push(reachable);
+
+            if is_reachable {
+                let (authentication, warnings) = check_authentication(&config, client);
+                checks.push(authentication);
+                version = warnings.map(check_version);
+            } else {
+                checks.push(Check::skip("API key", "Skipped, as the API is unreachable"));
+            }
+        }
+        Err(err) => {
+            checks.push(Check::fail(
+                "Configuration",
+                err.to_string(),
+                "Run `sitt config set` to create a valid configuration",
+                true,
+            ));
+            checks.push(Check::skip("API", "Skipped, as there is no configuration"));
+            checks.push(Check::skip(
+                "API key",
+                "Skipped, as there is no configuration",
+            ));
+        }
+    }
+
+    checks.push(check_cache_dir(cache_dir));
+    checks.push(version.unwrap_or_else(|| {
+        Check::skip(
+            "Version",
+            &format!(
+                "Client is v{}, skipped as the API could not be asked",
+                env!("CARGO_PKG_VERSION")
+            ),
+        )
+    }));
+
+    checks
+}
+
+fn check_reachable(config: &Config, client: &impl DoctorClient) -> Check {
+    match client.ping(config) {
+        Ok(_) => Check::pass("API", format!("{} is reachable", config.get_url())),
+        Err(err) => Check::fail(
+            "API",
+            err.to_string(),
+            "Check your network and the URL with `sitt config get`",
+            true,
+        ),
+    }
+}
+
+// Also returns the warnings of the API, if it accepted the API key
+fn check_authentication(
+    config: &Config,
+    client: &impl DoctorClient,
+) -> (Check, Option<Vec<String>>) {
+    match client.authenticate(config) {
+        Ok(warnings) => (
+            Check::pass("API key", String::from("The API key is accepted")),
+            Some(warnings),
+        ),
+        Err(ClientError::Unauthorized) => (
+            Check::fail(
+                "API key",
+                ClientError::Unauthorized.to_string(),
+                "Ask an admin for a new API key and run `sitt config set`",
+                true,
+            ),
+            None,
+        ),
+        Err(err) => (
+            Check::fail(
+                "API key",
+                err.to_string(),
+                "The API failed to verify the API key, try again later",
+                true,
+            ),
+            None,
+        ),
+    }
+}
+
+// The API warns in a response header when the client is older than the API
+fn check_version(warnings: Vec<String>) -> Check {
+    match warnings.into_iter().next() {
+        Some(warning) => Check::fail(
+            "Version",
+            warning,
+            "Install the latest version of sitt",
+            false,
+        ),
+        None => Check::pass(
+            "Version",
+            format!(
+                "Client is v{}, which is not older than the API",
+                env!("CARGO_PKG_VERSION")
+            ),
+        ),
+    }
+}
+
+fn check_cache_dir(cache_dir: &Path) -> Check {
+    let probe = cache_dir.join(PROBE_FILE);
+    let result = fs::create_dir_all(cache_dir)
+        .and_then(|_| fs::write(&probe, b"ok"))
+        .and_then(|_| fs::remove_file(&probe));
+
+    match result {
+        Ok(_) => Check::pass(
+            "Cache",
+            format!("{} is writable", cache_dir.to_string_lossy()),
+        ),
+        // Not critical, as the project cache is rebuilt from the API when it can not be read
+        Err(err) => Check::fail(
+            "Cache",
+            format!("{} is not writable: {}", cache_dir.to_string_lossy(), err),
+            "Make sure you own the cache directory and it is not read-only",
+            false,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, io};
+
+    const API_KEY: &str = "abcdefghijklmnopqrstuvwxyz123456";
+
+    struct MockClient {
+        reachable: bool,
+        authorized: bool,
+        version_warning: Option<&'static str>,
+    }
+
+    impl DoctorClient for MockClient {
+        fn ping(&self, _config: &Config) -> Result<(), ClientError> {
+            if !self.reachable {
+                return Err(ClientError::RequestFailed(String::from(
+                    "connection refused",
+                )));
+            }
+            Ok(())
+        }
+
+        fn authenticate(&self, _config: &Config) -> Result<Vec<String>, ClientError> {
+            if !self.authorized {
+                return Err(ClientError::Unauthorized);
+            }
+            Ok(self.version_warning.map(String::from).into_iter().collect())
+        }
+    }
+
+    fn config() -> Result<Config, ConfigError> {
+        Config::parse(&format!(
+            "api_key = \"{}\"\nsitt_url = \"https://sitt.example.com\"",
+            API_KEY
+        ))
+    }
+
+    fn find<'a>(checks: &'a [Check], name: &str) -> &'a Check {
+        checks.iter().find(|check| check.name == name).unwrap()
+    }
+
+    #[test]
+    fn all_checks_pass() {
+        let client = MockClient {
+            reachable: true,
+            authorized: true,
+            version_warning: None,
+        };
+
+        let checks = run_checks(config(), &client, &env::temp_dir());
+
+        for name in ["Configuration", "API", "API key", "Cache", "Version"] {
+            assert_eq!(
+                find(&checks, name).status,
+                CheckStatus::Pass,
+                "Expected the {} check to pass",
+                name
+            );
+        }
+        assert_eq!(
+            find(&checks, "API").render(),
+            "✅ API: https://sitt.example.com is reachable"
+        );
+    }
+
+    #[test]
+    fn outdated_client_fails_the_version_check() {
+        let client = MockClient {
+            reachable: true,
+            authorized: true,
+            version_warning: Some("sitt 0.1.0 is older than the API (0.2.0)"),
+        };
+
+        let checks = run_checks(config(), &client, &env::temp_dir());
+
+        let version = find(&checks, "Version");
+        assert!(
+            !version.critical,
+            "Expected an outdated client to not be critical"
+        );
+        assert_eq!(
+            version.render(),
+            "❌ Version: sitt 0.1.0 is older than the API (0.2.0)\n   \
+             Hint: Install the latest version of sitt"
+        );
+    }
+
+    #[test]
+    fn missing_config_fails_and_skips_api_checks() {
+        let client = MockClient {
+            reachable: true,
+            authorized: true,
+            version_warning: None,
+        };
+        let missing = Err(ConfigError::MissingFile(io::Error::new(
+            io::ErrorKind::NotFound,
+            "sitt.toml",
+        )));
+
+        let checks = run_checks(missing, &client, &env::temp_dir());
+
+        let configuration = find(&checks, "Configuration");
+        assert!(configuration.critical, "Expected a critical failure");
+        assert_eq!(
+            configuration.render(),
+            "❌ Configuration: Missing file or failed to open it. Expected it at path: sitt.toml\n   \
+             Hint: Run `sitt config set` to create a valid configuration"
+        );
+        assert_eq!(
+            find(&checks, "API").render(),
+            "➖ API: Skipped, as there is no configuration"
+        );
+    }
+
+    #[test]
+    fn unreachable_api_fails_and_skips_authentication() {
+        let client = MockClient {
+            reachable: false,
+            authorized: true,
+            version_warning: None,
+        };
+
+        let checks = run_checks(config(), &client, &env::temp_dir());
+
+        assert_eq!(
+            find(&checks, "API").render(),
+            "❌ API: Request failed: connection refused\n   \
+             Hint: Check your network and the URL with `sitt config get`"
+        );
+        assert_eq!(find(&checks, "API key").status, CheckStatus::Skip);
+    }
+
+    #[test]
+    fn rejected_api_key_fails() {
+        let client = MockClient {
+            reachable: true,
+            authorized: false,
+            version_warning: None,
+        };
+
+        let checks = run_checks(config(), &client, &env::temp_dir());
+
+        let api_key = find(&checks, "API key");
+        assert!(api_key.critical, "Expected a critical failure");
+        assert_eq!(
+            api_key.render(),
+            "❌ API key: Unauthorized request\n   \
+             Hint: Ask an admin for a new API key and run `sitt config set`"
+        );
+    }
+
+    #[test]
+    fn unwritable_cache_dir_fails_without_being_critical() {
+        // A file can not be used as a directory
+        let file = env::temp_dir().join("sitt-doctor-not-a-dir");
+        fs::write(&file, b"").unwrap();
+
+        let check = check_cache_dir(&file);
+
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert!(
+            !check.critical,
+            "Expected the cache check to not be critical"
+        );
+        assert!(
+            check.render().starts_with("❌ Cache: "),
+            "Expected a failed cache check, got: {}",
+            check.render()
+        );
+    }
+}
@@ -1,30 +1,51 @@
-use clap::{command, Args, Parser, Subcommand};
+use chrono::NaiveDate;
+use clap::{Args, Parser, Subcommand};
 use colored::{Color, Colorize};
 use config::{Config, ConfigError};
-use std::process::exit;
+use std::{path::PathBuf, process::exit};
 
 mod config;
+mod doctor;
 mod project;
+mod queue;
+mod report;
 mod sitt_client;
 mod timetrack;
 mod user;
 mod utils;
+mod watermark;
+
+const EXIT_CODES_HELP: &str = "Exit codes:
+  0  Success
+  1  Any other failure
+  2  Bad usage of the command
+  3  Unauthorized or forbidden
+  4  Conflict, e.g. the name is already in use
+  5  The API could not be reached
+  6  Not found";
 
 #[derive(Parser)]
 #[command(
     author,
     version,
-    about = "Use this CLI tool to interact with the (Si)mple (T)ime (T)racking API ⏱️"
+    about = "Use this CLI tool to interact with the (Si)mple (T)ime (T)racking API ⏱️",
+    after_help = EXIT_CODES_HELP
 )]
 struct Cli {
     #[command(subcommand)]
     command: Command,
+    #[arg(
+        long,
+        global = true,
+        help = "Resolve project names from the local cache only, without calling the API"
+    )]
+    offline: bool,
 }
 
 #[derive(Subcommand)]
 enum Command {
     #[command(about = "Start time tracking on a project")]
-    Start(NameArg),
+    Start(StartArgs),
     #[command(about = "Stop time tracking on a project")]
     Stop(NameArg),
     #[command(subcommand, about = "Manage your projects")]
@@ -35,40 +56,62 @@ enum Command {
     Config(ConfigCommand),
     #[command(subcommand, about = "[ADMIN ONLY] Manage users")]
     User(UserCommand),
+    #[command(subcommand, about = "[ADMIN ONLY] Set up the admin of a new server")]
+    Admin(AdminCommand),
+    #[command(about = "Summarize the time logged on your projects")]
+    Report(ReportArgs),
+    #[command(about = "Send time queued while the API was unreachable")]
+    Sync,
+    #[command(about = "Diagnose common setup problems")]
+    Doctor,
 }
 
 #[derive(Subcommand)]
 enum TimeTrackCommand {
     #[command(about = "Add time on a project")]
-    Add(NameArg),
+    Add(AddTimeArgs),
     #[command(about = "Delete time logged on a project")]
     Delete(NameArg),
     #[command(about = "Edit a time log on a project")]
     Edit(NameArg),
+    #[command(about = "Append a note to a time log on a project")]
+    Note(NoteArgs),
     #[command(visible_alias = "ls", about = "List time logged on a project")]
-    List(NameArg),
+    List(ListTimeArgs),
+    #[command(about = "List recently logged time across all projects")]
+    Recent(RecentArgs),
+    #[command(about = "Export time logged on a project as CSV")]
+    Export(ExportTimeArgs),
 }
 
 #[derive(Subcommand)]
 enum ProjectCommand {
     #[command(about = "Create a project")]
-    Create(NameArg),
+    Create(CreateProjectArgs),
     #[command(about = "Edit the name of a project")]
-    Edit(NameArg),
+    Edit(EditProjectArgs),
     #[command(about = "Delete a project")]
     Delete(NameArg),
-    #[command(about = "Get a project by name")]
-    Get(NameArg),
+    #[command(visible_alias = "info", about = "Get a project by name")]
+    Get(GetProjectArgs),
     #[command(visible_alias = "ls", about = "List projects")]
-    List,
+    List(ListProjectsArgs),
+}
+
+#[derive(Subcommand)]
+enum AdminCommand {
+    #[command(
+        about = "Replace the default admin with your own admin and delete the default admin"
+    )]
+    Claim(ClaimAdminArgs),
 }
 
 #[derive(Subcommand)]
 enum UserCommand {
-    #[command(about = "Create a user")]
-    Create,
+    #[command(about = "Create a user, or several users from a CSV file")]
+    Create(CreateUserArgs),
     #[command(about = "Get details about a user")]
-    Get,
+    Get(GetUserArgs),
     #[command(about = "Delete a user")]
     Delete,
     #[command(visible_alias = "ls", about = "List users")]
@@ -81,6 +124,10 @@ enum ConfigCommand {
     Set,
     #[command(about = "Get your configuration")]
     Get,
+    #[command(about = "Print your configuration, so it can be moved to another machine")]
+    Export(ExportConfigArgs),
+    #[command(about = "Import a configuration exported from another machine")]
+    Import(ImportConfigArgs),
 }
 
 #[derive(Args)]
@@ -89,12 +136,311 @@ pub struct NameArg {
     name: Option<String>,
 }
 
+#[derive(Args)]
+pub struct AddTimeArgs {
+    #[arg(short, long, help = "Specify the name of the project")]
+    name: Option<String>,
+    #[arg(
+        long,
+        requires = "name",
+        help = "Create the project first if no project has the name"
+    )]
+    create_if_missing: bool,
+    #[arg(
+        short,
+        long,
+        requires = "create_if_missing",
+        help = "Create the missing project without asking"
+    )]
+    yes: bool,
+}
+
+#[derive(Args)]
+pub struct CreateUserArgs {
+    #[arg(
+        long,
+        help = "Create the users listed in a CSV file with the columns name and role"
+    )]
+    from_file: Option<PathBuf>,
+    #[arg(
+        short,
+        long,
+        requires = "from_file",
+        default_value = "sitt-api-keys.csv",
+        help = "New file to write the API keys of the created users to, which must not exist yet"
+    )]
+    output: PathBuf,
+    #[arg(
+        long,
+        conflicts_with = "from_file",
+        help = "Give the user an API key that can only read, e.g. to share a dashboard"
+    )]
+    read_only: bool,
+}
+
+#[derive(Args)]
+pub struct GetUserArgs {
+    #[arg(
+        short,
+        long,
+        help = "Get the users with this name instead of selecting one"
+    )]
+    name: Option<String>,
+}
+
+#[derive(Args)]
+pub struct ExportConfigArgs {
+    #[arg(long, help = "Mask the API key in the output")]
+    mask: bool,
+}
+
+#[derive(Args)]
+pub struct ImportConfigArgs {
+    #[arg(help = "Path to the exported configuration file")]
+    file: PathBuf,
+}
+
+#[derive(Args)]
+pub struct ListTimeArgs {
+    #[arg(short, long, help = "Specify the name of the project")]
+    name: Option<String>,
+    #[arg(long, help = "Show times relative to now, e.g. '5 minutes ago'")]
+    relative: bool,
+    #[arg(long, help = "List the oldest logged time first")]
+    reverse: bool,
+    #[arg(
+        long,
+        help = "Show comments in full instead of truncating them to the terminal width"
+    )]
+    full: bool,
+    #[arg(
+        short,
+        long,
+        help = "Only show the first N logged times, after sorting and filtering. Defaults to the configured projects per page"
+    )]
+    limit: Option<usize>,
+    #[arg(
+        long,
+        conflicts_with = "yesterday",
+        help = "Only list time started today"
+    )]
+    today: bool,
+    #[arg(long, help = "Only list time started yesterday")]
+    yesterday: bool,
+    #[arg(
+        long,
+        visible_alias = "ndjson",
+        help = "Print one JSON object per line"
+    )]
+    json_lines: bool,
+}
+
+#[derive(Args)]
+pub struct NoteArgs {
+    #[arg(short, long, help = "Specify the name of the project")]
+    name: Option<String>,
+    #[arg(
+        long,
+        value_name = "TIME_LOG_ID",
+        help = "Append to the time log with this ID, instead of choosing it from a list"
+    )]
+    id: Option<String>,
+    #[arg(short, long, help = "The note to append")]
+    message: Option<String>,
+}
+
+#[derive(Args)]
+pub struct ClaimAdminArgs {
+    #[arg(short, long, help = "The name of your admin")]
+    name: Option<String>,
+}
+
+#[derive(Args)]
+pub struct ExportTimeArgs {
+    #[arg(short, long, help = "Specify the name of the project")]
+    name: Option<String>,
+    #[arg(long, help = "Leave out the comment column")]
+    no_comments: bool,
+    #[arg(
+        long,
+        help = "Only export time finished since the last export with this flag"
+    )]
+    since_last: bool,
+    #[arg(
+        long,
+        requires = "since_last",
+        help = "Ignore the last export and export all finished time"
+    )]
+    full: bool,
+    #[arg(
+        long,
+        help = "Count the time of a running time log so far, instead of exporting it as no time"
+    )]
+    include_running: bool,
+}
+
+#[derive(Args)]
+pub struct StartArgs {
+    #[arg(short, long, help = "Specify the name of the project")]
+    name: Option<String>,
+    #[arg(
+        short,
+        long,
+        help = "Keep running and send heartbeats, so the server can detect a crashed client"
+    )]
+    watch: bool,
+    #[arg(
+        short,
+        long = "tag",
+        help = "Add a tag to the project when starting, e.g. 'client'. Can be repeated"
+    )]
+    tags: Vec<String>,
+    #[arg(
+        long,
+        help = "Add a note about what you are working on to the time log"
+    )]
+    note: Option<String>,
+}
+
+#[derive(Args)]
+pub struct CreateProjectArgs {
+    #[arg(short, long, help = "Specify the name of the project")]
+    name: Option<String>,
+    #[arg(
+        short,
+        long = "tag",
+        help = "Tag the project, e.g. 'client' or 'billable'. Can be repeated"
+    )]
+    tags: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct EditProjectArgs {
+    #[arg(short, long, help = "Specify the name of the project")]
+    name: Option<String>,
+    #[arg(
+        short,
+        long = "tag",
+        help = "Replace the tags of the project. Can be repeated"
+    )]
+    tags: Vec<String>,
+    #[arg(
+        long,
+        conflicts_with = "tags",
+        help = "Remove all tags from the project"
+    )]
+    clear_tags: bool,
+    #[arg(
+        short,
+        long,
+        help = "Fail on a name conflict instead of suggesting another name"
+    )]
+    quiet: bool,
+}
+
+#[derive(Args)]
+pub struct GetProjectArgs {
+    #[arg(short, long, help = "Specify the name of the project")]
+    name: Option<String>,
+    #[arg(
+        short,
+        long,
+        help = "Also show the project id and who created and modified it"
+    )]
+    verbose: bool,
+}
+
+#[derive(Args)]
+pub struct ListProjectsArgs {
+    #[arg(long, help = "Show times relative to now, e.g. '5 minutes ago'")]
+    relative: bool,
+    #[arg(short, long, help = "Only list projects with the tag")]
+    tag: Option<String>,
+    #[arg(
+        long,
+        visible_alias = "ndjson",
+        help = "Print one JSON object per line"
+    )]
+    json_lines: bool,
+    #[arg(
+        short,
+        long,
+        help = "Only show the first N projects. Defaults to the configured projects per page"
+    )]
+    limit: Option<usize>,
+    #[arg(
+        long,
+        value_name = "USER_NAME_OR_ID",
+        help = "List the projects of another user. Requires an ADMIN API key"
+    )]
+    created_by: Option<String>,
+}
+
+#[derive(Args)]
+pub struct ReportArgs {
+    #[arg(
+        long,
+        help = "Sum the time by project tag. Projects with several tags count towards each"
+    )]
+    by_tag: bool,
+    #[arg(
+        long,
+        value_name = "YYYY-MM-DD",
+        conflicts_with = "by_tag",
+        help = "Only count time started on or after this day"
+    )]
+    since: Option<NaiveDate>,
+    #[arg(
+        long,
+        value_name = "YYYY-MM-DD",
+        conflicts_with = "by_tag",
+        help = "Only count time started on or before this day"
+    )]
+    until: Option<NaiveDate>,
+    #[arg(
+        long,
+        help = "Leave out the time of running time logs, which is counted so far by default"
+    )]
+    exclude_running: bool,
+}
+
+#[derive(Args)]
+pub struct RecentArgs {
+    #[arg(
+        short,
+        long,
+        default_value_t = 10,
+        help = "Number of time logs to show per page"
+    )]
+    limit: usize,
+    #[arg(long, help = "Continue from a cursor returned by a previous page")]
+    cursor: Option<String>,
+    #[arg(
+        long,
+        help = "Print the page as JSON, with any warnings of the API in a warnings array"
+    )]
+    json: bool,
+}
+
 impl Command {
     fn exec() {
         let args = Cli::parse();
 
+        // Importing a configuration must not require an existing configuration
+        if let Command::Config(ConfigCommand::Import(import_args)) = &args.command {
+            utils::print_and_exit_on_error(Config::import(&import_args.file));
+            println!("Configuration was successfully imported ✅");
+            return;
+        }
+
+        // The doctor reports a missing configuration instead of starting the setup
+        if let Command::Doctor = &args.command {
+            doctor::run();
+            return;
+        }
+
         // Ensure the configuration file is valid
-        let config: Config = config::Config::load().unwrap_or_else(|err| {
+        let mut config: Config = config::Config::load().unwrap_or_else(|err| {
             match err {
                 // Assume if there is no configuration file, it's their first time
                 ConfigError::MissingFile(_) => {
@@ -113,28 +459,40 @@ impl Command {
             }
         });
 
+        config.set_offline(args.offline);
+
         match args.command {
             Command::Start(args) => timetrack::start_time_tracking(&config, &args),
             Command::Stop(args) => timetrack::stop_time_tracking(&config, &args),
+            Command::Sync => queue::sync(&config),
+            Command::Report(args) => report::print_report(&config, &args),
             Command::Project(project_command) => match project_command {
                 ProjectCommand::Create(args) => project::create_project(&config, args),
                 ProjectCommand::Edit(args) => project::update_project(&config, &args),
                 ProjectCommand::Delete(args) => project::delete_project(&config, &args),
                 ProjectCommand::Get(args) => project::get_project_by_name(&config, &args),
-                ProjectCommand::List => project::get_projects(&config),
+                ProjectCommand::List(args) => project::get_projects(&config, &args),
             },
             Command::Time(timetrack_command) => match timetrack_command {
                 TimeTrackCommand::Add(args) => timetrack::add_time_tracking(&config, &args),
                 TimeTrackCommand::List(args) => timetrack::get_time_trackings(&config, &args),
                 TimeTrackCommand::Edit(args) => timetrack::edit_time_track(&config, &args),
+                TimeTrackCommand::Note(args) => timetrack::append_note(&config, &args),
                 TimeTrackCommand::Delete(args) => timetrack::delete_time_tracking(&config, &args),
+                TimeTrackCommand::Recent(args) => {
+                    timetrack::get_recent_time_trackings(&config, &args)
+                }
+                TimeTrackCommand::Export(args) => timetrack::export_time_trackings(&config, &args),
             },
             Command::User(user_command) => match user_command {
-                UserCommand::Create => user::create_user(&config),
-                UserCommand::Get => user::get_user(&config),
+                UserCommand::Create(args) => user::create_user(&config, &args),
+                UserCommand::Get(args) => user::get_user(&config, &args),
                 UserCommand::Delete => user::delete_user(&config),
                 UserCommand::List => user::get_users(&config),
             },
+            Command::Admin(admin_command) => match admin_command {
+                AdminCommand::Claim(args) => user::claim_default_admin(&mut config, &args),
+            },
             Command::Config(config_command) => match config_command {
                 ConfigCommand::Set => {
                     Config::setup();
@@ -144,7 +502,15 @@ impl Command {
                     println!("{} URL: {}", "sitt".color(Color::Yellow), &config.get_url(),);
                     println!("API key:  {}", &config.get_api_key());
                 }
+                ConfigCommand::Export(args) => {
+                    let toml = utils::print_and_exit_on_error(config.export(args.mask));
+                    print!("{}", toml);
+                }
+                // Handled before the configuration is loaded
+                ConfigCommand::Import(_) => {}
             },
+            // Handled before the configuration is loaded
+            Command::Doctor => {}
         }
     }
 }
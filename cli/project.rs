@@ -1,10 +1,10 @@
 use crate::{
     config::Config,
-    sitt_client,
-    utils::{self, print_and_exit_on_error, DATETIME_FORMAT},
-    NameArg,
+    sitt_client::{self, ClientError, EXIT_FAILURE, EXIT_NOT_FOUND},
+    user,
+    utils::{self, format_datetime, print_and_exit_on_error, ExitCode},
+    CreateProjectArgs, EditProjectArgs, GetProjectArgs, ListProjectsArgs, NameArg,
 };
-use chrono::Local;
 use colored::{Color, Colorize};
 use etcetera::{self, BaseStrategy};
 use inquire::{validator::Validation, Confirm, Select, Text};
@@ -17,6 +17,10 @@ use std::{fs, path::PathBuf, process::exit};
 use thiserror::Error;
 
 const CACHE_FILE: &str = "sitt-projects.toml";
+const MAX_NAME_LENGTH: usize = 25;
+// Above this many projects, they are searched before being selected. Below the API's default
+// limit of 15 projects, so the search is reachable without raising MAX_PROJECTS.
+const SEARCH_THRESHOLD: usize = 10;
 
 #[derive(Error, Debug)]
 pub enum ProjectError {
@@ -24,6 +28,19 @@ pub enum ProjectError {
     NoProjectWithName(String),
     #[error("Failed finding ID for project {0} in cache")]
     CacheError(String),
+    #[error("No project named {0} in the cache. Run the command without --offline to refresh it")]
+    NotCachedOffline(String),
+}
+
+impl ExitCode for ProjectError {
+    fn exit_code(&self) -> i32 {
+        match self {
+            ProjectError::NoProjectWithName(_) | ProjectError::NotCachedOffline(_) => {
+                EXIT_NOT_FOUND
+            }
+            ProjectError::CacheError(_) => EXIT_FAILURE,
+        }
+    }
 }
 
 pub enum ProjectSelectOption {
@@ -38,7 +55,7 @@ struct ProjectCache {
     name: String,
 }
 
-pub fn create_project(config: &Config, args: NameArg) {
+pub fn create_project(config: &Config, args: CreateProjectArgs) {
     let name = if let Some(name) = args.name {
         name
     } else {
@@ -49,16 +66,19 @@ pub fn create_project(config: &Config, args: NameArg) {
 
         name
     };
-    let create_project_dto = CreateProjectDto { name };
+    let create_project_dto = CreateProjectDto {
+        name,
+        tags: Some(args.tags),
+    };
 
     let result = sitt_client::create_project(config, &create_project_dto);
     let project = utils::print_and_exit_on_error(result);
 
     println!("New project created ✅:");
-    print_project(&project);
+    print_project(&project, false);
 }
 
-pub fn get_project_by_name(config: &Config, args: &NameArg) {
+pub fn get_project_by_name(config: &Config, args: &GetProjectArgs) {
     let name = resolve_project_name(args.name.clone(), config, "get", ProjectSelectOption::None);
 
     let project_id_result = get_project_id_by_name(config, &name);
@@ -67,10 +87,14 @@ pub fn get_project_by_name(config: &Config, args: &NameArg) {
     let api_response = sitt_client::get_project_by_id(config, &project_id);
     let project = utils::print_and_exit_on_error(api_response);
 
-    print_project(&project);
+    print_project(&project, false);
+
+    if args.verbose {
+        println!("{}", project_details(&project));
+    }
 }
 
-pub fn update_project(config: &Config, args: &NameArg) {
+pub fn update_project(config: &Config, args: &EditProjectArgs) {
     let name = resolve_project_name(
         args.name.clone(),
         config,
@@ -78,13 +102,10 @@ pub fn update_project(config: &Config, args: &NameArg) {
         ProjectSelectOption::None,
     );
 
-    let project_id_result = get_project_id_by_name(config, &name);
-    let project_id = print_and_exit_on_error(project_id_result);
-
     let length_validator = |input: &str| {
         if input.chars().count() == 0 {
             Ok(Validation::Invalid("You have to enter something.".into()))
-        } else if input.chars().count() > 25 {
+        } else if input.chars().count() > MAX_NAME_LENGTH {
             Ok(Validation::Invalid("Too long.".into()))
         } else {
             Ok(Validation::Valid)
@@ -100,12 +121,82 @@ pub fn update_project(config: &Config, args: &NameArg) {
             exit(1);
         });
 
-    let update_project_dto = CreateProjectDto { name: new_name };
+    // Only send tags when asked to change them, so editing the name keeps them
+    let tags = match (args.clear_tags, args.tags.is_empty()) {
+        (true, _) => Some(Vec::new()),
+        (false, false) => Some(args.tags.clone()),
+        (false, true) => None,
+    };
+    let update_project_dto = CreateProjectDto {
+        name: new_name,
+        tags,
+    };
 
-    let api_response = sitt_client::update_project(config, &project_id, &update_project_dto);
+    let api_response = match sitt_client::update_project(config, &name, &update_project_dto) {
+        Err(ClientError::Conflict(message)) if !args.quiet => {
+            retry_with_suggested_name(config, &name, &update_project_dto, message)
+        }
+        api_response => api_response,
+    };
     let project = utils::print_and_exit_on_error(api_response);
 
-    print_project(&project);
+    // Recache projects, so the new name resolves
+    recache_projects(config);
+
+    print_project(&project, false);
+}
+
+// Offers to rename to a free variant of the taken name instead, e.g. "Work 2"
+fn retry_with_suggested_name(
+    config: &Config,
+    name: &str,
+    update_project_dto: &CreateProjectDto,
+    conflict_message: String,
+) -> Result<ProjectDto, ClientError> {
+    let taken_name = &update_project_dto.name;
+    let existing_names: Vec<String> = sitt_client::get_projects(config)?
+        .into_iter()
+        .map(|project| project.name)
+        .collect();
+    let suggestion = suggest_project_name(taken_name, &existing_names);
+
+    let retry = Confirm::new(&format!(
+        "A project named {} already exists. Rename to {} instead?",
+        taken_name.color(Color::Cyan),
+        suggestion.color(Color::Cyan)
+    ))
+    .prompt()
+    .unwrap_or_else(|err| {
+        eprintln!("Error: {}", err);
+        exit(1);
+    });
+
+    if !retry {
+        return Err(ClientError::Conflict(conflict_message));
+    }
+
+    let update_project_dto = CreateProjectDto {
+        name: suggestion,
+        tags: update_project_dto.tags.clone(),
+    };
+    sitt_client::update_project(config, name, &update_project_dto)
+}
+
+/// Appends the lowest free number to the name, shortening it to fit the name length limit
+fn suggest_project_name(name: &str, existing_names: &[String]) -> String {
+    let name = name.trim();
+
+    (2..)
+        .map(|n| {
+            let suffix = format!(" {}", n);
+            let base: String = name
+                .chars()
+                .take(MAX_NAME_LENGTH.saturating_sub(suffix.chars().count()))
+                .collect();
+            format!("{}{}", base.trim_end(), suffix)
+        })
+        .find(|candidate| !existing_names.iter().any(|existing| existing == candidate))
+        .expect("There is always a free suffix")
 }
 
 pub fn delete_project(config: &Config, args: &NameArg) {
@@ -143,60 +234,113 @@ pub fn delete_project(config: &Config, args: &NameArg) {
     );
 }
 
-pub fn get_projects(config: &Config) {
-    let result = sitt_client::get_projects(config);
-    let projects = utils::print_and_exit_on_error(result);
+pub fn get_projects(config: &Config, args: &ListProjectsArgs) {
+    let result = match &args.created_by {
+        Some(name_or_id) => {
+            let user_id = user::resolve_user_id(config, name_or_id);
+            sitt_client::get_projects_created_by(config, &user_id, args.tag.as_deref())
+        }
+        None => sitt_client::get_projects_with_tag(config, args.tag.as_deref()),
+    };
+    let mut projects = utils::print_and_exit_on_error(result);
+    let total = projects.len();
+    let truncated_note = utils::limit_list(&mut projects, config.list_limit(args.limit));
+
+    if args.json_lines {
+        let json_lines = utils::to_json_lines(&projects);
+        print!("{}", utils::print_and_exit_on_error(json_lines));
+        return;
+    }
 
     if !projects.is_empty() {
-        println!("Your {} projects: ", projects.len());
-        projects.iter().for_each(print_project);
+        println!("Your {} projects: ", total);
+        projects
+            .iter()
+            .for_each(|project| print_project(project, args.relative));
+        if let Some(note) = truncated_note {
+            println!("{}", note);
+        }
+    } else if let Some(tag) = &args.tag {
+        println!("You have no projects tagged {}", tag.color(Color::Cyan));
     } else {
         println!("You have no projects");
     }
 }
 
+// More projects than fit on a configured page are searched, the same as more than the default
+fn search_threshold(projects_per_page: Option<usize>) -> usize {
+    projects_per_page.unwrap_or(SEARCH_THRESHOLD)
+}
+
 pub fn select_project(config: &Config, action: &str, select_option: ProjectSelectOption) -> String {
     let result = sitt_client::get_projects(config);
     let projects = utils::print_and_exit_on_error(result);
 
-    let mut options: Vec<&str> = Vec::new();
-    match select_option {
-        ProjectSelectOption::None => options = projects.iter().map(|p| p.name.as_str()).collect(),
-        ProjectSelectOption::Active => {
-            options = projects
-                .iter()
-                .filter(|p| p.status == ProjectStatus::Active)
-                .map(|p| p.name.as_str())
-                .collect()
-        }
-        ProjectSelectOption::InActive => {
-            options = projects
-                .iter()
-                .filter(|p| p.status == ProjectStatus::Inactive)
-                .map(|p| p.name.as_str())
-                .collect()
-        }
-    }
+    let mut options = project_options(&projects, &select_option);
 
     if options.is_empty() {
         println!("No projects to {} 👀", action);
         exit(0);
     }
 
-    let project_name = Select::new(
+    // Scrolling through a long list is slow, so narrow it down with a search first
+    if options.len() > search_threshold(config.list_limit(None)) {
+        let query = Text::new("Search for the project:")
+            .prompt()
+            .unwrap_or_else(|err| {
+                eprintln!("Error: {}", err);
+                exit(1);
+            });
+
+        let result = search_project_options(&query, &select_option, |query| {
+            sitt_client::search_projects(config, query)
+        });
+        options = utils::print_and_exit_on_error(result);
+
+        match options.len() {
+            0 => {
+                println!("No projects matching '{}' to {} 👀", query, action);
+                exit(0);
+            }
+            1 => return options.remove(0),
+            _ => {}
+        }
+    }
+
+    Select::new(
         &format!("Which project do you want to {}?:", action),
         options,
     )
+    .with_help_message("↑↓ to move, type to filter, enter to select")
     .prompt()
     .unwrap_or_else(|err| {
         eprintln!("Error: {}", err);
         exit(1);
-    });
+    })
+}
+
+fn project_options(projects: &[ProjectDto], select_option: &ProjectSelectOption) -> Vec<String> {
+    projects
+        .iter()
+        .filter(|p| match select_option {
+            ProjectSelectOption::None => true,
+            ProjectSelectOption::Active => p.status == ProjectStatus::Active,
+            ProjectSelectOption::InActive => p.status == ProjectStatus::Inactive,
+        })
+        .map(|p| p.name.clone())
+        .collect()
+}
 
-    project_name.to_string()
+fn search_project_options(
+    query: &str,
+    select_option: &ProjectSelectOption,
+    search: impl Fn(&str) -> Result<Vec<ProjectDto>, ClientError>,
+) -> Result<Vec<String>, ClientError> {
+    let projects = search(query.trim())?;
+    Ok(project_options(&projects, select_option))
 }
 
-fn print_project(project: &ProjectDto) {
+fn print_project(project: &ProjectDto, relative: bool) {
     let status_with_color = {
         let mut status_with_color = project.status.to_string().color(Color::Yellow);
         if project.status == ProjectStatus::Active {
@@ -214,20 +358,32 @@ CREATED AT:   {}"#,
         project.name.color(Color::Cyan),
         status_with_color,
         project.total_duration,
-        project
-            .created_at
-            .with_timezone(&Local)
-            .format(DATETIME_FORMAT),
+        format_datetime(project.created_at, relative),
     );
 
     if let Some(modified_at) = project.modified_at {
-        println!(
-            "MODIFIED AT:  {}",
-            modified_at.with_timezone(&Local).format(DATETIME_FORMAT)
-        )
+        println!("MODIFIED AT:  {}", format_datetime(modified_at, relative))
+    }
+
+    if !project.tags.is_empty() {
+        println!("TAGS:         {}", project.tags.join(", "))
     }
 }
 
+/// The fields hidden by default, which are needed for id based API calls and debugging
+fn project_details(project: &ProjectDto) -> String {
+    let mut details = format!(
+        "PROJECT ID:   {}\nCREATED BY:   {}",
+        project.project_id, project.created_by
+    );
+
+    if let Some(modified_by) = &project.modified_by {
+        details.push_str(&format!("\nMODIFIED BY:  {}", modified_by));
+    }
+
+    details
+}
+
 pub fn resolve_project_name(
     args_name: Option<String>,
     config: &Config,
@@ -252,17 +408,97 @@ pub fn get_project_id_by_name(config: &Config, name: &str) -> Result<String, Pro
         .cache_dir()
         .join(CACHE_FILE);
 
+    find_project_id(&cache_file_path, name, config.is_offline(), || {
+        cache_projects(config, &cache_file_path)
+    })
+}
+
+/// Gets the id of the project. A missing project is created first if asked to, after confirming
+/// unless `skip_confirm` is set.
+pub fn get_or_create_project_id(
+    config: &Config,
+    name: &str,
+    create_if_missing: bool,
+    skip_confirm: bool,
+) -> String {
+    let project_id_result = find_or_create_project_id(
+        name,
+        create_if_missing,
+        |name| get_project_id_by_name(config, name),
+        || skip_confirm || confirm_create_project(name),
+        |name| {
+            let create_project_dto = CreateProjectDto {
+                name: name.to_string(),
+                tags: None,
+            };
+            let api_response = sitt_client::create_project(config, &create_project_dto);
+            let project = print_and_exit_on_error(api_response);
+
+            // Recache projects, so the new name resolves next time
+            recache_projects(config);
+
+            println!("New project {} created ✅", project.name.color(Color::Cyan));
+            project.project_id
+        },
+    );
+    print_and_exit_on_error(project_id_result)
+}
+
+fn find_or_create_project_id(
+    name: &str,
+    create_if_missing: bool,
+    find_project_id: impl FnOnce(&str) -> Result<String, ProjectError>,
+    confirm_create: impl FnOnce() -> bool,
+    create_project: impl FnOnce(&str) -> String,
+) -> Result<String, ProjectError> {
+    match find_project_id(name) {
+        Err(ProjectError::NoProjectWithName(_)) if create_if_missing && confirm_create() => {
+            Ok(create_project(name))
+        }
+        result => result,
+    }
+}
+
+fn confirm_create_project(name: &str) -> bool {
+    Confirm::new(&format!(
+        "No project is named {}. Create it?",
+        name.color(Color::Cyan)
+    ))
+    .with_default(true)
+    .prompt()
+    .unwrap_or_else(|err| {
+        eprintln!("Error: {}", err);
+        exit(1);
+    })
+}
+
+// Looks up the project id in the cache. The cache is refreshed from the API, unless offline.
+fn find_project_id<F>(
+    cache_file_path: &PathBuf,
+    name: &str,
+    offline: bool,
+    mut refresh_cache: F,
+) -> Result<String, ProjectError>
+where
+    F: FnMut() -> Result<Vec<ProjectCache>, ProjectError>,
+{
+    let mut refresh_cache = || {
+        if offline {
+            return Err(ProjectError::NotCachedOffline(name.to_string()));
+        }
+        refresh_cache()
+    };
+
     let mut cache: Vec<ProjectCache> = Vec::new();
 
     // If no cache file exists, we need to create it to reduce API calls
     if !cache_file_path.exists() {
-        let new_cache_result = cache_projects(config, &cache_file_path);
-        let new_cache = print_and_exit_on_error(new_cache_result);
+        let new_cache = refresh_cache()?;
 
         // Add the newly cached projects to the existing project cache
         cache.extend(new_cache);
     } else {
-        let cache_content = fs::read_to_string(&cache_file_path).unwrap_or_else(|err| {
+        let cache_content = fs::read_to_string(cache_file_path).unwrap_or_else(|err| {
             eprintln!("Error: {}", err);
             exit(1);
         });
@@ -274,17 +510,14 @@ pub fn get_project_id_by_name(config: &Config, name: &str) -> Result<String, Pro
             Ok(existing_cache) => existing_cache, // If deserialization is successful, use the existing cache
             Err(_) => {
                 // If deserialization fails, create a new cache
-                let new_project_cache_result = cache_projects(config, &cache_file_path);
-
-                print_and_exit_on_error(new_project_cache_result) // Use the newly created cache
+                refresh_cache()? // Use the newly created cache
             }
         };
 
         // Check if the project name is in the cache => if not update cache
         let project = project_cache.iter().find(|p| p.name == name);
         if project.is_none() {
-            let new_project_cache_result = cache_projects(config, &cache_file_path);
-            project_cache = print_and_exit_on_error(new_project_cache_result);
+            project_cache = refresh_cache()?;
         }
 
         cache.extend(project_cache);
@@ -338,3 +571,198 @@ fn cache_projects(
 
     Ok(new_project_cache)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn search_threshold_follows_the_projects_per_page() {
+        assert_eq!(search_threshold(None), SEARCH_THRESHOLD);
+        assert_eq!(search_threshold(Some(5)), 5);
+    }
+
+    #[test]
+    fn find_or_create_project_id_creates_the_missing_project() {
+        let not_found = |name: &str| Err(ProjectError::NoProjectWithName(name.to_string()));
+        let created = std::cell::RefCell::new(Vec::new());
+
+        let project_id = find_or_create_project_id(
+            "new work",
+            true,
+            not_found,
+            || true,
+            |name| {
+                created.borrow_mut().push(name.to_string());
+                String::from("new_id")
+            },
+        );
+
+        assert_eq!(project_id.unwrap(), "new_id");
+        assert_eq!(created.into_inner(), vec!["new work"]);
+    }
+
+    #[test]
+    fn find_or_create_project_id_only_creates_when_asked_and_confirmed() {
+        let not_found = |name: &str| Err(ProjectError::NoProjectWithName(name.to_string()));
+        let never_create = |_: &str| -> String { panic!("Expected no project to be created") };
+
+        let existing = find_or_create_project_id(
+            "sitt",
+            true,
+            |_| Ok(String::from("id")),
+            || true,
+            never_create,
+        );
+        assert_eq!(existing.unwrap(), "id");
+
+        let not_asked = find_or_create_project_id(
+            "new work",
+            false,
+            not_found,
+            || panic!("Expected no confirmation"),
+            never_create,
+        );
+        assert!(matches!(not_asked, Err(ProjectError::NoProjectWithName(_))));
+
+        let declined =
+            find_or_create_project_id("new work", true, not_found, || false, never_create);
+        assert!(matches!(declined, Err(ProjectError::NoProjectWithName(_))));
+    }
+
+    fn project(modified_by: Option<&str>) -> ProjectDto {
+        ProjectDto {
+            project_id: String::from("3f1c9a52-8d0e-4b8e-9a57-6c1f0d2e7b41"),
+            name: String::from("sitt"),
+            status: ProjectStatus::Inactive,
+            total_duration: String::from("1h"),
+            tags: Vec::new(),
+            created_at: Utc::now(),
+            created_by: String::from("user_id"),
+            modified_at: modified_by.map(|_| Utc::now()),
+            modified_by: modified_by.map(String::from),
+        }
+    }
+
+    #[test]
+    fn project_details_shows_id_and_owner() {
+        let details = project_details(&project(Some("modifier_id")));
+
+        assert_eq!(
+            details,
+            "PROJECT ID:   3f1c9a52-8d0e-4b8e-9a57-6c1f0d2e7b41\n\
+             CREATED BY:   user_id\n\
+             MODIFIED BY:  modifier_id"
+        );
+    }
+
+    #[test]
+    fn project_details_skips_missing_modified_by() {
+        let details = project_details(&project(None));
+
+        assert!(
+            !details.contains("MODIFIED BY"),
+            "Expected no MODIFIED BY line, got:\n{}",
+            details
+        );
+    }
+
+    #[test]
+    fn suggest_project_name_appends_the_lowest_free_number() {
+        let existing = vec![String::from("Work"), String::from("Work 2")];
+
+        assert_eq!(suggest_project_name("Work", &existing), "Work 3");
+        assert_eq!(suggest_project_name("Home", &existing), "Home 2");
+    }
+
+    #[test]
+    fn suggest_project_name_fits_the_name_length_limit() {
+        let name = "abcdefghijklmnopqrstuvwxy";
+
+        let suggestion = suggest_project_name(name, &[name.to_string()]);
+
+        assert_eq!(suggestion, "abcdefghijklmnopqrstuvw 2");
+        assert_eq!(suggestion.chars().count(), MAX_NAME_LENGTH);
+    }
+
+    #[test]
+    fn search_project_options_uses_the_server_search() {
+        let options = search_project_options(" client ", &ProjectSelectOption::InActive, |query| {
+            assert_eq!(query, "client", "Expected the query to be trimmed");
+            Ok(vec![
+                ProjectDto {
+                    name: String::from("client work"),
+                    status: ProjectStatus::Active,
+                    ..project(None)
+                },
+                ProjectDto {
+                    name: String::from("client meetings"),
+                    ..project(None)
+                },
+            ])
+        });
+
+        assert_eq!(options.unwrap(), vec!["client meetings"]);
+    }
+
+    fn write_cache(file_name: &str, projects: &[(&str, &str)]) -> PathBuf {
+        let cache: Vec<ProjectCache> = projects
+            .iter()
+            .map(|(id, name)| ProjectCache {
+                id: id.to_string(),
+                name: name.to_string(),
+            })
+            .collect();
+        let path = std::env::temp_dir().join(file_name);
+        fs::write(&path, serde_json::to_string(&cache).unwrap()).unwrap();
+        path
+    }
+
+    #[test]
+    fn find_project_id_offline_uses_cache_only() {
+        let path = write_cache("sitt-offline-cached.json", &[("id_1", "sitt")]);
+
+        let project_id = find_project_id(&path, "sitt", true, || {
+            panic!("Expected no API calls in offline mode")
+        });
+        let missing = find_project_id(&path, "unknown", true, || {
+            panic!("Expected no API calls in offline mode")
+        });
+
+        assert_eq!(project_id.unwrap(), "id_1");
+        assert!(
+            matches!(missing, Err(ProjectError::NotCachedOffline(ref name)) if name == "unknown"),
+            "Expected a clear error for an uncached project"
+        );
+    }
+
+    #[test]
+    fn find_project_id_offline_without_cache_file_fails() {
+        let path = std::env::temp_dir().join("sitt-offline-missing.json");
+        let _ = fs::remove_file(&path);
+
+        let result = find_project_id(&path, "sitt", true, || {
+            panic!("Expected no API calls in offline mode")
+        });
+
+        assert!(matches!(result, Err(ProjectError::NotCachedOffline(_))));
+    }
+
+    #[test]
+    fn find_project_id_online_refreshes_stale_cache() {
+        let path = write_cache("sitt-online-stale.json", &[("id_1", "sitt")]);
+        let mut refreshes = 0;
+
+        let project_id = find_project_id(&path, "new project", false, || {
+            refreshes += 1;
+            Ok(vec![ProjectCache {
+                id: String::from("id_2"),
+                name: String::from("new project"),
+            }])
+        });
+
+        assert_eq!(project_id.unwrap(), "id_2");
+        assert_eq!(refreshes, 1, "Expected the cache to be refreshed once");
+    }
+}
@@ -0,0 +1,588 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::exit,
+};
+
+use chrono::{DateTime, Utc};
+use colored::{Color, Colorize};
+use etcetera::BaseStrategy;
+use serde::{Deserialize, Serialize};
+use sitt_api::handlers::dtos::time_track_dtos::{CreateTimeTrackDto, TimeTrackDto};
+use thiserror::Error;
+
+use crate::{
+    config::Config,
+    sitt_client::{self, ClientError},
+    utils::ExitCode,
+};
+
+const QUEUE_FILE: &str = "sitt-queue.json";
+
+#[derive(Error, Debug)]
+pub enum QueueError {
+    #[error("Failed reading the offline queue: {0}")]
+    Read(String),
+    #[error("Failed writing the offline queue: {0}")]
+    Write(String),
+}
+
+impl ExitCode for QueueError {}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "operation", rename_all = "snake_case")]
+pub enum QueuedOperation {
+    Start {
+        project_id: String,
+        project_name: String,
+    },
+    Stop {
+        project_id: String,
+        project_name: String,
+    },
+    Add {
+        project_id: String,
+        project_name: String,
+        started_at: DateTime<Utc>,
+        stopped_at: DateTime<Utc>,
+        comment: Option<String>,
+    },
+}
+
+impl QueuedOperation {
+    fn project_name(&self) -> &str {
+        match self {
+            QueuedOperation::Start { project_name, .. }
+            | QueuedOperation::Stop { project_name, .. }
+            | QueuedOperation::Add { project_name, .. } => project_name,
+        }
+    }
+
+    fn describe(&self) -> &'static str {
+        match self {
+            QueuedOperation::Start { .. } => "start",
+            QueuedOperation::Stop { .. } => "stop",
+            QueuedOperation::Add { .. } => "add",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueuedItem {
+    pub queued_at: DateTime<Utc>,
+    pub operation: QueuedOperation,
+}
+
+/// The API calls needed to replay the queue, so the replay can be tested without a server
+pub trait QueueClient {
+    fn stop(
+        &self,
+        project_id: &str,
+        stopped_at: DateTime<Utc>,
+    ) -> Result<TimeTrackDto, ClientError>;
+    fn add(&self, create_time_track: &CreateTimeTrackDto) -> Result<TimeTrackDto, ClientError>;
+}
+
+struct SittClient<'a> {
+    config: &'a Config,
+}
+
+impl QueueClient for SittClient<'_> {
+    // The API stops the time tracking at the time of the request, so it is moved back to when
+    // the stop was queued
+    fn stop(
+        &self,
+        project_id: &str,
+        stopped_at: DateTime<Utc>,
+    ) -> Result<TimeTrackDto, ClientError> {
+        let timetrack = sitt_client::stop_time_tracking(self.config, project_id)?;
+        let update_time_track = CreateTimeTrackDto {
+            project_id: project_id.to_string(),
+            started_at: timetrack.started_at,
+            stopped_at: stopped_at.max(timetrack.started_at),
+            comment: timetrack.comment,
+        };
+
+        sitt_client::update_time_track(self.config, &timetrack.time_track_id, &update_time_track)
+    }
+
+    fn add(&self, create_time_track: &CreateTimeTrackDto) -> Result<TimeTrackDto, ClientError> {
+        sitt_client::add_time_tracking(self.config, create_time_track)
+    }
+}
+
+pub struct ReplayResult {
+    pub project_name: String,
+    pub operation: &'static str,
+    pub result: Result<TimeTrackDto, ClientError>,
+}
+
+fn queue_file_path() -> PathBuf {
+    etcetera::choose_base_strategy()
+        .unwrap_or_else(|err| {
+            eprintln!("Error: {}", err);
+            exit(1);
+        })
+        .cache_dir()
+        .join(QUEUE_FILE)
+}
+
+fn load_queue(queue_file_path: &Path) -> Result<Vec<QueuedItem>, QueueError> {
+    if !queue_file_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content =
+        fs::read_to_string(queue_file_path).map_err(|err| QueueError::Read(err.to_string()))?;
+
+    serde_json::from_str(&content).map_err(|err| QueueError::Read(err.to_string()))
+}
+
+fn save_queue(queue_file_path: &Path, queue: &[QueuedItem]) -> Result<(), QueueError> {
+    // Remove the file when the queue is empty, so a stale queue is never replayed
+    if queue.is_empty() {
+        if queue_file_path.exists() {
+            fs::remove_file(queue_file_path).map_err(|err| QueueError::Write(err.to_string()))?;
+        }
+        return Ok(());
+    }
+
+    if let Some(parent) = queue_file_path.parent() {
+        fs::create_dir_all(parent).map_err(|err| QueueError::Write(err.to_string()))?;
+    }
+
+    let content =
+        serde_json::to_string_pretty(queue).map_err(|err| QueueError::Write(err.to_string()))?;
+
+    fs::write(queue_file_path, content).map_err(|err| QueueError::Write(err.to_string()))
+}
+
+fn enqueue_at(
+    queue_file_path: &Path,
+    operation: QueuedOperation,
+    queued_at: DateTime<Utc>,
+) -> Result<(), QueueError> {
+    let mut queue = load_queue(queue_file_path)?;
+    queue.push(QueuedItem {
+        queued_at,
+        operation,
+    });
+
+    save_queue(queue_file_path, &queue)
+}
+
+/// Queues the operation if the API is unreachable. Exits after queueing, otherwise the
+/// result is handed back to the caller.
+pub fn queue_if_unreachable<T>(
+    result: Result<T, ClientError>,
+    operation: QueuedOperation,
+) -> Result<T, ClientError> {
+    match result {
+        Err(err) if err.is_unreachable() => {
+            let project_name = operation.project_name().to_string();
+            let queued = enqueue_at(&queue_file_path(), operation, Utc::now());
+            crate::utils::print_and_exit_on_error(queued);
+
+            println!(
+                "The API is unreachable, so the time on {} was queued 📥",
+                project_name.color(Color::Cyan)
+            );
+            println!("Run `sitt sync` to send it when you are back online");
+            exit(0);
+        }
+        result => result,
+    }
+}
+
+/// A queued start followed by a queued stop on the same project is turned into a single
+/// add, so the time tracking keeps the timestamps of when it was queued.
+fn coalesce(queue: Vec<QueuedItem>) -> Vec<QueuedItem> {
+    let mut coalesced: Vec<QueuedItem> = Vec::new();
+
+    for item in queue {
+        if let QueuedOperation::Stop { project_id, .. } = &item.operation {
+            let start_index = coalesced.iter().rposition(|queued| {
+                matches!(
+                    &queued.operation,
+                    QueuedOperation::Start { project_id: id, .. } if id == project_id
+                )
+            });
+
+            if let Some(start_index) = start_index {
+                let start = coalesced.remove(start_index);
+                if let QueuedOperation::Start {
+                    project_id,
+                    project_name,
+                } = start.operation
+                {
+                    coalesced.insert(
+                        start_index,
+                        QueuedItem {
+                            queued_at: start.queued_at,
+                            operation: QueuedOperation::Add {
+                                project_id,
+                                project_name,
+                                started_at: start.queued_at,
+                                stopped_at: item.queued_at,
+                                comment: None,
+                            },
+                        },
+                    );
+                }
+                continue;
+            }
+        }
+
+        coalesced.push(item);
+    }
+
+    coalesced
+}
+
+/// Whether a start of the project is queued without a queued stop after it
+fn has_queued_start(queue: &[QueuedItem], project_id: &str) -> bool {
+    coalesce(queue.to_vec()).iter().any(|item| {
+        matches!(
+            &item.operation,
+            QueuedOperation::Start { project_id: id, .. } if id == project_id
+        )
+    })
+}
+
+/// Queues the stop when the start of the project is still queued, as the API has no time tracking
+/// to stop yet. They are synced together as an add. Exits after queueing.
+pub fn queue_stop_if_start_is_queued(project_id: &str, project_name: &str) {
+    let queue_file_path = queue_file_path();
+    let queue = crate::utils::print_and_exit_on_error(load_queue(&queue_file_path));
+    if !has_queued_start(&queue, project_id) {
+        return;
+    }
+
+    let operation = QueuedOperation::Stop {
+        project_id: project_id.to_string(),
+        project_name: project_name.to_string(),
+    };
+    crate::utils::print_and_exit_on_error(enqueue_at(&queue_file_path, operation, Utc::now()));
+
+    println!(
+        "The start on {} is still queued, so the stop was queued too 📥",
+        project_name.color(Color::Cyan)
+    );
+    println!("Run `sitt sync` to send them");
+    exit(0);
+}
+
+/// Replays the queue in the order it was queued. Returns the result of each replayed item
+/// and the items that must stay queued: the ones that failed, starts without a queued stop and,
+/// if the API became unreachable, everything after it.
+fn replay(
+    queue: Vec<QueuedItem>,
+    client: &impl QueueClient,
+) -> (Vec<ReplayResult>, Vec<QueuedItem>) {
+    let mut results = Vec::new();
+    let mut remaining = Vec::new();
+    let mut items = coalesce(queue).into_iter();
+
+    for item in items.by_ref() {
+        let result = match &item.operation {
+            // Started now, the time tracking would lose when it was queued. It is kept until its
+            // stop is queued, and both are replayed as an add.
+            QueuedOperation::Start { .. } => {
+                remaining.push(item.clone());
+                continue;
+            }
+            QueuedOperation::Stop { project_id, .. } => client.stop(project_id, item.queued_at),
+            QueuedOperation::Add {
+                project_id,
+                started_at,
+                stopped_at,
+                comment,
+                ..
+            } => client.add(&CreateTimeTrackDto {
+                project_id: project_id.clone(),
+                started_at: *started_at,
+                stopped_at: *stopped_at,
+                comment: comment.clone(),
+            }),
+        };
+
+        let unreachable = matches!(&result, Err(err) if err.is_unreachable());
+        if result.is_err() {
+            remaining.push(item.clone());
+        }
+
+        results.push(ReplayResult {
+            project_name: item.operation.project_name().to_string(),
+            operation: item.operation.describe(),
+            result,
+        });
+
+        if unreachable {
+            break;
+        }
+    }
+
+    remaining.extend(items);
+
+    (results, remaining)
+}
+
+pub fn sync(config: &Config) {
+    let queue_file_path = queue_file_path();
+    let queue = crate::utils::print_and_exit_on_error(load_queue(&queue_file_path));
+
+    if queue.is_empty() {
+        println!("There is nothing queued to sync ✅");
+        return;
+    }
+
+    let (results, remaining) = replay(queue, &SittClient { config });
+
+    for replayed in &results {
+        match &replayed.result {
+            Ok(_) => println!(
+                "✅ {} on {}",
+                replayed.operation,
+                replayed.project_name.color(Color::Cyan)
+            ),
+            Err(err) => println!(
+                "❌ {} on {}: {}",
+                replayed.operation,
+                replayed.project_name.color(Color::Cyan),
+                err
+            ),
+        }
+    }
+
+    let (waiting, failed): (Vec<&QueuedItem>, Vec<&QueuedItem>) = remaining
+        .iter()
+        .partition(|item| matches!(item.operation, QueuedOperation::Start { .. }));
+    for item in &waiting {
+        println!(
+            "⏸️ start on {} is kept queued until it is stopped with `sitt stop`",
+            item.operation.project_name().color(Color::Cyan)
+        );
+    }
+
+    crate::utils::print_and_exit_on_error(save_queue(&queue_file_path, &remaining));
+
+    if !failed.is_empty() {
+        eprintln!(
+            "\n{} queued item(s) could not be synced and are kept in the queue",
+            failed.len()
+        );
+        exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use sitt_api::models::time_track_model::TimeTrackStatus;
+    use std::cell::RefCell;
+
+    struct MockClient {
+        calls: RefCell<Vec<String>>,
+        failing_project: Option<&'static str>,
+        unreachable_project: Option<&'static str>,
+    }
+
+    impl MockClient {
+        fn new() -> Self {
+            MockClient {
+                calls: RefCell::new(Vec::new()),
+                failing_project: None,
+                unreachable_project: None,
+            }
+        }
+
+        fn call(&self, call: String, project_id: &str) -> Result<TimeTrackDto, ClientError> {
+            self.calls.borrow_mut().push(call);
+
+            if self.failing_project == Some(project_id) {
+                return Err(ClientError::Conflict(String::from("already in progress")));
+            }
+            if self.unreachable_project == Some(project_id) {
+                return Err(ClientError::Unreachable(String::from("connection refused")));
+            }
+
+            Ok(TimeTrackDto {
+                time_track_id: String::from("time_track_id"),
+                project_id: project_id.to_string(),
+                project_name: project_id.to_string(),
+                status: TimeTrackStatus::Finished,
+                started_at: Utc::now(),
+                stopped_at: Some(Utc::now()),
+                total_duration: String::from("0s"),
+                comment: None,
+                notes: Vec::new(),
+                created_at: Utc::now(),
+                created_by: String::from("user_id"),
+            })
+        }
+    }
+
+    impl QueueClient for MockClient {
+        fn stop(
+            &self,
+            project_id: &str,
+            _stopped_at: DateTime<Utc>,
+        ) -> Result<TimeTrackDto, ClientError> {
+            self.call(format!("stop {}", project_id), project_id)
+        }
+
+        fn add(&self, dto: &CreateTimeTrackDto) -> Result<TimeTrackDto, ClientError> {
+            self.call(format!("add {}", dto.project_id), &dto.project_id)
+        }
+    }
+
+    fn at(minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 8, 1, 12, minute, 0).unwrap()
+    }
+
+    fn start(project: &str) -> QueuedOperation {
+        QueuedOperation::Start {
+            project_id: project.to_string(),
+            project_name: project.to_string(),
+        }
+    }
+
+    fn stop(project: &str) -> QueuedOperation {
+        QueuedOperation::Stop {
+            project_id: project.to_string(),
+            project_name: project.to_string(),
+        }
+    }
+
+    fn add(project: &str) -> QueuedOperation {
+        QueuedOperation::Add {
+            project_id: project.to_string(),
+            project_name: project.to_string(),
+            started_at: at(0),
+            stopped_at: at(30),
+            comment: None,
+        }
+    }
+
+    fn item(operation: QueuedOperation, minute: u32) -> QueuedItem {
+        QueuedItem {
+            queued_at: at(minute),
+            operation,
+        }
+    }
+
+    fn queue_file(file_name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(file_name);
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn enqueue_keeps_the_order_of_operations() {
+        let path = queue_file("sitt-queue-order.json");
+
+        enqueue_at(&path, start("a"), at(0)).unwrap();
+        enqueue_at(&path, add("b"), at(1)).unwrap();
+        enqueue_at(&path, stop("a"), at(2)).unwrap();
+
+        let queue = load_queue(&path).unwrap();
+        assert_eq!(
+            queue,
+            vec![item(start("a"), 0), item(add("b"), 1), item(stop("a"), 2)]
+        );
+    }
+
+    #[test]
+    fn saving_an_empty_queue_removes_the_file() {
+        let path = queue_file("sitt-queue-empty.json");
+        enqueue_at(&path, start("a"), at(0)).unwrap();
+
+        save_queue(&path, &[]).unwrap();
+
+        assert!(!path.exists(), "Expected the queue file to be removed");
+        assert!(load_queue(&path).unwrap().is_empty());
+    }
+
+    #[test]
+    fn queued_start_and_stop_are_replayed_as_an_add() {
+        let queue = vec![item(start("a"), 0), item(add("b"), 5), item(stop("a"), 10)];
+
+        let coalesced = coalesce(queue);
+
+        assert_eq!(
+            coalesced,
+            vec![
+                item(
+                    QueuedOperation::Add {
+                        project_id: String::from("a"),
+                        project_name: String::from("a"),
+                        started_at: at(0),
+                        stopped_at: at(10),
+                        comment: None,
+                    },
+                    0
+                ),
+                item(add("b"), 5),
+            ]
+        );
+    }
+
+    #[test]
+    fn replay_runs_in_queued_order_and_keeps_failed_items() {
+        let client = MockClient {
+            failing_project: Some("b"),
+            ..MockClient::new()
+        };
+        let queue = vec![item(stop("a"), 0), item(add("b"), 1), item(add("c"), 2)];
+
+        let (results, remaining) = replay(queue, &client);
+
+        assert_eq!(
+            *client.calls.borrow(),
+            vec!["stop a", "add b", "add c"],
+            "Expected the queue to be replayed in order"
+        );
+        assert_eq!(results.len(), 3);
+        assert_eq!(remaining, vec![item(add("b"), 1)]);
+    }
+
+    #[test]
+    fn lone_queued_start_is_kept_until_it_is_stopped() {
+        let client = MockClient::new();
+        let queue = vec![item(start("a"), 0), item(add("b"), 5)];
+        assert!(has_queued_start(&queue, "a"));
+        assert!(!has_queued_start(&queue, "b"));
+
+        let (results, remaining) = replay(queue, &client);
+
+        assert_eq!(
+            *client.calls.borrow(),
+            vec!["add b"],
+            "Expected the start to not be sent, as it would start now"
+        );
+        assert_eq!(results.len(), 1);
+        assert_eq!(remaining, vec![item(start("a"), 0)]);
+
+        let queue = vec![item(start("a"), 0), item(stop("a"), 10)];
+        assert!(
+            !has_queued_start(&queue, "a"),
+            "Expected a start with a queued stop to be replayed as an add"
+        );
+    }
+
+    #[test]
+    fn replay_stops_when_the_api_is_unreachable() {
+        let client = MockClient {
+            unreachable_project: Some("b"),
+            ..MockClient::new()
+        };
+        let queue = vec![item(add("a"), 0), item(add("b"), 1), item(add("c"), 2)];
+
+        let (results, remaining) = replay(queue, &client);
+
+        assert_eq!(*client.calls.borrow(), vec!["add a", "add b"]);
+        assert_eq!(results.len(), 2);
+        assert_eq!(remaining, vec![item(add("b"), 1), item(add("c"), 2)]);
+    }
+}
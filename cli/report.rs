@@ -0,0 +1,314 @@
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use colored::{Color, Colorize};
+use sitt_api::handlers::dtos::{project_dtos::TagSummaryDto, time_track_dtos::TimeTrackDto};
+use std::{process::exit, time::Duration};
+
+use crate::{config::Config, sitt_client, utils, ReportArgs};
+
+pub fn print_report(config: &Config, args: &ReportArgs) {
+    let include_running = !args.exclude_running;
+    // The totals of the projects include the running time, so without it the time tracks are summed
+    let sums_time_tracks = !include_running && !args.by_tag;
+    if args.since.is_some() || args.until.is_some() || sums_time_tracks {
+        print_windowed_report(config, args.since, args.until, include_running);
+        return;
+    }
+
+    let rows: Vec<(String, String, String)> = if args.by_tag {
+        let api_response = sitt_client::get_summary_by_tag(config, include_running);
+        let summaries = utils::print_and_exit_on_error(api_response);
+
+        println!("Time logged by tag:\n");
+        summaries.into_iter().map(tag_summary_row).collect()
+    } else {
+        let api_response = sitt_client::get_projects(config);
+        let projects = utils::print_and_exit_on_error(api_response);
+
+        println!("Time logged by project:\n");
+        projects
+            .into_iter()
+            .map(|p| (p.name, p.total_duration, String::new()))
+            .collect()
+    };
+
+    if rows.is_empty() {
+        println!("You have no projects");
+        return;
+    }
+
+    for line in format_rows(&rows) {
+        println!("{}", line);
+    }
+
+    // Projects with several tags count towards each, so the tags do not add up to a total
+    if !args.by_tag {
+        let total = utils::print_and_exit_on_error(
+            rows.iter()
+                .map(|(_, duration, _)| parse_duration(duration))
+                .sum::<Result<Duration, String>>(),
+        );
+        println!("\nTotal: {}", humantime::format_duration(total));
+    }
+}
+
+/// Sums the time logged in the window per project, as the stored totals of the projects are all-time
+fn print_windowed_report(
+    config: &Config,
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+    include_running: bool,
+) {
+    if let (Some(since), Some(until)) = (since, until) {
+        if since > until {
+            eprintln!("--since must not be after --until");
+            exit(1);
+        }
+    }
+
+    let start = since.map(|date| utils::local_date_bounds(date).0);
+    let end = until.map(|date| utils::local_date_bounds(date).1);
+    let label = window_label(since, until);
+
+    let api_response = sitt_client::get_projects(config);
+    let projects = utils::print_and_exit_on_error(api_response);
+
+    let mut total = Duration::ZERO;
+    let mut rows = Vec::new();
+    for project in projects {
+        let api_response =
+            sitt_client::get_time_trackings(config, &project.project_id, include_running);
+        let timetrack_list = utils::print_and_exit_on_error(api_response);
+
+        let project_total =
+            utils::print_and_exit_on_error(windowed_total(&timetrack_list, start, end));
+        if project_total.is_zero() {
+            continue;
+        }
+
+        total += project_total;
+        rows.push((
+            project.name,
+            humantime::format_duration(project_total).to_string(),
+            String::new(),
+        ));
+    }
+
+    if rows.is_empty() {
+        println!(
+            "You have not logged any time {}",
+            no_time_label(since, until)
+        );
+        return;
+    }
+
+    println!("Time logged by project ({}):\n", label);
+    for line in format_rows(&rows) {
+        println!("{}", line);
+    }
+    println!("\nTotal ({}): {}", label, humantime::format_duration(total));
+}
+
+// A duration the API sent that can't be parsed fails the report, rather than counting as zero
+fn parse_duration(duration: &str) -> Result<Duration, String> {
+    humantime::parse_duration(duration)
+        .map_err(|err| format!("Invalid duration '{}' from the API: {}", duration, err))
+}
+
+/// Sums the time started within the window. A missing bound leaves that side open.
+fn windowed_total(
+    timetrack_list: &[TimeTrackDto],
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+) -> Result<Duration, String> {
+    timetrack_list
+        .iter()
+        .filter(|t| start.is_none_or(|start| t.started_at >= start))
+        .filter(|t| end.is_none_or(|end| t.started_at < end))
+        .map(|t| parse_duration(&t.total_duration))
+        .sum()
+}
+
+// E.g. "Jan 1–31", "Jan 20–Feb 3" or "Dec 1, 2024–Jan 31, 2025"
+fn window_label(since: Option<NaiveDate>, until: Option<NaiveDate>) -> String {
+    match (since, until) {
+        (Some(since), Some(until)) if since == until => since.format("%b %-d").to_string(),
+        (Some(since), Some(until))
+            if since.year() == until.year() && since.month() == until.month() =>
+        {
+            format!("{}–{}", since.format("%b %-d"), until.format("%-d"))
+        }
+        (Some(since), Some(until)) if since.year() == until.year() => {
+            format!("{}–{}", since.format("%b %-d"), until.format("%b %-d"))
+        }
+        (Some(since), Some(until)) => {
+            format!(
+                "{}–{}",
+                since.format("%b %-d, %Y"),
+                until.format("%b %-d, %Y")
+            )
+        }
+        (Some(since), None) => format!("since {}", since.format("%b %-d, %Y")),
+        (None, Some(until)) => format!("until {}", until.format("%b %-d, %Y")),
+        (None, None) => String::from("all time"),
+    }
+}
+
+fn no_time_label(since: Option<NaiveDate>, until: Option<NaiveDate>) -> String {
+    match (since, until) {
+        (Some(_), Some(_)) => format!("in {}", window_label(since, until)),
+        _ => window_label(since, until),
+    }
+}
+
+fn tag_summary_row(summary: TagSummaryDto) -> (String, String, String) {
+    let projects = match summary.project_count {
+        1 => String::from("1 project"),
+        count => format!("{} projects", count),
+    };
+
+    (summary.tag, summary.total_duration, projects)
+}
+
+// Pads the name and duration columns, so the rows line up
+fn format_rows(rows: &[(String, String, String)]) -> Vec<String> {
+    let name_width = rows
+        .iter()
+        .map(|(name, _, _)| name.chars().count())
+        .max()
+        .unwrap_or(0);
+    let duration_width = rows
+        .iter()
+        .map(|(_, duration, _)| duration.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    rows.iter()
+        .map(|(name, duration, note)| {
+            let line = format!(
+                "{}  {:>duration_width$}  {}",
+                format!("{:<name_width$}", name).color(Color::Cyan),
+                duration,
+                note,
+            );
+            line.trim_end().to_string()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use sitt_api::models::time_track_model::TimeTrackStatus;
+
+    fn time_track_dto(started_at: DateTime<Utc>, total_duration: &str) -> TimeTrackDto {
+        TimeTrackDto {
+            time_track_id: started_at.to_rfc3339(),
+            project_id: String::from("project"),
+            project_name: String::from("Project"),
+            status: TimeTrackStatus::Finished,
+            started_at,
+            stopped_at: Some(started_at),
+            total_duration: total_duration.to_string(),
+            comment: None,
+            notes: Vec::new(),
+            created_at: started_at,
+            created_by: String::from("user_id"),
+        }
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn windowed_total_only_counts_time_in_the_window() {
+        let timetrack_list = [
+            time_track_dto(Utc.with_ymd_and_hms(2024, 12, 31, 9, 0, 0).unwrap(), "5h"),
+            time_track_dto(Utc.with_ymd_and_hms(2025, 1, 1, 9, 0, 0).unwrap(), "2h"),
+            time_track_dto(
+                Utc.with_ymd_and_hms(2025, 1, 31, 9, 0, 0).unwrap(),
+                "1h 30m",
+            ),
+            time_track_dto(Utc.with_ymd_and_hms(2025, 2, 1, 0, 0, 0).unwrap(), "4h"),
+        ];
+        let start = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        let end = Utc.with_ymd_and_hms(2025, 2, 1, 0, 0, 0).unwrap();
+
+        let all_time = windowed_total(&timetrack_list, None, None).unwrap();
+        let january = windowed_total(&timetrack_list, Some(start), Some(end)).unwrap();
+
+        assert_eq!(all_time, Duration::from_secs(12 * 3600 + 30 * 60));
+        assert_eq!(january, Duration::from_secs(3 * 3600 + 30 * 60));
+        assert_ne!(january, all_time);
+        assert_eq!(
+            windowed_total(&timetrack_list, Some(start), None).unwrap(),
+            Duration::from_secs(7 * 3600 + 30 * 60)
+        );
+    }
+
+    #[test]
+    fn windowed_total_fails_on_an_invalid_duration() {
+        let started_at = Utc.with_ymd_and_hms(2025, 1, 1, 9, 0, 0).unwrap();
+        let timetrack_list = [
+            time_track_dto(started_at, "2h"),
+            time_track_dto(started_at, "two hours"),
+        ];
+
+        let err = windowed_total(&timetrack_list, None, None).unwrap_err();
+
+        assert!(err.contains("two hours"), "Unexpected error: {}", err);
+    }
+
+    #[test]
+    fn window_label_names_the_window() {
+        assert_eq!(
+            window_label(Some(date(2025, 1, 1)), Some(date(2025, 1, 31))),
+            "Jan 1–31"
+        );
+        assert_eq!(
+            window_label(Some(date(2025, 1, 20)), Some(date(2025, 2, 3))),
+            "Jan 20–Feb 3"
+        );
+        assert_eq!(
+            window_label(Some(date(2024, 12, 1)), Some(date(2025, 1, 31))),
+            "Dec 1, 2024–Jan 31, 2025"
+        );
+        assert_eq!(
+            window_label(Some(date(2025, 1, 1)), None),
+            "since Jan 1, 2025"
+        );
+        assert_eq!(
+            window_label(None, Some(date(2025, 1, 31))),
+            "until Jan 31, 2025"
+        );
+    }
+
+    #[test]
+    fn tag_summary_rows_line_up() {
+        colored::control::set_override(false);
+        let rows: Vec<(String, String, String)> = [
+            ("billable", "3h 30m", 2),
+            ("internal", "1h", 1),
+            ("(untagged)", "45m", 3),
+        ]
+        .into_iter()
+        .map(|(tag, total_duration, project_count)| {
+            tag_summary_row(TagSummaryDto {
+                tag: tag.to_string(),
+                total_duration: total_duration.to_string(),
+                project_count,
+            })
+        })
+        .collect();
+
+        assert_eq!(
+            format_rows(&rows),
+            vec![
+                "billable    3h 30m  2 projects",
+                "internal        1h  1 project",
+                "(untagged)     45m  3 projects",
+            ]
+        );
+    }
+}
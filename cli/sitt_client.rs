@@ -1,16 +1,25 @@
-use crate::{config::Config, utils::get_spinner};
+use crate::{
+    config::Config,
+    utils::{get_spinner, ExitCode},
+};
+use indicatif::ProgressBar;
 use reqwest::{
     self,
-    blocking::Client,
-    header::{HeaderMap, HeaderValue},
+    blocking::{Client, Response},
+    header::{HeaderMap, HeaderValue, RETRY_AFTER},
+    StatusCode,
 };
+use sitt_api::fairings::version_warning::{CLIENT_VERSION_HEADER, VERSION_WARNING_HEADER};
 use sitt_api::handlers::dtos::{
     common_dtos::ErrorResponse,
-    project_dtos::{CreateProjectDto, ProjectDto},
-    time_track_dtos::{CreateTimeTrackDto, TimeTrackDto},
-    user_dtos::{CreateUserDto, UserDto},
+    project_dtos::{CreateProjectDto, ProjectDto, TagSummaryDto},
+    time_track_dtos::{
+        AppendNoteDto, CreateTimeTrackDto, DeletedTimeTrackDto, RecentTimeTracksDto, TimeTrackDto,
+    },
+    user_dtos::{ClaimAdminDto, ClaimedAdminDto, CreateUserDto, CreatedUsersDto, UserDto},
 };
-use std::time::Duration;
+use sitt_api::infrastructure::user_repository::DEFAULT_ADMIN_API_KEY;
+use std::{cell::RefCell, thread, time::Duration};
 use thiserror::Error;
 use url::Url;
 
@@ -20,6 +29,8 @@ pub enum ClientError {
     BadRequest(String),
     #[error("Unauthorized request")]
     Unauthorized,
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
     #[error("Not found: {0}")]
     NotFound(String),
     #[error("Conflict: {0}")]
@@ -32,28 +43,95 @@ pub enum ClientError {
     RequestFailed(String),
     #[error("Failed to parse response body: {0}")]
     ParseResponseBodyFailed(String),
+    #[error("The API is unreachable: {0}")]
+    Unreachable(String),
     #[error(transparent)]
-    ReqwestError(#[from] reqwest::Error),
+    ReqwestError(reqwest::Error),
+}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(err: reqwest::Error) -> Self {
+        // Kept apart from other request errors, so the request can be retried later
+        if err.is_connect() || err.is_timeout() {
+            return ClientError::Unreachable(err.to_string());
+        }
+        ClientError::ReqwestError(err)
+    }
+}
+
+// Exit codes of failed commands, so scripts can branch on the kind of failure
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_UNAUTHORIZED: i32 = 3;
+pub const EXIT_CONFLICT: i32 = 4;
+pub const EXIT_NETWORK: i32 = 5;
+// Not 2, which clap exits with on bad usage of a command
+pub const EXIT_NOT_FOUND: i32 = 6;
+
+impl ClientError {
+    pub fn is_unreachable(&self) -> bool {
+        matches!(self, ClientError::Unreachable(_))
+    }
+}
+
+impl ExitCode for ClientError {
+    fn exit_code(&self) -> i32 {
+        match self {
+            ClientError::NotFound(_) => EXIT_NOT_FOUND,
+            ClientError::Unauthorized | ClientError::Forbidden(_) => EXIT_UNAUTHORIZED,
+            ClientError::Conflict(_) => EXIT_CONFLICT,
+            ClientError::Unreachable(_) | ClientError::ReqwestError(_) => EXIT_NETWORK,
+            ClientError::BadRequest(_)
+            | ClientError::InternalServerError(_)
+            | ClientError::BuildRequest(_)
+            | ClientError::RequestFailed(_)
+            | ClientError::ParseResponseBodyFailed(_) => EXIT_FAILURE,
+        }
+    }
 }
 
 const PROJECTS_PATH: &str = "/api/v1/projects";
 const TIME_TRACKS_PATH: &str = "/api/v1/timetrack";
 const USERS_PATH: &str = "/api/v1/users";
+const HEALTH_PATH: &str = "/api/v1/health";
+
+// Used when a 503 does not say when to retry
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(1);
+// So a `Retry-After: 0` still spends the budget, instead of retrying forever
+const MIN_RETRY_AFTER: Duration = Duration::from_millis(100);
 
 struct ApiClient {
     client: Client,
     base_url: Url,
+    retry_budget: Duration,
+    // Warnings of the API, printed to stderr when the client is dropped unless taken before
+    warnings: RefCell<Vec<String>>,
+}
+
+impl Drop for ApiClient {
+    fn drop(&mut self) {
+        for warning in self.warnings.get_mut().drain(..) {
+            eprintln!("Warning: {}", warning);
+        }
+    }
 }
 
 impl ApiClient {
     pub fn build(config: &Config) -> Result<Self, ClientError> {
-        // Get API key
-        let api_key = HeaderValue::from_str(config.get_api_key())
+        Self::build_with_api_key(config, config.get_api_key())
+    }
+
+    // For requests made as another user than the configured one, e.g. the default admin
+    pub fn build_with_api_key(config: &Config, api_key: &str) -> Result<Self, ClientError> {
+        let api_key = HeaderValue::from_str(api_key)
             .map_err(|err| ClientError::BuildRequest(err.to_string()))?;
 
-        // Create header with API key
+        // Create header with API key, and the version of sitt, so the API can warn when it is outdated
         let mut headers = HeaderMap::new();
         headers.insert("x-api-key", api_key);
+        headers.insert(
+            CLIENT_VERSION_HEADER,
+            HeaderValue::from_static(env!("CARGO_PKG_VERSION")),
+        );
 
         let client = reqwest::blocking::ClientBuilder::new()
             .default_headers(headers)
@@ -64,7 +142,27 @@ impl ApiClient {
         let base_url = Url::parse(config.get_url())
             .map_err(|_| ClientError::BuildRequest("Failed to create base_url".to_string()))?;
 
-        Ok(Self { client, base_url })
+        Ok(Self {
+            client,
+            base_url,
+            retry_budget: config.startup_retry_budget(),
+            warnings: RefCell::new(Vec::new()),
+        })
+    }
+
+    /// Takes the warnings of the API so far, so they can be output instead of printed to stderr
+    fn take_warnings(&self) -> Vec<String> {
+        self.warnings.take()
+    }
+
+    fn record_warnings(&self, response: &Response) {
+        let warnings = response
+            .headers()
+            .get_all(VERSION_WARNING_HEADER)
+            .iter()
+            .filter_map(|warning| warning.to_str().ok())
+            .map(String::from);
+        self.warnings.borrow_mut().extend(warnings);
     }
 
     pub fn build_url(&self, path: &str) -> Url {
@@ -73,10 +171,36 @@ impl ApiClient {
         url
     }
 
+    /// Sends a GET request, waiting as long as `Retry-After` asks and retrying while the API answers
+    /// 503 within the retry budget. Writes are never retried, as they could be applied twice.
+    fn get(&self, url: Url, spinner: &ProgressBar) -> Result<Response, ClientError> {
+        let mut waited = Duration::ZERO;
+
+        loop {
+            let response = self.client.get(url.clone()).send()?;
+            if response.status() != StatusCode::SERVICE_UNAVAILABLE {
+                return Ok(response);
+            }
+
+            let retry_after = retry_after(&response)
+                .unwrap_or(DEFAULT_RETRY_AFTER)
+                .max(MIN_RETRY_AFTER);
+            if waited + retry_after > self.retry_budget {
+                return Ok(response);
+            }
+
+            spinner.set_message("Server starting, retrying...");
+            thread::sleep(retry_after);
+            waited += retry_after;
+        }
+    }
+
     fn handle_response<T: serde::de::DeserializeOwned>(
         &self,
         response: reqwest::blocking::Response,
     ) -> Result<T, ClientError> {
+        self.record_warnings(&response);
+
         match response.status() {
             reqwest::StatusCode::OK
             | reqwest::StatusCode::CREATED
@@ -98,6 +222,12 @@ impl ApiClient {
                 Err(ClientError::BadRequest(error_response.error_message))
             }
             reqwest::StatusCode::UNAUTHORIZED => Err(ClientError::Unauthorized),
+            // The API explains rejections of allowed users, e.g. when ADMIN creation is disabled.
+            // Guards reject other users without a body, which is treated as unauthorized.
+            reqwest::StatusCode::FORBIDDEN => match response.json::<ErrorResponse>() {
+                Ok(error_response) => Err(ClientError::Forbidden(error_response.error_message)),
+                Err(_) => Err(ClientError::Unauthorized),
+            },
             reqwest::StatusCode::NOT_FOUND => {
                 let error_response: ErrorResponse = response
                     .json()
@@ -133,12 +263,71 @@ impl ApiClient {
     }
 }
 
+// Only the delay in seconds is supported, the API does not send HTTP dates
+fn retry_after(response: &Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+/// Succeeds if the API answers at all, regardless of the status code
+pub fn ping(config: &Config) -> Result<(), ClientError> {
+    let api = ApiClient::build(config)?;
+    let url = api.build_url("/");
+
+    let spinner = get_spinner(String::from("Reaching the API..."));
+    let result = api.client.get(url).send();
+    spinner.finish_and_clear();
+
+    result?;
+
+    Ok(())
+}
+
+/// Succeeds if an API answers on the URL, before a configuration with it exists
+pub fn probe_url(sitt_url: &str) -> Result<(), ClientError> {
+    let mut url = Url::parse(sitt_url)
+        .map_err(|_| ClientError::BuildRequest("Failed to create base_url".to_string()))?;
+    url.set_path(HEALTH_PATH);
+
+    let client = reqwest::blocking::ClientBuilder::new()
+        .connect_timeout(Duration::from_secs(5))
+        .build()
+        .map_err(|err| ClientError::BuildRequest(err.to_string()))?;
+
+    let spinner = get_spinner(String::from("Reaching the API..."));
+    let result = client.head(url).send();
+    spinner.finish_and_clear();
+
+    result?;
+
+    Ok(())
+}
+
 pub fn validate_user_config(config: &Config) -> Result<(), ClientError> {
     let api = ApiClient::build(config)?;
+    authenticate(&api)
+}
+
+/// Validates the configuration, returning the warnings of the API, e.g. that sitt is outdated
+pub fn validate_user_config_with_warnings(config: &Config) -> Result<Vec<String>, ClientError> {
+    let api = ApiClient::build(config)?;
+    authenticate(&api)?;
+
+    Ok(api.take_warnings())
+}
+
+fn authenticate(api: &ApiClient) -> Result<(), ClientError> {
     let url = api.build_url(PROJECTS_PATH);
 
     let spinner = get_spinner(String::from("Authenticating user..."));
-    let response = api.client.get(url).send()?;
+    let response = api.get(url, &spinner)?;
     spinner.finish_and_clear();
 
     api.handle_response::<Vec<ProjectDto>>(response)?;
@@ -174,7 +363,7 @@ pub fn get_project_by_id(config: &Config, project_id: &str) -> Result<ProjectDto
     let url = api.build_url(&format!("{}/{}", PROJECTS_PATH, project_id));
 
     let spinner = get_spinner(String::from("Fetching project..."));
-    let response = api.client.get(url).send()?;
+    let response = api.get(url, &spinner)?;
     spinner.finish_and_clear();
 
     let project = api.handle_response::<ProjectDto>(response)?;
@@ -183,11 +372,51 @@ pub fn get_project_by_id(config: &Config, project_id: &str) -> Result<ProjectDto
 }
 
 pub fn get_projects(config: &Config) -> Result<Vec<ProjectDto>, ClientError> {
+    get_projects_with_tag(config, None)
+}
+
+pub fn get_projects_with_tag(
+    config: &Config,
+    tag: Option<&str>,
+) -> Result<Vec<ProjectDto>, ClientError> {
+    get_filtered_projects(config, tag, None, None)
+}
+
+/// Gets the projects of another user, which only ADMINs are allowed to
+pub fn get_projects_created_by(
+    config: &Config,
+    user_id: &str,
+    tag: Option<&str>,
+) -> Result<Vec<ProjectDto>, ClientError> {
+    get_filtered_projects(config, tag, None, Some(user_id))
+}
+
+/// Gets the projects whose name contains the query, searched by the server
+pub fn search_projects(config: &Config, query: &str) -> Result<Vec<ProjectDto>, ClientError> {
+    get_filtered_projects(config, None, Some(query), None)
+}
+
+fn get_filtered_projects(
+    config: &Config,
+    tag: Option<&str>,
+    search: Option<&str>,
+    created_by: Option<&str>,
+) -> Result<Vec<ProjectDto>, ClientError> {
     let api = ApiClient::build(config)?;
-    let url = api.build_url(PROJECTS_PATH);
+    let mut url = api.build_url(PROJECTS_PATH);
+
+    if let Some(tag) = tag {
+        url.query_pairs_mut().append_pair("tag", tag);
+    }
+    if let Some(search) = search {
+        url.query_pairs_mut().append_pair("search", search);
+    }
+    if let Some(created_by) = created_by {
+        url.query_pairs_mut().append_pair("created_by", created_by);
+    }
 
     let spinner = get_spinner(String::from("Fetching projects..."));
-    let response = api.client.get(url).send()?;
+    let response = api.get(url, &spinner)?;
     spinner.finish_and_clear();
 
     let projects = api.handle_response::<Vec<ProjectDto>>(response)?;
@@ -195,13 +424,34 @@ pub fn get_projects(config: &Config) -> Result<Vec<ProjectDto>, ClientError> {
     Ok(projects)
 }
 
+pub fn get_summary_by_tag(
+    config: &Config,
+    include_running: bool,
+) -> Result<Vec<TagSummaryDto>, ClientError> {
+    let api = ApiClient::build(config)?;
+    let mut url = api.build_url(&format!("{}/summary/by-tag", PROJECTS_PATH));
+    exclude_running(&mut url, include_running);
+
+    let spinner = get_spinner(String::from("Summing time by tag..."));
+    let response = api.get(url, &spinner)?;
+    spinner.finish_and_clear();
+
+    let summaries = api.handle_response::<Vec<TagSummaryDto>>(response)?;
+
+    Ok(summaries)
+}
+
 pub fn update_project(
     config: &Config,
-    project_id: &str,
+    project_name: &str,
     update_project_dto: &CreateProjectDto,
 ) -> Result<ProjectDto, ClientError> {
     let api = ApiClient::build(config)?;
-    let url = api.build_url(&format!("{}/{}", PROJECTS_PATH, project_id));
+    let mut url = api.build_url(&format!("{}/by-name", PROJECTS_PATH));
+    // Push the name as a path segment, so it is percent-encoded
+    if let Ok(mut segments) = url.path_segments_mut() {
+        segments.push(project_name);
+    }
 
     let spinner = get_spinner(String::from("Updating project..."));
     let response = api
@@ -261,6 +511,19 @@ pub fn stop_time_tracking(config: &Config, project_id: &str) -> Result<TimeTrack
     Ok(timetrack)
 }
 
+pub fn send_heartbeat(config: &Config, project_id: &str) -> Result<(), ClientError> {
+    let api = ApiClient::build(config)?;
+    let path = &format!("{}/{}/heartbeat", TIME_TRACKS_PATH, project_id);
+    let url = api.build_url(path);
+
+    // No spinner, as heartbeats are sent in the background of `start --watch`
+    let response = api.client.post(url).send()?;
+
+    api.handle_response::<()>(response)?;
+
+    Ok(())
+}
+
 pub fn add_time_tracking(
     config: &Config,
     create_time_track_dto: &CreateTimeTrackDto,
@@ -277,15 +540,19 @@ pub fn add_time_tracking(
     Ok(timetrack)
 }
 
+/// Gets the time tracks of the project. Without `include_running`, the running time track counts
+/// as no time.
 pub fn get_time_trackings(
     config: &Config,
     project_id: &str,
+    include_running: bool,
 ) -> Result<Vec<TimeTrackDto>, ClientError> {
     let api = ApiClient::build(config)?;
-    let url = api.build_url(&format!("{}/{}", TIME_TRACKS_PATH, project_id));
+    let mut url = api.build_url(&format!("{}/{}", TIME_TRACKS_PATH, project_id));
+    exclude_running(&mut url, include_running);
 
     let spinner = get_spinner(String::from("Fetching logged time on project..."));
-    let response = api.client.get(url).send()?;
+    let response = api.get(url, &spinner)?;
     spinner.finish_and_clear();
 
     let timetrack_list = api.handle_response::<Vec<TimeTrackDto>>(response)?;
@@ -293,6 +560,57 @@ pub fn get_time_trackings(
     Ok(timetrack_list)
 }
 
+// The API includes the running time by default, so only leaving it out needs to be asked for
+fn exclude_running(url: &mut Url, include_running: bool) {
+    if !include_running {
+        url.query_pairs_mut()
+            .append_pair("include_running", "false");
+    }
+}
+
+pub fn get_recent_time_trackings(
+    config: &Config,
+    limit: usize,
+    cursor: Option<&str>,
+) -> Result<RecentTimeTracksDto, ClientError> {
+    let api = ApiClient::build(config)?;
+    fetch_recent_time_trackings(&api, limit, cursor)
+}
+
+/// Like `get_recent_time_trackings`, but returns the warnings of the API instead of printing them
+pub fn get_recent_time_trackings_with_warnings(
+    config: &Config,
+    limit: usize,
+    cursor: Option<&str>,
+) -> Result<(RecentTimeTracksDto, Vec<String>), ClientError> {
+    let api = ApiClient::build(config)?;
+    let recent = fetch_recent_time_trackings(&api, limit, cursor)?;
+
+    Ok((recent, api.take_warnings()))
+}
+
+fn fetch_recent_time_trackings(
+    api: &ApiClient,
+    limit: usize,
+    cursor: Option<&str>,
+) -> Result<RecentTimeTracksDto, ClientError> {
+    let mut url = api.build_url(&format!("{}/recent", TIME_TRACKS_PATH));
+
+    url.query_pairs_mut()
+        .append_pair("limit", &limit.to_string());
+    if let Some(cursor) = cursor {
+        url.query_pairs_mut().append_pair("cursor", cursor);
+    }
+
+    let spinner = get_spinner(String::from("Fetching recently logged time..."));
+    let response = api.get(url, &spinner)?;
+    spinner.finish_and_clear();
+
+    let recent = api.handle_response::<RecentTimeTracksDto>(response)?;
+
+    Ok(recent)
+}
+
 pub fn update_time_track(
     config: &Config,
     time_track_id: &str,
@@ -310,24 +628,46 @@ pub fn update_time_track(
     Ok(timetrack)
 }
 
-pub fn delete_time_track(
+pub fn append_note(
     config: &Config,
     project_id: &str,
     time_track_id: &str,
-) -> Result<(), ClientError> {
+    append_note_dto: &AppendNoteDto,
+) -> Result<TimeTrackDto, ClientError> {
     let api = ApiClient::build(config)?;
     let url = api.build_url(&format!(
+        "{}/{}/{}/notes",
+        TIME_TRACKS_PATH, project_id, time_track_id
+    ));
+
+    let spinner = get_spinner(String::from("Adding note..."));
+    let response = api.client.post(url).json(append_note_dto).send()?;
+    spinner.finish_and_clear();
+
+    let timetrack = api.handle_response::<TimeTrackDto>(response)?;
+
+    Ok(timetrack)
+}
+
+pub fn delete_time_track(
+    config: &Config,
+    project_id: &str,
+    time_track_id: &str,
+) -> Result<DeletedTimeTrackDto, ClientError> {
+    let api = ApiClient::build(config)?;
+    let mut url = api.build_url(&format!(
         "{}/{}/{}",
         TIME_TRACKS_PATH, project_id, time_track_id
     ));
+    url.query_pairs_mut().append_pair("include_totals", "true");
 
     let spinner = get_spinner(String::from("Deleting..."));
     let response = api.client.delete(url).send()?;
     spinner.finish_and_clear();
 
-    api.handle_response::<()>(response)?;
+    let deleted = api.handle_response::<DeletedTimeTrackDto>(response)?;
 
-    Ok(())
+    Ok(deleted)
 }
 
 // ##################################### USER #####################################
@@ -348,6 +688,39 @@ pub fn create_user(
     Ok(user)
 }
 
+pub fn create_users(
+    config: &Config,
+    create_user_dtos: &[CreateUserDto],
+) -> Result<CreatedUsersDto, ClientError> {
+    let api = ApiClient::build(config)?;
+    let url = api.build_url(&format!("{}/bulk", USERS_PATH));
+
+    let spinner = get_spinner(format!("Creating {} users...", create_user_dtos.len()));
+    let response = api.client.post(url).json(create_user_dtos).send()?;
+    spinner.finish_and_clear();
+
+    let created_users = api.handle_response::<CreatedUsersDto>(response)?;
+
+    Ok(created_users)
+}
+
+/// Replaces the default admin with a personal admin, authenticated with the default admin's key
+pub fn claim_default_admin(
+    config: &Config,
+    claim_admin_dto: &ClaimAdminDto,
+) -> Result<ClaimedAdminDto, ClientError> {
+    let api = ApiClient::build_with_api_key(config, DEFAULT_ADMIN_API_KEY)?;
+    let url = api.build_url(&format!("{}/claim", USERS_PATH));
+
+    let spinner = get_spinner(String::from("Claiming the default admin..."));
+    let response = api.client.post(url).json(claim_admin_dto).send()?;
+    spinner.finish_and_clear();
+
+    let claimed = api.handle_response::<ClaimedAdminDto>(response)?;
+
+    Ok(claimed)
+}
+
 pub fn get_user(
     config: &Config,
     user_id: &str,
@@ -361,7 +734,7 @@ pub fn get_user(
     }
 
     let spinner = get_spinner(String::from("Fetching user..."));
-    let response = api.client.get(url).send()?;
+    let response = api.get(url, &spinner)?;
     spinner.finish_and_clear();
 
     let user = api.handle_response::<UserDto>(response)?;
@@ -369,12 +742,29 @@ pub fn get_user(
     Ok(user)
 }
 
+pub fn get_users_by_name(config: &Config, name: &str) -> Result<Vec<UserDto>, ClientError> {
+    let api = ApiClient::build(config)?;
+    let mut url = api.build_url(&format!("{}/by-name", USERS_PATH));
+    // Push the name as a path segment, so it is percent-encoded
+    if let Ok(mut segments) = url.path_segments_mut() {
+        segments.push(name);
+    }
+
+    let spinner = get_spinner(String::from("Fetching users..."));
+    let response = api.get(url, &spinner)?;
+    spinner.finish_and_clear();
+
+    let users = api.handle_response::<Vec<UserDto>>(response)?;
+
+    Ok(users)
+}
+
 pub fn get_users(config: &Config) -> Result<Vec<UserDto>, ClientError> {
     let api = ApiClient::build(config)?;
     let url = api.build_url(USERS_PATH);
 
     let spinner = get_spinner(String::from("Fetching users..."));
-    let response = api.client.get(url).send()?;
+    let response = api.get(url, &spinner)?;
     spinner.finish_and_clear();
 
     let user = api.handle_response::<Vec<UserDto>>(response)?;
@@ -394,3 +784,146 @@ pub fn delete_user(config: &Config, user_id: &str) -> Result<(), ClientError> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+    };
+
+    // Answers the requests with the responses in order, and returns how many were answered
+    fn mock_api(responses: Vec<&'static str>) -> (Url, thread::JoinHandle<usize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+
+        let handle = thread::spawn(move || {
+            let mut answered = 0;
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+                    line.clear();
+                }
+                stream.write_all(response.as_bytes()).unwrap();
+                answered += 1;
+            }
+            answered
+        });
+
+        (url, handle)
+    }
+
+    fn api_client(base_url: Url, retry_budget: Duration) -> ApiClient {
+        ApiClient {
+            client: Client::new(),
+            base_url,
+            retry_budget,
+            warnings: RefCell::new(Vec::new()),
+        }
+    }
+
+    const STARTING: &str = "HTTP/1.1 503 Service Unavailable\r\nRetry-After: 0\r\nConnection: close\r\nContent-Length: 0\r\n\r\n";
+    const PROJECTS: &str =
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nConnection: close\r\nContent-Length: 2\r\n\r\n[]";
+
+    #[test]
+    fn warnings_of_the_api_are_taken_from_the_client() {
+        let (url, handle) = mock_api(vec![
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nx-sitt-version-warning: sitt 0.1.0 is older than the API (0.2.0)\r\nConnection: close\r\nContent-Length: 2\r\n\r\n[]",
+        ]);
+        let api = api_client(url, Duration::ZERO);
+
+        let response = api.client.get(api.build_url(PROJECTS_PATH)).send().unwrap();
+        let projects: Vec<ProjectDto> = api.handle_response(response).unwrap();
+
+        assert!(projects.is_empty());
+        assert_eq!(
+            api.take_warnings(),
+            vec!["sitt 0.1.0 is older than the API (0.2.0)"]
+        );
+        assert!(
+            api.take_warnings().is_empty(),
+            "Expected the warnings to be taken"
+        );
+        assert_eq!(handle.join().unwrap(), 1);
+    }
+
+    #[test]
+    fn probe_url_succeeds_when_an_api_answers() {
+        let (url, handle) = mock_api(vec![PROJECTS]);
+
+        assert!(probe_url(url.as_str()).is_ok());
+        assert_eq!(handle.join().unwrap(), 1);
+    }
+
+    #[test]
+    fn probe_url_fails_when_nothing_listens() {
+        // The port is free again once the listener is dropped
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let result = probe_url(&url);
+
+        assert!(
+            matches!(result, Err(ClientError::Unreachable(_))),
+            "Expected the URL to be unreachable, got: {:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn get_retries_while_the_api_is_starting() {
+        let (url, handle) = mock_api(vec![STARTING, PROJECTS]);
+        let api = api_client(url, Duration::from_secs(1));
+
+        let response = api
+            .get(api.build_url(PROJECTS_PATH), &ProgressBar::hidden())
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let projects: Vec<ProjectDto> = api.handle_response(response).unwrap();
+        assert!(projects.is_empty());
+        assert_eq!(handle.join().unwrap(), 2);
+    }
+
+    #[test]
+    fn get_stops_retrying_when_the_budget_is_spent() {
+        let (url, handle) = mock_api(vec![STARTING]);
+        let api = api_client(url, Duration::ZERO);
+
+        let response = api
+            .get(api.build_url(PROJECTS_PATH), &ProgressBar::hidden())
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(handle.join().unwrap(), 1);
+    }
+
+    #[test]
+    fn exit_code_per_client_error() {
+        let message = || String::from("message");
+        let cases = [
+            (ClientError::BadRequest(message()), EXIT_FAILURE),
+            (ClientError::Unauthorized, EXIT_UNAUTHORIZED),
+            (ClientError::Forbidden(message()), EXIT_UNAUTHORIZED),
+            (ClientError::NotFound(message()), EXIT_NOT_FOUND),
+            (ClientError::Conflict(message()), EXIT_CONFLICT),
+            (ClientError::InternalServerError(message()), EXIT_FAILURE),
+            (ClientError::BuildRequest(message()), EXIT_FAILURE),
+            (ClientError::RequestFailed(message()), EXIT_FAILURE),
+            (
+                ClientError::ParseResponseBodyFailed(message()),
+                EXIT_FAILURE,
+            ),
+            (ClientError::Unreachable(message()), EXIT_NETWORK),
+        ];
+
+        for (err, exit_code) in cases {
+            assert_eq!(err.exit_code(), exit_code, "Wrong exit code for: {}", err);
+        }
+    }
+}
@@ -2,22 +2,31 @@ use std::{process::exit, time::Duration};
 
 use chrono::{DateTime, Local, Utc};
 use colored::{Color, Colorize};
-use inquire::{Confirm, Select};
+use console::{measure_text_width, truncate_str, Term};
+use inquire::{Confirm, Select, Text};
 use sitt_api::{
-    handlers::dtos::time_track_dtos::{CreateTimeTrackDto, TimeTrackDto},
-    models::time_track_model::TimeTrackStatus,
+    handlers::dtos::{
+        project_dtos::{validate_tags, CreateProjectDto, ProjectDto, MAX_TAGS, MAX_TAG_LENGTH},
+        time_track_dtos::{AppendNoteDto, CreateTimeTrackDto, RecentTimeTracksDto, TimeTrackDto},
+    },
+    models::time_track_model::{TimeTrackNote, TimeTrackStatus},
 };
 
 use crate::{
     config::Config,
-    project::{get_project_id_by_name, resolve_project_name, ProjectSelectOption},
-    sitt_client,
-    utils::{self, print_and_exit_on_error, DATETIME_FORMAT},
-    NameArg,
+    project::{
+        get_or_create_project_id, get_project_id_by_name, resolve_project_name, ProjectSelectOption,
+    },
+    queue::{queue_if_unreachable, queue_stop_if_start_is_queued, QueuedOperation},
+    sitt_client::{self, ClientError},
+    utils::{self, humanize_relative, print_and_exit_on_error, DATETIME_FORMAT},
+    watermark, AddTimeArgs, ExportTimeArgs, ListTimeArgs, NameArg, NoteArgs, RecentArgs, StartArgs,
 };
 
 use std::fmt;
 
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(60);
+
 struct CliTimeTrack {
     pub id: String,
     pub project_id: String,
@@ -25,6 +34,7 @@ struct CliTimeTrack {
     pub started_at: DateTime<Utc>,
     pub stopped_at: Option<DateTime<Utc>>,
     pub total_duration: String,
+    pub comment: Option<String>,
 }
 
 impl fmt::Display for CliTimeTrack {
@@ -52,6 +62,25 @@ impl fmt::Display for CliTimeTrack {
     }
 }
 
+impl CliTimeTrack {
+    fn to_relative_string(&self) -> String {
+        if let Some(stopped_at) = self.stopped_at {
+            format!(
+                "{} -> {} | {}",
+                humanize_relative(self.started_at),
+                humanize_relative(stopped_at),
+                self.total_duration
+            )
+        } else {
+            format!(
+                "{} -> IN PROGRESS | {} ⏱️ ",
+                humanize_relative(self.started_at),
+                self.total_duration
+            )
+        }
+    }
+}
+
 impl From<TimeTrackDto> for CliTimeTrack {
     fn from(dto: TimeTrackDto) -> Self {
         CliTimeTrack {
@@ -61,11 +90,12 @@ impl From<TimeTrackDto> for CliTimeTrack {
             started_at: dto.started_at,
             stopped_at: dto.stopped_at,
             total_duration: dto.total_duration,
+            comment: dto.comment,
         }
     }
 }
 
-pub fn start_time_tracking(config: &Config, project_args: &NameArg) {
+pub fn start_time_tracking(config: &Config, project_args: &StartArgs) {
     let name = resolve_project_name(
         project_args.name.clone(),
         config,
@@ -75,10 +105,125 @@ pub fn start_time_tracking(config: &Config, project_args: &NameArg) {
     let project_id_result = get_project_id_by_name(config, &name);
     let project_id = print_and_exit_on_error(project_id_result);
 
-    let api_response = sitt_client::start_time_tracking(config, &project_id);
-    let timetrack = utils::print_and_exit_on_error(api_response);
+    // Checked before starting, so invalid tags don't leave a time tracking half set up
+    let tags = if project_args.tags.is_empty() {
+        None
+    } else {
+        let project = print_and_exit_on_error(sitt_client::get_project_by_id(config, &project_id));
+        let tags = with_added_tags(project.tags, &project_args.tags);
+        if validate_tags(&tags).is_err() {
+            eprintln!(
+                "Error: A project can have at most {} tags of 1 to {} characters",
+                MAX_TAGS, MAX_TAG_LENGTH
+            );
+            exit(1);
+        }
+        Some(tags)
+    };
 
-    print_time_track_full(&timetrack)
+    let has_context = tags.is_some() || project_args.note.is_some();
+    let api_response = start_with_context(
+        &name,
+        tags,
+        project_args.note.clone(),
+        || {
+            let result = sitt_client::start_time_tracking(config, &project_id);
+            // Only the start itself is queued, as the queue exits once it has queued it
+            if has_context && matches!(&result, Err(err) if err.is_unreachable()) {
+                eprintln!(
+                    "{}",
+                    "Warning: The tags and note are not queued, add them again when you are back online"
+                        .color(Color::Yellow)
+                );
+            }
+            queue_if_unreachable(
+                result,
+                QueuedOperation::Start {
+                    project_id: project_id.clone(),
+                    project_name: name.clone(),
+                },
+            )
+        },
+        |update_project_dto| sitt_client::update_project(config, &name, update_project_dto),
+        |project_id, time_track_id, append_note_dto| {
+            sitt_client::append_note(config, project_id, time_track_id, append_note_dto)
+        },
+    );
+    let (timetrack, warnings) = utils::print_and_exit_on_error(api_response);
+
+    print_time_track_full(&timetrack);
+    print_notes(&timetrack.notes);
+    for warning in warnings {
+        eprintln!("{}", format!("Warning: {}", warning).color(Color::Yellow));
+    }
+
+    if project_args.watch {
+        watch_time_tracking(config, &project_id, &name);
+    }
+}
+
+/// Starts the time tracking, then tags the project and adds the note. Only a failed start is an
+/// error, as the time tracking runs either way, so failed tags or notes are returned as warnings.
+fn start_with_context(
+    name: &str,
+    tags: Option<Vec<String>>,
+    note: Option<String>,
+    start: impl FnOnce() -> Result<TimeTrackDto, ClientError>,
+    update_project: impl FnOnce(&CreateProjectDto) -> Result<ProjectDto, ClientError>,
+    append_note: impl FnOnce(&str, &str, &AppendNoteDto) -> Result<TimeTrackDto, ClientError>,
+) -> Result<(TimeTrackDto, Vec<String>), ClientError> {
+    let mut timetrack = start()?;
+    let mut warnings = Vec::new();
+
+    if let Some(tags) = tags {
+        let update_project_dto = CreateProjectDto {
+            name: name.to_string(),
+            tags: Some(tags),
+        };
+        if let Err(err) = update_project(&update_project_dto) {
+            warnings.push(format!("Failed to tag the project: {}", err));
+        }
+    }
+
+    if let Some(note) = note {
+        let append_note_dto = AppendNoteDto { note };
+        match append_note(
+            &timetrack.project_id,
+            &timetrack.time_track_id,
+            &append_note_dto,
+        ) {
+            Ok(with_note) => timetrack = with_note,
+            Err(err) => warnings.push(format!("Failed to add the note: {}", err)),
+        }
+    }
+
+    Ok((timetrack, warnings))
+}
+
+// Keeps the existing tags first and skips tags the project already has
+fn with_added_tags(mut tags: Vec<String>, added_tags: &[String]) -> Vec<String> {
+    for tag in added_tags {
+        let tag = tag.trim();
+        if !tags.iter().any(|existing| existing == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
+
+/// Sends heartbeats until the process is stopped. The time tracking keeps running after exit.
+fn watch_time_tracking(config: &Config, project_id: &str, name: &str) {
+    println!(
+        "\nWatching {}. Press Ctrl+C to stop watching, the time tracking keeps running.",
+        name.color(Color::Cyan)
+    );
+
+    loop {
+        let api_response = sitt_client::send_heartbeat(config, project_id);
+        utils::print_and_exit_on_error(api_response);
+
+        std::thread::sleep(HEARTBEAT_INTERVAL);
+    }
 }
 
 pub fn stop_time_tracking(config: &Config, project_args: &NameArg) {
@@ -90,32 +235,40 @@ pub fn stop_time_tracking(config: &Config, project_args: &NameArg) {
     );
     let project_id_result = get_project_id_by_name(config, &name);
     let project_id = print_and_exit_on_error(project_id_result);
+    queue_stop_if_start_is_queued(&project_id, &name);
 
-    let api_response = sitt_client::stop_time_tracking(config, &project_id);
+    let api_response = queue_if_unreachable(
+        sitt_client::stop_time_tracking(config, &project_id),
+        QueuedOperation::Stop {
+            project_id: project_id.clone(),
+            project_name: name,
+        },
+    );
     let timetrack = utils::print_and_exit_on_error(api_response);
 
     print_time_track_full(&timetrack)
 }
 
-pub fn add_time_tracking(config: &Config, args: &NameArg) {
+pub fn add_time_tracking(config: &Config, args: &AddTimeArgs) {
     let name = resolve_project_name(
         args.name.clone(),
         config,
         "add time on",
         ProjectSelectOption::None,
     );
-    let project_id_result = get_project_id_by_name(config, &name);
-    let project_id = print_and_exit_on_error(project_id_result);
+    let project_id = get_or_create_project_id(config, &name, args.create_if_missing, args.yes);
 
     let started_at = utils::prompt_user_for_datetime(
         &format!("Enter the {} date", "starting".color(Color::Yellow)),
         None,
+        Some(Utc::now()),
         None,
     );
 
     let stopped_at = utils::prompt_user_for_datetime(
         &format!("Enter the {} date", "stopping".color(Color::Yellow),),
         Some(started_at),
+        Some(Utc::now()),
         Some(started_at),
     );
 
@@ -141,19 +294,38 @@ pub fn add_time_tracking(config: &Config, args: &NameArg) {
         exit(0)
     }
 
+    let comment = Text::new("Comment:")
+        .with_help_message("Optional, press ESC to skip")
+        .prompt_skippable()
+        .unwrap_or_else(|err| {
+            eprintln!("Error: {}", err);
+            exit(1);
+        })
+        .filter(|comment| !comment.trim().is_empty());
+
     let create_time_track = CreateTimeTrackDto {
         project_id,
         started_at,
         stopped_at,
+        comment,
     };
 
-    let api_response = sitt_client::add_time_tracking(config, &create_time_track);
+    let api_response = queue_if_unreachable(
+        sitt_client::add_time_tracking(config, &create_time_track),
+        QueuedOperation::Add {
+            project_id: create_time_track.project_id.clone(),
+            project_name: name,
+            started_at: create_time_track.started_at,
+            stopped_at: create_time_track.stopped_at,
+            comment: create_time_track.comment.clone(),
+        },
+    );
     let timetrack = utils::print_and_exit_on_error(api_response);
 
     print_time_track_full(&timetrack)
 }
 
-pub fn get_time_trackings(config: &Config, args: &NameArg) {
+pub fn get_time_trackings(config: &Config, args: &ListTimeArgs) {
     let name = resolve_project_name(
         args.name.clone(),
         config,
@@ -163,26 +335,283 @@ pub fn get_time_trackings(config: &Config, args: &NameArg) {
     let project_id_result = get_project_id_by_name(config, &name);
     let project_id = print_and_exit_on_error(project_id_result);
 
-    let api_response = sitt_client::get_time_trackings(config, &project_id);
-    let timetrack_list = utils::print_and_exit_on_error(api_response);
+    let day = match (args.today, args.yesterday) {
+        (true, _) => Some(("today", utils::local_day_bounds(0))),
+        (_, true) => Some(("yesterday", utils::local_day_bounds(1))),
+        _ => None,
+    };
+
+    let api_response = sitt_client::get_time_trackings(config, &project_id, true);
+    let mut timetrack_list = utils::print_and_exit_on_error(api_response);
+    if let Some((_, (start, end))) = day {
+        timetrack_list.retain(|t| t.started_at >= start && t.started_at < end);
+    }
+    sort_time_trackings(&mut timetrack_list, args.reverse);
+
+    // The --limit flag wins over the configured page size
+    let limit = config.list_limit(args.limit);
+
+    if args.json_lines {
+        utils::limit_list(&mut timetrack_list, limit);
+        let json_lines = utils::to_json_lines(&timetrack_list);
+        print!("{}", utils::print_and_exit_on_error(json_lines));
+        return;
+    }
 
     if timetrack_list.is_empty() {
-        println!(
-            "You have not yet tracked any time on {}",
+        match day {
+            Some((label, _)) => println!(
+                "You have not tracked any time on {} {}",
+                name.color(Color::Cyan),
+                label
+            ),
+            None => println!(
+                "You have not yet tracked any time on {}",
+                name.color(Color::Cyan)
+            ),
+        }
+        exit(0)
+    }
+
+    match day {
+        Some((label, _)) => println!(
+            "You have logged {} on {} {}:\n",
+            total_duration(&timetrack_list).color(Color::Yellow),
+            name.color(Color::Cyan),
+            label
+        ),
+        None => println!(
+            "You have logged time {} times on {}:\n",
+            timetrack_list.len().to_string().color(Color::Yellow),
             name.color(Color::Cyan)
+        ),
+    }
+
+    let truncated_note = utils::limit_list(&mut timetrack_list, limit);
+
+    // Without a terminal, e.g. when piped, comments are not truncated
+    let width = if args.full {
+        None
+    } else {
+        Term::stdout()
+            .size_checked()
+            .map(|(_, columns)| columns as usize)
+    };
+
+    timetrack_list.iter().for_each(|t| {
+        let time_track = CliTimeTrack::from(t.clone());
+        let line = if args.relative {
+            time_track.to_relative_string()
+        } else {
+            time_track.to_string()
+        };
+        println!(
+            "{}",
+            with_comment(line, time_track.comment.as_deref(), width)
         );
-        exit(0)
+    });
+
+    if let Some(note) = truncated_note {
+        println!("{}", note);
     }
+}
 
-    println!(
-        "You have logged time {} times on {}:\n",
-        timetrack_list.len().to_string().color(Color::Yellow),
-        name.color(Color::Cyan)
+/// Appends the comment to the line on one line, truncated with an ellipsis to fit the width if given
+fn with_comment(line: String, comment: Option<&str>, width: Option<usize>) -> String {
+    let comment = match comment {
+        Some(comment) => comment.split_whitespace().collect::<Vec<_>>().join(" "),
+        None => return line,
+    };
+
+    let line = format!("{} | ", line);
+    match width {
+        Some(width) => {
+            let available = width.saturating_sub(measure_text_width(&line));
+            format!("{}{}", line, truncate_str(&comment, available, "…"))
+        }
+        None => format!("{}{}", line, comment),
+    }
+}
+
+pub fn export_time_trackings(config: &Config, args: &ExportTimeArgs) {
+    let name = resolve_project_name(
+        args.name.clone(),
+        config,
+        "export logged time on",
+        ProjectSelectOption::None,
     );
+    let project_id_result = get_project_id_by_name(config, &name);
+    let project_id = print_and_exit_on_error(project_id_result);
+
+    // Left out by default, so exporting twice gives the same durations
+    let api_response = sitt_client::get_time_trackings(config, &project_id, args.include_running);
+    let mut timetrack_list = utils::print_and_exit_on_error(api_response);
 
-    timetrack_list
+    if !args.since_last {
+        sort_time_trackings(&mut timetrack_list, true);
+        print!(
+            "{}",
+            time_trackings_to_csv(&timetrack_list, !args.no_comments)
+        );
+        return;
+    }
+
+    let watermark_file_path = watermark::watermark_file_path();
+    let mut watermarks =
+        utils::print_and_exit_on_error(watermark::load_watermarks(&watermark_file_path));
+    let last_export = if args.full {
+        None
+    } else {
+        watermarks.get(&project_id).copied()
+    };
+
+    let mut timetrack_list = watermark::select_since(timetrack_list, last_export);
+    sort_time_trackings(&mut timetrack_list, true);
+    print!(
+        "{}",
+        time_trackings_to_csv(&timetrack_list, !args.no_comments)
+    );
+
+    // Only advance the watermark once the export was written
+    if let Some(watermark) = watermark::advance(last_export, &timetrack_list) {
+        watermarks.insert(project_id, watermark);
+        utils::print_and_exit_on_error(watermark::save_watermarks(
+            &watermark_file_path,
+            &watermarks,
+        ));
+    }
+}
+
+/// Formats the time trackings as CSV, optionally without the comment column
+fn time_trackings_to_csv(timetrack_list: &[TimeTrackDto], include_comments: bool) -> String {
+    let mut header = vec!["project", "started_at", "stopped_at", "duration"];
+    if include_comments {
+        header.push("comment");
+    }
+
+    let mut csv = header.join(",");
+    csv.push_str("\r\n");
+
+    for t in timetrack_list {
+        let mut row = vec![
+            utils::csv_field(&t.project_name),
+            utils::csv_field(&t.started_at.to_rfc3339()),
+            utils::csv_field(&t.stopped_at.map(|s| s.to_rfc3339()).unwrap_or_default()),
+            utils::csv_field(&t.total_duration),
+        ];
+        if include_comments {
+            row.push(utils::csv_field(t.comment.as_deref().unwrap_or_default()));
+        }
+
+        csv.push_str(&row.join(","));
+        csv.push_str("\r\n");
+    }
+
+    csv
+}
+
+/// Sums the durations of the time trackings, including the ones in progress
+fn total_duration(timetrack_list: &[TimeTrackDto]) -> String {
+    let total: Duration = timetrack_list
         .iter()
-        .for_each(|t| println!("{}", CliTimeTrack::from(t.clone())));
+        .filter_map(|t| humantime::parse_duration(&t.total_duration).ok())
+        .sum();
+
+    humantime::format_duration(total).to_string()
+}
+
+/// Orders the time trackings newest first, or oldest first when reversed
+fn sort_time_trackings(timetrack_list: &mut [TimeTrackDto], reverse: bool) {
+    if reverse {
+        timetrack_list.sort_by_key(|t| t.started_at);
+    } else {
+        timetrack_list.sort_by_key(|t| std::cmp::Reverse(t.started_at));
+    }
+}
+
+pub fn get_recent_time_trackings(config: &Config, args: &RecentArgs) {
+    let fetch_page = |cursor: Option<&str>| {
+        let api_response = sitt_client::get_recent_time_trackings(config, args.limit, cursor);
+        utils::print_and_exit_on_error(api_response)
+    };
+
+    if args.json {
+        let api_response = sitt_client::get_recent_time_trackings_with_warnings(
+            config,
+            args.limit,
+            args.cursor.as_deref(),
+        );
+        let (page, warnings) = utils::print_and_exit_on_error(api_response);
+        let json = utils::to_json_with_warnings(&page, warnings);
+        println!("{}", utils::print_and_exit_on_error(json));
+        return;
+    }
+
+    let mut is_first_page = true;
+    browse_recent_pages(
+        args.cursor.clone(),
+        fetch_page,
+        |time_tracks| {
+            if time_tracks.is_empty() && is_first_page {
+                println!("You have not yet tracked any time");
+            }
+            is_first_page = false;
+
+            time_tracks
+                .iter()
+                .for_each(|t| println!("{}", format_recent_time_track(t)));
+        },
+        || {
+            Confirm::new("Show more?")
+                .with_default(true)
+                .prompt()
+                .unwrap_or_else(|err| {
+                    eprintln!("Error: {}", err);
+                    exit(1);
+                })
+        },
+    );
+}
+
+// Fetches pages until there are no more, or the user does not want to see more
+fn browse_recent_pages<F, P, M>(
+    mut cursor: Option<String>,
+    mut fetch_page: F,
+    mut print_page: P,
+    mut show_more: M,
+) where
+    F: FnMut(Option<&str>) -> RecentTimeTracksDto,
+    P: FnMut(&[TimeTrackDto]),
+    M: FnMut() -> bool,
+{
+    loop {
+        let page = fetch_page(cursor.as_deref());
+        print_page(&page.time_tracks);
+
+        match page.next_cursor {
+            Some(next_cursor) if show_more() => cursor = Some(next_cursor),
+            _ => break,
+        }
+    }
+}
+
+fn format_recent_time_track(timetrack: &TimeTrackDto) -> String {
+    let duration = if timetrack.status == TimeTrackStatus::InProgress {
+        format!("{} ⏱️", timetrack.total_duration).color(Color::BrightGreen)
+    } else {
+        timetrack.total_duration.color(Color::Yellow)
+    };
+
+    format!(
+        "{} | {} ({}) | {}",
+        timetrack.project_name.color(Color::Cyan),
+        timetrack
+            .started_at
+            .with_timezone(&Local)
+            .format(DATETIME_FORMAT),
+        humanize_relative(timetrack.started_at),
+        duration
+    )
 }
 
 pub fn edit_time_track(config: &Config, args: &NameArg) {
@@ -205,6 +634,7 @@ pub fn edit_time_track(config: &Config, args: &NameArg) {
     let started_at = utils::prompt_user_for_datetime(
         &format!("Enter the {} date", "starting".color(Color::Yellow)),
         None,
+        Some(Utc::now()),
         Some(time_track.started_at),
     );
 
@@ -218,6 +648,7 @@ pub fn edit_time_track(config: &Config, args: &NameArg) {
     let stopped_at = utils::prompt_user_for_datetime(
         &format!("Enter the {} date", "stopping".color(Color::Yellow),),
         Some(started_at),
+        Some(Utc::now()),
         Some(stopped_at_placeholder),
     );
 
@@ -247,6 +678,7 @@ pub fn edit_time_track(config: &Config, args: &NameArg) {
         project_id,
         started_at,
         stopped_at,
+        comment: time_track.comment,
     };
 
     let api_response = sitt_client::update_time_track(config, &time_track.id, &update_time_track);
@@ -255,6 +687,39 @@ pub fn edit_time_track(config: &Config, args: &NameArg) {
     println!("The time log was successfully updated! ✅")
 }
 
+pub fn append_note(config: &Config, args: &NoteArgs) {
+    let name = resolve_project_name(
+        args.name.clone(),
+        config,
+        "add a note on",
+        ProjectSelectOption::None,
+    );
+
+    let project_id_result = get_project_id_by_name(config, &name);
+    let project_id = print_and_exit_on_error(project_id_result);
+
+    let time_track_id = match &args.id {
+        Some(id) => id.clone(),
+        None => select_time_track(config, "add a note to", &name, &project_id).id,
+    };
+
+    let note = match &args.message {
+        Some(message) => message.clone(),
+        None => Text::new("Note:").prompt().unwrap_or_else(|err| {
+            eprintln!("Error: {}", err);
+            exit(1)
+        }),
+    };
+
+    let append_note_dto = AppendNoteDto { note };
+    let api_response =
+        sitt_client::append_note(config, &project_id, &time_track_id, &append_note_dto);
+    let timetrack = utils::print_and_exit_on_error(api_response);
+
+    println!("The note was added to the time log! ✅");
+    print_notes(&timetrack.notes);
+}
+
 pub fn delete_time_tracking(config: &Config, args: &NameArg) {
     let name = resolve_project_name(
         args.name.clone(),
@@ -281,9 +746,14 @@ pub fn delete_time_tracking(config: &Config, args: &NameArg) {
 
     let api_response =
         sitt_client::delete_time_track(config, &time_track.project_id, &time_track.id);
-    utils::print_and_exit_on_error(api_response);
+    let deleted = utils::print_and_exit_on_error(api_response);
 
-    println!("The time log was successfully deleted! ✅")
+    println!(
+        "Removed {} — {} now totals {} ✅",
+        deleted.deleted_duration,
+        name.color(Color::Cyan),
+        deleted.total_duration
+    )
 }
 
 fn select_time_track(
@@ -292,7 +762,7 @@ fn select_time_track(
     project_name: &str,
     project_id: &str,
 ) -> CliTimeTrack {
-    let api_response = sitt_client::get_time_trackings(config, project_id);
+    let api_response = sitt_client::get_time_trackings(config, project_id, true);
     let timetrack_list = utils::print_and_exit_on_error(api_response);
 
     if timetrack_list.is_empty() {
@@ -350,4 +820,324 @@ STARTED AT:   {}"#,
         );
         println!("DURATION:     {}", timetrack.total_duration);
     }
+
+    if let Some(comment) = &timetrack.comment {
+        println!("COMMENT:      {}", comment);
+    }
+
+    print_notes(&timetrack.notes);
+}
+
+fn print_notes(notes: &[TimeTrackNote]) {
+    if notes.is_empty() {
+        return;
+    }
+
+    println!("NOTES:");
+    for note in notes {
+        println!(
+            "  {}  {}",
+            note.created_at
+                .with_timezone(&Local)
+                .format(DATETIME_FORMAT),
+            note.text
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(ids: &[&str], next_cursor: Option<&str>) -> RecentTimeTracksDto {
+        RecentTimeTracksDto {
+            time_tracks: ids
+                .iter()
+                .map(|id| TimeTrackDto {
+                    time_track_id: id.to_string(),
+                    project_id: String::from("project"),
+                    project_name: String::from("Project"),
+                    status: TimeTrackStatus::Finished,
+                    started_at: Utc::now(),
+                    stopped_at: Some(Utc::now()),
+                    total_duration: String::from("1h"),
+                    comment: None,
+                    notes: Vec::new(),
+                    created_at: Utc::now(),
+                    created_by: String::from("user_id"),
+                })
+                .collect(),
+            next_cursor: next_cursor.map(String::from),
+        }
+    }
+
+    #[test]
+    fn browse_recent_pages_follows_cursor() {
+        let mut requested_cursors: Vec<Option<String>> = Vec::new();
+        let mut printed_ids: Vec<String> = Vec::new();
+
+        browse_recent_pages(
+            None,
+            |cursor| {
+                requested_cursors.push(cursor.map(String::from));
+                match cursor {
+                    None => page(&["a", "b"], Some("cursor_1")),
+                    Some("cursor_1") => page(&["c", "d"], Some("cursor_2")),
+                    _ => page(&["e"], None),
+                }
+            },
+            |time_tracks| printed_ids.extend(time_tracks.iter().map(|t| t.time_track_id.clone())),
+            || true,
+        );
+
+        assert_eq!(
+            requested_cursors,
+            vec![
+                None,
+                Some(String::from("cursor_1")),
+                Some(String::from("cursor_2"))
+            ]
+        );
+        assert_eq!(printed_ids, vec!["a", "b", "c", "d", "e"]);
+    }
+
+    #[test]
+    fn browse_recent_pages_stops_when_user_declines() {
+        let mut fetched_pages = 0;
+
+        browse_recent_pages(
+            Some(String::from("cursor_1")),
+            |cursor| {
+                fetched_pages += 1;
+                assert_eq!(
+                    cursor,
+                    Some("cursor_1"),
+                    "Expected to start from the given cursor"
+                );
+                page(&["c", "d"], Some("cursor_2"))
+            },
+            |_| {},
+            || false,
+        );
+
+        assert_eq!(fetched_pages, 1, "Expected only one page to be fetched");
+    }
+
+    fn commented_time_track() -> TimeTrackDto {
+        let mut time_track = page(&["id"], None).time_tracks.remove(0);
+        time_track.started_at = "2024-08-01T12:00:00Z".parse().unwrap();
+        time_track.stopped_at = Some("2024-08-01T13:00:00Z".parse().unwrap());
+        time_track.comment = Some(String::from("Review, \"part 1\"\nand notes"));
+        time_track
+    }
+
+    fn project_dto() -> ProjectDto {
+        ProjectDto {
+            project_id: String::from("project"),
+            name: String::from("Project"),
+            status: sitt_api::models::project_model::ProjectStatus::Active,
+            total_duration: String::from("0s"),
+            tags: vec![String::from("client"), String::from("billable")],
+            created_at: Utc::now(),
+            created_by: String::from("user_id"),
+            modified_at: None,
+            modified_by: None,
+        }
+    }
+
+    #[test]
+    fn start_with_context_tags_the_project_and_adds_the_note() {
+        let started = page(&["started"], None).time_tracks.remove(0);
+        let updated_project = std::cell::RefCell::new(None);
+        let appended_note = std::cell::RefCell::new(None);
+
+        let (timetrack, warnings) = start_with_context(
+            "Project",
+            Some(vec![String::from("client"), String::from("billable")]),
+            Some(String::from("Fixing the login")),
+            || Ok(started.clone()),
+            |update_project_dto| {
+                updated_project.replace(Some((
+                    update_project_dto.name.clone(),
+                    update_project_dto.tags.clone(),
+                )));
+                Ok(project_dto())
+            },
+            |_, time_track_id, append_note_dto| {
+                appended_note.replace(Some((
+                    time_track_id.to_string(),
+                    append_note_dto.note.clone(),
+                )));
+                let mut with_note = started.clone();
+                with_note
+                    .notes
+                    .push(TimeTrackNote::new(&append_note_dto.note));
+                Ok(with_note)
+            },
+        )
+        .unwrap();
+
+        assert!(warnings.is_empty(), "Expected no warnings: {:?}", warnings);
+        assert_eq!(
+            updated_project.into_inner(),
+            Some((
+                String::from("Project"),
+                Some(vec![String::from("client"), String::from("billable")])
+            ))
+        );
+        assert_eq!(
+            appended_note.into_inner(),
+            Some((String::from("started"), String::from("Fixing the login")))
+        );
+        assert_eq!(timetrack.notes[0].text, "Fixing the login");
+    }
+
+    #[test]
+    fn start_with_context_only_starts_without_tags_or_comment() {
+        let started = page(&["started"], None).time_tracks.remove(0);
+
+        let (timetrack, warnings) = start_with_context(
+            "Project",
+            None,
+            None,
+            || Ok(started.clone()),
+            |_| panic!("Expected the project to not be updated"),
+            |_, _, _| panic!("Expected no note to be added"),
+        )
+        .unwrap();
+
+        assert_eq!(timetrack.time_track_id, "started");
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn start_with_context_warns_when_tagging_or_the_note_fails_after_starting() {
+        let started = page(&["started"], None).time_tracks.remove(0);
+
+        let (timetrack, warnings) = start_with_context(
+            "Project",
+            Some(vec![String::from("client")]),
+            Some(String::from("Fixing the login")),
+            || Ok(started.clone()),
+            |_| Err(ClientError::Unauthorized),
+            |_, _, _| Err(ClientError::NotFound(String::from("Time track not found"))),
+        )
+        .unwrap();
+
+        assert_eq!(timetrack.time_track_id, "started");
+        assert_eq!(warnings.len(), 2, "Expected a warning each: {:?}", warnings);
+        assert!(warnings[0].starts_with("Failed to tag the project"));
+        assert!(warnings[1].starts_with("Failed to add the note"));
+    }
+
+    #[test]
+    fn with_added_tags_keeps_existing_tags_and_skips_duplicates() {
+        let tags = with_added_tags(
+            vec![String::from("client")],
+            &[String::from(" billable "), String::from("client")],
+        );
+
+        assert_eq!(tags, vec!["client", "billable"]);
+    }
+
+    #[test]
+    fn csv_quotes_comments_with_commas_and_newlines() {
+        let csv = time_trackings_to_csv(&[commented_time_track()], true);
+
+        assert_eq!(
+            csv,
+            "project,started_at,stopped_at,duration,comment\r\n\
+             Project,2024-08-01T12:00:00+00:00,2024-08-01T13:00:00+00:00,1h,\
+             \"Review, \"\"part 1\"\"\nand notes\"\r\n"
+        );
+    }
+
+    #[test]
+    fn csv_omits_the_comment_column_without_comments() {
+        let csv = time_trackings_to_csv(&[commented_time_track()], false);
+
+        assert_eq!(
+            csv,
+            "project,started_at,stopped_at,duration\r\n\
+             Project,2024-08-01T12:00:00+00:00,2024-08-01T13:00:00+00:00,1h\r\n"
+        );
+    }
+
+    #[test]
+    fn total_duration_sums_the_time_trackings() {
+        let mut time_tracks = page(&["first", "second"], None).time_tracks;
+        time_tracks[1].total_duration = String::from("30m 15s");
+
+        assert_eq!(total_duration(&time_tracks), "1h 30m 15s");
+    }
+
+    #[test]
+    fn sort_time_trackings_orders_newest_or_oldest_first() {
+        let mut time_tracks = page(&["middle", "oldest", "newest"], None).time_tracks;
+        time_tracks[0].started_at = Utc::now() - chrono::Duration::hours(2);
+        time_tracks[1].started_at = Utc::now() - chrono::Duration::hours(3);
+        time_tracks[2].started_at = Utc::now() - chrono::Duration::hours(1);
+        let ids = |time_tracks: &[TimeTrackDto]| -> Vec<String> {
+            time_tracks
+                .iter()
+                .map(|t| t.time_track_id.clone())
+                .collect()
+        };
+
+        sort_time_trackings(&mut time_tracks, false);
+        assert_eq!(ids(&time_tracks), vec!["newest", "middle", "oldest"]);
+
+        sort_time_trackings(&mut time_tracks, true);
+        assert_eq!(ids(&time_tracks), vec!["oldest", "middle", "newest"]);
+    }
+
+    #[test]
+    fn limit_time_trackings_keeps_the_first_after_sorting() {
+        let mut time_tracks = page(&["middle", "oldest", "newest"], None).time_tracks;
+        time_tracks[0].started_at = Utc::now() - chrono::Duration::hours(2);
+        time_tracks[1].started_at = Utc::now() - chrono::Duration::hours(3);
+        time_tracks[2].started_at = Utc::now() - chrono::Duration::hours(1);
+        sort_time_trackings(&mut time_tracks, true);
+
+        let note = utils::limit_list(&mut time_tracks, Some(2));
+
+        assert_eq!(note.as_deref(), Some("(showing 2 of 3)"));
+        let ids: Vec<&str> = time_tracks
+            .iter()
+            .map(|t| t.time_track_id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["oldest", "middle"]);
+    }
+
+    #[test]
+    fn limit_time_trackings_without_truncation_has_no_note() {
+        let mut time_tracks = page(&["a", "b"], None).time_tracks;
+
+        assert_eq!(utils::limit_list(&mut time_tracks, Some(2)), None);
+        assert_eq!(utils::limit_list(&mut time_tracks, None), None);
+        assert_eq!(time_tracks.len(), 2);
+    }
+
+    #[test]
+    fn with_comment_truncates_to_the_width() {
+        let line = with_comment(
+            String::from("12:00 -> 13:00 | 1h"),
+            Some("Reviewed the pull request\nand wrote notes"),
+            Some(40),
+        );
+
+        assert_eq!(line, "12:00 -> 13:00 | 1h | Reviewed the pull…");
+        assert_eq!(measure_text_width(&line), 40);
+    }
+
+    #[test]
+    fn with_comment_without_width_shows_the_full_comment() {
+        let comment = "Reviewed the pull request\nand wrote notes";
+
+        assert_eq!(
+            with_comment(String::from("1h"), Some(comment), None),
+            "1h | Reviewed the pull request and wrote notes"
+        );
+        assert_eq!(with_comment(String::from("1h"), None, Some(10)), "1h");
+    }
 }
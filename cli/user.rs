@@ -1,14 +1,24 @@
 use core::fmt;
-use std::process::exit;
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+    process::exit,
+    str::FromStr,
+};
 
 use colored::{Color, Colorize};
 use inquire::{Confirm, Select, Text};
 use sitt_api::{
-    handlers::dtos::user_dtos::{CreateUserDto, UserDto},
-    models::user_model::UserRole,
+    handlers::dtos::user_dtos::{ClaimAdminDto, CreateUserDto, UserDto},
+    models::user_model::{ApiKeyScope, UserRole},
 };
 
-use crate::{config::Config, sitt_client, utils};
+use crate::{
+    config::Config,
+    sitt_client::{self, ClientError},
+    utils, ClaimAdminArgs, CreateUserArgs, GetUserArgs,
+};
 
 struct SelectUser {
     pub id: String,
@@ -21,7 +31,12 @@ impl fmt::Display for SelectUser {
     }
 }
 
-pub fn create_user(config: &Config) {
+pub fn create_user(config: &Config, args: &CreateUserArgs) {
+    if let Some(file) = &args.from_file {
+        create_users_from_file(config, file, &args.output);
+        return;
+    }
+
     let name = Text::new("Name of user:").prompt().unwrap_or_else(|err| {
         eprintln!("Error: {}", err);
         exit(1);
@@ -51,7 +66,13 @@ pub fn create_user(config: &Config) {
         }
     }
 
-    let create_user_dto = CreateUserDto { name, role };
+    let scope = if args.read_only {
+        ApiKeyScope::ReadOnly
+    } else {
+        ApiKeyScope::ReadWrite
+    };
+
+    let create_user_dto = CreateUserDto { name, role, scope };
 
     let api_response = sitt_client::create_user(config, &create_user_dto);
 
@@ -61,7 +82,208 @@ pub fn create_user(config: &Config) {
     print_user(&user);
 }
 
-pub fn get_user(config: &Config) {
+pub fn claim_default_admin(config: &mut Config, args: &ClaimAdminArgs) {
+    let name = match &args.name {
+        Some(name) => name.clone(),
+        None => Text::new("Name of your admin:")
+            .prompt()
+            .unwrap_or_else(|err| {
+                eprintln!("Error: {}", err);
+                exit(1);
+            }),
+    };
+
+    let claim_admin_dto = ClaimAdminDto { name };
+    let api_response = sitt_client::claim_default_admin(config, &claim_admin_dto);
+    let claimed = utils::print_and_exit_on_error(api_response);
+
+    println!("Your admin was successfully created! ✅");
+    print_user(&claimed.admin);
+
+    if claimed.default_admin_deleted {
+        println!("The default admin was deleted 🗑️");
+    } else {
+        eprintln!(
+            "{}",
+            "Failed to delete the default admin, delete it with 'sitt user delete'"
+                .color(Color::Red)
+        );
+    }
+
+    let Some(api_key) = claimed.admin.api_key else {
+        return;
+    };
+    let use_api_key = Confirm::new("Use the API key of your admin in your configuration?")
+        .with_default(true)
+        .prompt()
+        .unwrap_or_else(|err| {
+            eprintln!("Error: {}", err);
+            exit(1);
+        });
+    if use_api_key {
+        config.set_api_key(api_key);
+        let config_path = config.save();
+        println!(
+            "The configuration was updated at {} 🔑",
+            config_path.to_string_lossy()
+        );
+    }
+}
+
+fn create_users_from_file(config: &Config, file: &Path, output: &Path) {
+    let content = fs::read_to_string(file).unwrap_or_else(|err| {
+        eprintln!("Error: Failed to read {}: {}", file.to_string_lossy(), err);
+        exit(1);
+    });
+
+    let create_user_dtos = parse_users_csv(&content).unwrap_or_else(|err| {
+        eprintln!("Error: {}", err);
+        exit(1);
+    });
+
+    if create_user_dtos.is_empty() {
+        eprintln!("Error: {} contains no users", file.to_string_lossy());
+        exit(1);
+    }
+
+    // The API keys can't be fetched again, so fail before creating anyone
+    if output.exists() {
+        eprintln!(
+            "Error: {} already exists, choose another file for the API keys",
+            output.to_string_lossy()
+        );
+        exit(1);
+    }
+
+    let api_response = sitt_client::create_users(config, &create_user_dtos);
+    let created_users = utils::print_and_exit_on_error(api_response);
+
+    // The API keys are only returned once, so write them before reporting anything else
+    if !created_users.api_keys.is_empty() {
+        let mut csv = String::from("name,api_key\r\n");
+        for (name, api_key) in &created_users.api_keys {
+            csv.push_str(&format!("{},{}\r\n", utils::csv_field(name), api_key));
+        }
+
+        write_private_file(output, &csv).unwrap_or_else(|err| {
+            eprintln!(
+                "Error: Failed to write {}: {}",
+                output.to_string_lossy(),
+                err
+            );
+            exit(1);
+        });
+
+        println!(
+            "{} users were successfully created! ✅ Their API keys were written to {}",
+            created_users.api_keys.len(),
+            output.to_string_lossy()
+        );
+    }
+
+    for (name, error_message) in &created_users.failed {
+        eprintln!("❌ Failed to create '{}': {}", name, error_message);
+    }
+
+    if !created_users.failed.is_empty() {
+        exit(1);
+    }
+}
+
+/// Creates the file readable only by its owner on unix, as it holds API keys, and never replaces
+/// an existing file
+fn write_private_file(path: &Path, content: &str) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    options.open(path)?.write_all(content.as_bytes())
+}
+
+/// Resolves a user name or id to the id of the user, for ADMINs scoping listings to a user
+pub fn resolve_user_id(config: &Config, name_or_id: &str) -> String {
+    let result = find_user_id(name_or_id, |name| {
+        sitt_client::get_users_by_name(config, name)
+    });
+    utils::print_and_exit_on_error(result)
+}
+
+// A name that no user has is taken to be an id, which the API then checks
+fn find_user_id(
+    name_or_id: &str,
+    get_users_by_name: impl Fn(&str) -> Result<Vec<UserDto>, ClientError>,
+) -> Result<String, String> {
+    match get_users_by_name(name_or_id) {
+        Ok(users) => match users.as_slice() {
+            [user] => Ok(user.id.clone()),
+            [] => Ok(name_or_id.to_string()),
+            _ => Err(format!(
+                "{} users are named {}, use the id of the user instead",
+                users.len(),
+                name_or_id
+            )),
+        },
+        Err(ClientError::NotFound(_)) => Ok(name_or_id.to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+// Only an exact `name,role` header is skipped, so a first user named e.g. "nathan" is kept
+fn is_users_csv_header(line: &str) -> bool {
+    match line.split_once(',') {
+        Some((name, role)) => {
+            name.trim().eq_ignore_ascii_case("name") && role.trim().eq_ignore_ascii_case("role")
+        }
+        None => false,
+    }
+}
+
+/// Parses lines of `name,role` into users. The role defaults to USER and a header line is skipped.
+fn parse_users_csv(content: &str) -> Result<Vec<CreateUserDto>, String> {
+    let mut users = Vec::new();
+
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || (index == 0 && is_users_csv_header(line)) {
+            continue;
+        }
+
+        let (name, role) = match line.split_once(',') {
+            Some((name, role)) => (name.trim(), role.trim()),
+            None => (line, ""),
+        };
+
+        let role = match role {
+            "" => UserRole::User,
+            role => UserRole::from_str(&role.to_uppercase()).map_err(|_| {
+                format!(
+                    "Line {}: unknown role '{}', expected USER or ADMIN",
+                    index + 1,
+                    role
+                )
+            })?,
+        };
+
+        users.push(CreateUserDto {
+            name: name.to_string(),
+            role,
+            scope: ApiKeyScope::ReadWrite,
+        });
+    }
+
+    Ok(users)
+}
+
+pub fn get_user(config: &Config, args: &GetUserArgs) {
+    // Names are not unique, so every user with the name is shown
+    if let Some(name) = &args.name {
+        let api_response = sitt_client::get_users_by_name(config, name);
+        let users = utils::print_and_exit_on_error(api_response);
+        users.iter().for_each(print_user);
+        return;
+    }
+
     let user = select_user(config, "get");
 
     let include_api_key = Confirm::new("Should the API key be included?")
@@ -145,6 +367,7 @@ fn print_user(user: &UserDto) {
         r#"ID:          {}
 NAME:        {}
 ROLE:        {}
+SCOPE:       {}
 API_KEY:     {}
 CREATED AT:  {}
 CREATED BY:  {}
@@ -152,8 +375,117 @@ CREATED BY:  {}
         user.id,
         user.name.color(Color::Yellow),
         user.role,
+        user.scope,
         api_key,
         user.created_at,
         user.created_by,
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_users_csv_skips_header_and_defaults_role() {
+        let content = "name,role\r\nalice,USER\r\nbob, admin\r\n\r\ncarol\r\n";
+
+        let users = parse_users_csv(content).unwrap();
+
+        let parsed: Vec<(&str, &UserRole)> = users
+            .iter()
+            .map(|user| (user.name.as_str(), &user.role))
+            .collect();
+        assert_eq!(
+            parsed,
+            vec![
+                ("alice", &UserRole::User),
+                ("bob", &UserRole::Admin),
+                ("carol", &UserRole::User)
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_users_csv_only_skips_an_exact_header() {
+        let users = parse_users_csv("nathan,USER\nalice").unwrap();
+        let names: Vec<&str> = users.iter().map(|user| user.name.as_str()).collect();
+        assert_eq!(names, vec!["nathan", "alice"]);
+
+        let users = parse_users_csv(" Name , Role \nalice").unwrap();
+        let names: Vec<&str> = users.iter().map(|user| user.name.as_str()).collect();
+        assert_eq!(names, vec!["alice"]);
+    }
+
+    #[test]
+    fn write_private_file_never_replaces_a_file() {
+        let path = std::env::temp_dir().join(format!(
+            "sitt-api-keys-{}-{}.csv",
+            std::process::id(),
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+
+        write_private_file(&path, "name,api_key\r\n").unwrap();
+        let err = write_private_file(&path, "replaced").unwrap_err();
+        let content = fs::read_to_string(&path).unwrap();
+        #[cfg(unix)]
+        let mode = {
+            use std::os::unix::fs::PermissionsExt;
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777
+        };
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(content, "name,api_key\r\n");
+        #[cfg(unix)]
+        assert_eq!(mode, 0o600);
+    }
+
+    #[test]
+    fn parse_users_csv_rejects_unknown_role() {
+        let err = parse_users_csv("alice,USER\ndave,OWNER").unwrap_err();
+
+        assert_eq!(err, "Line 2: unknown role 'OWNER', expected USER or ADMIN");
+    }
+
+    fn user_dto(id: &str, name: &str) -> UserDto {
+        UserDto {
+            id: id.to_string(),
+            name: name.to_string(),
+            role: UserRole::User,
+            scope: ApiKeyScope::ReadWrite,
+            api_key: None,
+            created_at: chrono::Utc::now(),
+            created_by: String::from("admin_id"),
+        }
+    }
+
+    #[test]
+    fn find_user_id_resolves_names_and_passes_ids_through() {
+        let get_users_by_name = |name: &str| match name {
+            "alice" => Ok(vec![user_dto("alice_id", "alice")]),
+            "bob" => Ok(vec![user_dto("bob_1", "bob"), user_dto("bob_2", "bob")]),
+            _ => Err(ClientError::NotFound(String::from("User not found"))),
+        };
+
+        assert_eq!(
+            find_user_id("alice", get_users_by_name).unwrap(),
+            "alice_id"
+        );
+        assert_eq!(
+            find_user_id("3f1c9a52-8d0e-4b8e-9a57-6c1f0d2e7b41", get_users_by_name).unwrap(),
+            "3f1c9a52-8d0e-4b8e-9a57-6c1f0d2e7b41"
+        );
+        assert_eq!(
+            find_user_id("bob", get_users_by_name).unwrap_err(),
+            "2 users are named bob, use the id of the user instead"
+        );
+    }
+
+    #[test]
+    fn find_user_id_fails_for_non_admins() {
+        let result = find_user_id("alice", |_| Err(ClientError::Unauthorized));
+
+        assert_eq!(result.unwrap_err(), "Unauthorized request");
+    }
+}
@@ -1,23 +1,93 @@
 use std::{fmt::Display, process::exit, time::Duration};
 
 use chrono::{
-    DateTime, Datelike, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Timelike, Utc,
+    DateTime, Datelike, Days, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, TimeZone,
+    Timelike, Utc,
 };
 use indicatif::{ProgressBar, ProgressStyle};
-use inquire::{DateSelect, Text};
+use inquire::{validator::Validation, DateSelect, Text};
+use serde::Serialize;
+
+use crate::sitt_client::EXIT_FAILURE;
 
 pub const DATETIME_FORMAT: &str = "%d/%m/%Y %H:%M:%S";
 
+/// The exit code a command fails with because of the error. Only errors with their own code
+/// override the default of 1.
+pub trait ExitCode {
+    fn exit_code(&self) -> i32 {
+        EXIT_FAILURE
+    }
+}
+
+impl ExitCode for String {}
+impl ExitCode for serde_json::Error {}
+
 pub fn print_and_exit_on_error<T, E>(result: Result<T, E>) -> T
 where
-    E: Display,
+    E: Display + ExitCode,
 {
     result.unwrap_or_else(|err| {
         eprintln!("{}", err);
-        exit(1);
+        exit(err.exit_code());
     })
 }
 
+pub fn humanize_relative(datetime: DateTime<Utc>) -> String {
+    humanize_relative_from(datetime, Utc::now())
+}
+
+fn humanize_relative_from(datetime: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let time_delta = now - datetime;
+
+    let ago = |amount: i64, unit: &str| {
+        let plural = if amount == 1 { "" } else { "s" };
+        format!("{} {}{} ago", amount, unit, plural)
+    };
+
+    // Anything less than a minute ago (or in the future) is shown as "just now"
+    if time_delta.num_minutes() < 1 {
+        String::from("just now")
+    } else if time_delta.num_hours() < 1 {
+        ago(time_delta.num_minutes(), "minute")
+    } else if time_delta.num_days() < 1 {
+        ago(time_delta.num_hours(), "hour")
+    } else if time_delta.num_days() == 1 {
+        String::from("yesterday")
+    } else if time_delta.num_weeks() < 1 {
+        ago(time_delta.num_days(), "day")
+    } else if time_delta.num_days() < 30 {
+        ago(time_delta.num_weeks(), "week")
+    } else if time_delta.num_days() < 365 {
+        ago(time_delta.num_days() / 30, "month")
+    } else {
+        ago(time_delta.num_days() / 365, "year")
+    }
+}
+
+pub fn format_datetime(datetime: DateTime<Utc>, relative: bool) -> String {
+    if relative {
+        humanize_relative(datetime)
+    } else {
+        datetime
+            .with_timezone(&Local)
+            .format(DATETIME_FORMAT)
+            .to_string()
+    }
+}
+
+/// Keeps the first `limit` items, returning a note with how many were left out
+pub fn limit_list<T>(list: &mut Vec<T>, limit: Option<usize>) -> Option<String> {
+    let total = list.len();
+    match limit {
+        Some(limit) if limit < total => {
+            list.truncate(limit);
+            Some(format!("(showing {} of {})", limit, total))
+        }
+        _ => None,
+    }
+}
+
 pub fn get_spinner(msg: String) -> ProgressBar {
     let spinner = ProgressBar::new_spinner();
     spinner.set_message(msg);
@@ -35,6 +105,7 @@ pub fn get_spinner(msg: String) -> ProgressBar {
 pub fn prompt_user_for_datetime(
     msg: &str,
     min_date: Option<DateTime<Utc>>,
+    max_date: Option<DateTime<Utc>>,
     placeholder_date: Option<DateTime<Utc>>,
 ) -> DateTime<Utc> {
     // Ask the user to select a date (in local time zone)
@@ -51,6 +122,10 @@ pub fn prompt_user_for_datetime(
 
         initial_timestamp = get_local_time_as_str(min_date);
     }
+    if let Some(max_date) = max_date {
+        let naive_max_date = get_local_naive_date_from_utc_datetime(max_date);
+        date = date.with_max_date(naive_max_date);
+    }
     if let Some(placeholder_date) = placeholder_date {
         let naive_placeholder_date = get_local_naive_date_from_utc_datetime(placeholder_date);
         date = date.with_starting_date(naive_placeholder_date);
@@ -65,6 +140,25 @@ pub fn prompt_user_for_datetime(
         exit(1);
     });
 
+    // The date can be today, so the time must also be checked against the max date
+    let max_time_validator = move |input: &str| {
+        let datetime = NaiveTime::parse_from_str(input, "%H:%M:%S")
+            .ok()
+            .and_then(|time| {
+                Local
+                    .from_local_datetime(&NaiveDateTime::new(date, time))
+                    .earliest()
+            });
+
+        match datetime {
+            Some(datetime) if exceeds_max_date(datetime.with_timezone(&Utc), max_date) => Ok(
+                Validation::Invalid("The time can't be in the future".into()),
+            ),
+            _ => Ok(Validation::Valid),
+        }
+    };
+    time_input = time_input.with_validator(max_time_validator);
+
     let time_input_str = time_input.prompt().unwrap_or_else(|err| {
         eprintln!("Error: {}", err);
         exit(1);
@@ -88,12 +182,43 @@ pub fn prompt_user_for_datetime(
     local_datetime.with_timezone(&Utc)
 }
 
+/// Returns the start and (exclusive) end of the local calendar day `days_ago` days back
+pub fn local_day_bounds(days_ago: u64) -> (DateTime<Utc>, DateTime<Utc>) {
+    let date = Local::now().date_naive() - Days::new(days_ago);
+    day_bounds(date, &Local)
+}
+
+/// Returns the start and (exclusive) end of the local calendar day `date`
+pub fn local_date_bounds(date: NaiveDate) -> (DateTime<Utc>, DateTime<Utc>) {
+    day_bounds(date, &Local)
+}
+
+fn day_bounds<Tz: TimeZone>(date: NaiveDate, tz: &Tz) -> (DateTime<Utc>, DateTime<Utc>) {
+    let start_of_day = |date: NaiveDate| {
+        let midnight = date.and_time(NaiveTime::MIN);
+        // Midnight can be skipped by a DST change, then the day starts when the clocks resume
+        tz.from_local_datetime(&midnight)
+            .earliest()
+            .or_else(|| {
+                tz.from_local_datetime(&(midnight + TimeDelta::hours(1)))
+                    .earliest()
+            })
+            .map(|datetime| datetime.with_timezone(&Utc))
+            .unwrap_or_else(|| midnight.and_utc())
+    };
+
+    (start_of_day(date), start_of_day(date + Days::new(1)))
+}
+
+fn exceeds_max_date(datetime: DateTime<Utc>, max_date: Option<DateTime<Utc>>) -> bool {
+    max_date.is_some_and(|max_date| datetime > max_date)
+}
+
 fn get_local_naive_date_from_utc_datetime(date: DateTime<Utc>) -> NaiveDate {
     let local_date = date.with_timezone(&Local);
 
-
     NaiveDate::from_ymd_opt(local_date.year(), local_date.month(), local_date.day())
-            .unwrap_or_default()
+        .unwrap_or_default()
 }
 
 fn get_local_time_as_str(date: DateTime<Utc>) -> String {
@@ -110,3 +235,207 @@ fn get_local_time_as_str(date: DateTime<Utc>) -> String {
 
     initial_value
 }
+
+/// Quotes the field if it contains a delimiter, quote or line break (RFC 4180)
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Serializes the output as pretty JSON, with the warnings of the API in a top-level `warnings`
+/// array, so scripts see them without parsing stderr. Objects get the array when there are
+/// warnings. Other output, e.g. a list, is always wrapped as `{"items": ..., "warnings": [...]}`,
+/// so its shape doesn't depend on whether the API warned.
+pub fn to_json_with_warnings<T: Serialize>(
+    output: &T,
+    warnings: Vec<String>,
+) -> Result<String, serde_json::Error> {
+    let json = match serde_json::to_value(output)? {
+        serde_json::Value::Object(mut object) => {
+            if !warnings.is_empty() {
+                object.insert(String::from("warnings"), warnings.into());
+            }
+            serde_json::Value::Object(object)
+        }
+        items => serde_json::json!({ "items": items, "warnings": warnings }),
+    };
+
+    serde_json::to_string_pretty(&json)
+}
+
+/// Serializes each item as compact JSON on its own line (NDJSON), so the output can be streamed
+pub fn to_json_lines<T: Serialize>(items: &[T]) -> Result<String, serde_json::Error> {
+    let mut json_lines = String::new();
+    for item in items {
+        json_lines.push_str(&serde_json::to_string(item)?);
+        json_lines.push('\n');
+    }
+
+    Ok(json_lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        project::ProjectError,
+        sitt_client::{ClientError, EXIT_CONFLICT, EXIT_NOT_FOUND},
+    };
+    use chrono::FixedOffset;
+
+    #[test]
+    fn exit_code_follows_the_error_class() {
+        assert_eq!(
+            ClientError::Conflict(String::from("taken")).exit_code(),
+            EXIT_CONFLICT
+        );
+        assert_eq!(
+            ProjectError::NoProjectWithName(String::from("sitt")).exit_code(),
+            EXIT_NOT_FOUND
+        );
+        assert_eq!(String::from("Invalid date").exit_code(), EXIT_FAILURE);
+    }
+
+    #[test]
+    fn day_bounds_follow_the_local_timezone() {
+        let date = NaiveDate::from_ymd_opt(2024, 8, 1).unwrap();
+        let copenhagen_summer = FixedOffset::east_opt(2 * 3600).unwrap();
+
+        let (start, end) = day_bounds(date, &copenhagen_summer);
+
+        assert_eq!(start.to_rfc3339(), "2024-07-31T22:00:00+00:00");
+        assert_eq!(end.to_rfc3339(), "2024-08-01T22:00:00+00:00");
+    }
+
+    #[test]
+    fn day_bounds_west_of_utc_end_on_the_next_utc_day() {
+        let date = NaiveDate::from_ymd_opt(2024, 12, 31).unwrap();
+        let new_york_winter = FixedOffset::west_opt(5 * 3600).unwrap();
+
+        let (start, end) = day_bounds(date, &new_york_winter);
+
+        assert_eq!(start.to_rfc3339(), "2024-12-31T05:00:00+00:00");
+        assert_eq!(end.to_rfc3339(), "2025-01-01T05:00:00+00:00");
+    }
+
+    fn assert_humanized(delta: chrono::Duration, expected: &str) {
+        let now = Utc::now();
+        let humanized = humanize_relative_from(now - delta, now);
+
+        assert_eq!(
+            humanized, expected,
+            "Expected '{}' for {:?}, got '{}'",
+            expected, delta, humanized
+        );
+    }
+
+    #[test]
+    fn humanize_relative_just_now() {
+        assert_humanized(chrono::Duration::seconds(30), "just now");
+        assert_humanized(chrono::Duration::seconds(-30), "just now");
+    }
+
+    #[test]
+    fn humanize_relative_minutes() {
+        assert_humanized(chrono::Duration::minutes(1), "1 minute ago");
+        assert_humanized(chrono::Duration::minutes(5), "5 minutes ago");
+        assert_humanized(chrono::Duration::seconds(59 * 60 + 59), "59 minutes ago");
+    }
+
+    #[test]
+    fn humanize_relative_hours() {
+        assert_humanized(chrono::Duration::hours(1), "1 hour ago");
+        assert_humanized(chrono::Duration::hours(2), "2 hours ago");
+        assert_humanized(chrono::Duration::hours(23), "23 hours ago");
+    }
+
+    #[test]
+    fn humanize_relative_days() {
+        assert_humanized(chrono::Duration::hours(24), "yesterday");
+        assert_humanized(chrono::Duration::hours(47), "yesterday");
+        assert_humanized(chrono::Duration::days(3), "3 days ago");
+    }
+
+    #[test]
+    fn exceeds_max_date_rejects_future() {
+        let now = Utc::now();
+
+        assert!(
+            exceeds_max_date(now + chrono::Duration::minutes(5), Some(now)),
+            "Expected a date in the future to be rejected"
+        );
+        assert!(
+            !exceeds_max_date(now - chrono::Duration::hours(1), Some(now)),
+            "Expected a date in the past to be accepted"
+        );
+        assert!(
+            !exceeds_max_date(now, Some(now)),
+            "Expected the max date itself to be accepted"
+        );
+        assert!(
+            !exceeds_max_date(now + chrono::Duration::days(1), None),
+            "Expected any date to be accepted without a max date"
+        );
+    }
+
+    #[test]
+    fn humanize_relative_weeks_months_and_years() {
+        assert_humanized(chrono::Duration::days(7), "1 week ago");
+        assert_humanized(chrono::Duration::days(21), "3 weeks ago");
+        assert_humanized(chrono::Duration::days(60), "2 months ago");
+        assert_humanized(chrono::Duration::days(400), "1 year ago");
+    }
+
+    #[test]
+    fn json_lines_parse_independently() {
+        let items = vec![
+            serde_json::json!({ "name": "sitt", "comment": "line\nbreak" }),
+            serde_json::json!({ "name": "other", "total": 42 }),
+        ];
+
+        let json_lines = to_json_lines(&items).unwrap();
+
+        let lines: Vec<&str> = json_lines.lines().collect();
+        assert_eq!(lines.len(), 2, "Expected one line per item: {}", json_lines);
+        for (line, item) in lines.iter().zip(&items) {
+            let parsed: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert_eq!(&parsed, item);
+        }
+    }
+
+    #[test]
+    fn to_json_with_warnings_adds_the_warnings_to_the_output() {
+        let page = sitt_api::handlers::dtos::time_track_dtos::RecentTimeTracksDto {
+            time_tracks: Vec::new(),
+            next_cursor: Some(String::from("cursor")),
+        };
+
+        let json = to_json_with_warnings(&page, vec![String::from("Client is outdated")]).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            parsed,
+            serde_json::json!({
+                "time_tracks": [],
+                "next_cursor": "cursor",
+                "warnings": ["Client is outdated"]
+            })
+        );
+
+        let json = to_json_with_warnings(&page, Vec::new()).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert!(
+            parsed.get("warnings").is_none(),
+            "Expected no warnings array without warnings"
+        );
+
+        let json = to_json_with_warnings(&vec!["sitt"], Vec::new()).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            parsed,
+            serde_json::json!({ "items": ["sitt"], "warnings": [] })
+        );
+    }
+}
@@ -0,0 +1,218 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    process::exit,
+};
+
+use chrono::{DateTime, Utc};
+use etcetera::BaseStrategy;
+use sitt_api::{
+    handlers::dtos::time_track_dtos::TimeTrackDto, models::time_track_model::TimeTrackStatus,
+};
+use thiserror::Error;
+
+use crate::utils::ExitCode;
+
+const WATERMARK_FILE: &str = "sitt-export-watermarks.json";
+
+#[derive(Error, Debug)]
+pub enum WatermarkError {
+    #[error("Failed reading the export watermarks: {0}")]
+    Read(String),
+    #[error("Failed writing the export watermarks: {0}")]
+    Write(String),
+}
+
+impl ExitCode for WatermarkError {}
+
+/// The time of the last exported change, by project id
+pub type Watermarks = BTreeMap<String, DateTime<Utc>>;
+
+pub fn watermark_file_path() -> PathBuf {
+    etcetera::choose_base_strategy()
+        .unwrap_or_else(|err| {
+            eprintln!("Error: {}", err);
+            exit(1);
+        })
+        .cache_dir()
+        .join(WATERMARK_FILE)
+}
+
+pub fn load_watermarks(watermark_file_path: &Path) -> Result<Watermarks, WatermarkError> {
+    if !watermark_file_path.exists() {
+        return Ok(Watermarks::new());
+    }
+
+    let content = fs::read_to_string(watermark_file_path)
+        .map_err(|err| WatermarkError::Read(err.to_string()))?;
+
+    serde_json::from_str(&content).map_err(|err| WatermarkError::Read(err.to_string()))
+}
+
+pub fn save_watermarks(
+    watermark_file_path: &Path,
+    watermarks: &Watermarks,
+) -> Result<(), WatermarkError> {
+    if let Some(parent) = watermark_file_path.parent() {
+        fs::create_dir_all(parent).map_err(|err| WatermarkError::Write(err.to_string()))?;
+    }
+
+    let content = serde_json::to_string_pretty(watermarks)
+        .map_err(|err| WatermarkError::Write(err.to_string()))?;
+
+    fs::write(watermark_file_path, content).map_err(|err| WatermarkError::Write(err.to_string()))
+}
+
+// Time tracks have no modification time, so the latest of their creation, start and stop is used.
+// The creation time catches time added afterwards with a start and stop before the watermark.
+fn changed_at(time_track: &TimeTrackDto) -> DateTime<Utc> {
+    [
+        Some(time_track.created_at),
+        Some(time_track.started_at),
+        time_track.stopped_at,
+    ]
+    .into_iter()
+    .flatten()
+    .max()
+    .unwrap_or(time_track.started_at)
+}
+
+/// Selects the finished time tracks changed after the watermark, or all of them without one.
+/// Time tracks in progress are left out, so they are exported once they are finished.
+pub fn select_since(
+    time_tracks: Vec<TimeTrackDto>,
+    watermark: Option<DateTime<Utc>>,
+) -> Vec<TimeTrackDto> {
+    time_tracks
+        .into_iter()
+        .filter(|t| t.status == TimeTrackStatus::Finished)
+        .filter(|t| watermark.is_none_or(|watermark| changed_at(t) > watermark))
+        .collect()
+}
+
+/// Moves the watermark to the latest change among the exported time tracks.
+/// The server's timestamps are used instead of the local clock, so clock skew can not skip entries.
+pub fn advance(
+    watermark: Option<DateTime<Utc>>,
+    exported: &[TimeTrackDto],
+) -> Option<DateTime<Utc>> {
+    exported.iter().map(changed_at).chain(watermark).max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn time_track(id: &str, started_at: &str, stopped_at: Option<&str>) -> TimeTrackDto {
+        let started_at: DateTime<Utc> = started_at.parse().unwrap();
+        let stopped_at: Option<DateTime<Utc>> = stopped_at.map(|s| s.parse().unwrap());
+        TimeTrackDto {
+            time_track_id: id.to_string(),
+            project_id: String::from("project"),
+            project_name: String::from("Project"),
+            status: match stopped_at {
+                Some(_) => TimeTrackStatus::Finished,
+                None => TimeTrackStatus::InProgress,
+            },
+            started_at,
+            stopped_at,
+            total_duration: String::from("1h"),
+            comment: None,
+            notes: Vec::new(),
+            created_at: stopped_at.unwrap_or(started_at),
+            created_by: String::from("user_id"),
+        }
+    }
+
+    fn ids(time_tracks: &[TimeTrackDto]) -> Vec<&str> {
+        time_tracks
+            .iter()
+            .map(|t| t.time_track_id.as_str())
+            .collect()
+    }
+
+    fn time_tracks() -> Vec<TimeTrackDto> {
+        vec![
+            time_track("old", "2024-08-01T08:00:00Z", Some("2024-08-01T09:00:00Z")),
+            time_track("new", "2024-08-02T08:00:00Z", Some("2024-08-02T09:00:00Z")),
+            // Started before the watermark, but stopped after it
+            time_track(
+                "spanning",
+                "2024-08-01T10:00:00Z",
+                Some("2024-08-02T10:00:00Z"),
+            ),
+            time_track("running", "2024-08-02T11:00:00Z", None),
+            // Added after the watermark for time worked before it
+            TimeTrackDto {
+                created_at: "2024-08-02T12:00:00Z".parse().unwrap(),
+                ..time_track(
+                    "backdated",
+                    "2024-07-31T08:00:00Z",
+                    Some("2024-07-31T09:00:00Z"),
+                )
+            },
+        ]
+    }
+
+    #[test]
+    fn select_since_only_selects_finished_changes_after_the_watermark() {
+        let watermark = "2024-08-01T09:00:00Z".parse().ok();
+
+        let selected = select_since(time_tracks(), watermark);
+
+        assert_eq!(ids(&selected), vec!["new", "spanning", "backdated"]);
+    }
+
+    #[test]
+    fn select_since_without_watermark_selects_all_finished() {
+        let selected = select_since(time_tracks(), None);
+
+        assert_eq!(ids(&selected), vec!["old", "new", "spanning", "backdated"]);
+    }
+
+    #[test]
+    fn watermark_advances_to_the_latest_exported_change() {
+        let watermark = "2024-08-01T09:00:00Z".parse().ok();
+        let exported = select_since(time_tracks(), watermark);
+
+        let advanced = advance(watermark, &exported);
+
+        assert_eq!(advanced, "2024-08-02T12:00:00Z".parse().ok());
+        assert!(
+            select_since(time_tracks(), advanced).is_empty(),
+            "Expected nothing to be exported twice"
+        );
+        assert_eq!(
+            advance(advanced, &[]),
+            advanced,
+            "Expected the watermark to stay when nothing was exported"
+        );
+    }
+
+    #[test]
+    fn watermarks_are_saved_per_project() {
+        // Unique per run, so parallel or earlier runs can't leave a file behind to read
+        let path = env::temp_dir().join(format!(
+            "sitt-test-watermarks-{}-{}.json",
+            std::process::id(),
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        assert!(load_watermarks(&path).unwrap().is_empty());
+
+        let mut watermarks = Watermarks::new();
+        watermarks.insert(
+            String::from("project_1"),
+            "2024-08-01T09:00:00Z".parse().unwrap(),
+        );
+        watermarks.insert(
+            String::from("project_2"),
+            "2024-08-02T09:00:00Z".parse().unwrap(),
+        );
+        save_watermarks(&path, &watermarks).unwrap();
+
+        assert_eq!(load_watermarks(&path).unwrap(), watermarks);
+        fs::remove_file(&path).unwrap();
+    }
+}